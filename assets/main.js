@@ -5,6 +5,10 @@ document.addEventListener("DOMContentLoaded", function () {
 	initPagination();
 	initCsv();
 	initSlider();
+	initSuggest();
+	initSimilares();
+	initDescripcion();
+	initCampos();
 });
 
 function initHistorial() {
@@ -156,6 +160,25 @@ function initForm() {
 	document.getElementById("strategy").value = searchConfig.strategy;
 	document.getElementById("age_min").value = searchConfig.edad_min;
 	document.getElementById("age_max").value = searchConfig.edad_max;
+	document.getElementById("bm25").value = searchConfig.bm25 || "";
+	document.getElementById("sinonimos").value = searchConfig.sinonimos || "true";
+	document.getElementById("normalizacion").value =
+		searchConfig.normalizacion || "MinMax";
+	document.getElementById("rerank").checked = searchConfig.rerank === "true";
+	document.getElementById("rerank_n").value = searchConfig.rerank_n || "";
+	document.getElementById("mmr").value = searchConfig.mmr || "";
+	document.getElementById("puntaje_minimo").value =
+		searchConfig.puntaje_minimo || "";
+	document.getElementById("distancia_maxima").value =
+		searchConfig.distancia_maxima || "";
+	document.getElementById("codo").checked = searchConfig.codo === "true";
+	for (const campo of ["estudios", "experiencia", "descripcion"]) {
+		document.getElementById(`peso_${campo}`).value =
+			searchConfig[`peso_${campo}`] || 0;
+	}
+	document.getElementById("descripcion").checked =
+		searchConfig.descripcion === "true";
+	document.getElementById("descripcion-input").value = searchConfig.query;
 
 	document.getElementById("balanceSlider").value = searchConfig.peso_fts || 50;
 	document.getElementById("value1Display").textContent = searchConfig.peso_fts;
@@ -215,3 +238,110 @@ function initSlider() {
 
 	balance_slider.addEventListener("input", updateValues);
 }
+
+/**
+ * Completa las sugerencias del input de búsqueda con las respuestas de '/suggest' mientras se
+ * escribe.
+ */
+function initSuggest() {
+	const input = document.getElementById("search-input");
+	const datalist = document.getElementById("search-suggestions");
+
+	if (!input || !datalist) {
+		return;
+	}
+
+	let timeout;
+	let controller;
+
+	input.addEventListener("input", () => {
+		clearTimeout(timeout);
+		timeout = setTimeout(async () => {
+			if (controller) {
+				controller.abort();
+			}
+			controller = new AbortController();
+
+			const q = input.value;
+			if (q.trim() === "") {
+				datalist.replaceChildren();
+				return;
+			}
+
+			try {
+				const response = await fetch(
+					`/suggest?q=${encodeURIComponent(q)}`,
+					{ signal: controller.signal },
+				);
+				if (!response.ok) {
+					return;
+				}
+				const completions = await response.json();
+				datalist.replaceChildren(
+					...completions.map((completion) => {
+						const option = document.createElement("option");
+						option.value = completion.texto;
+						return option;
+					}),
+				);
+			} catch (err) {
+				if (err.name !== "AbortError") {
+					console.error(err);
+				}
+			}
+		}, 150);
+	});
+}
+
+/**
+ * Agrega los parámetros de la búsqueda actual a los enlaces de perfiles similares, para que se
+ * apliquen los mismos filtros.
+ */
+function initSimilares() {
+	const links = document.querySelectorAll(".similares-link");
+	links.forEach((link) => {
+		link.href = link.getAttribute("href") + window.location.search;
+	});
+}
+
+/**
+ * Alterna entre el input de búsqueda y el textarea para pegar una descripción de puesto. Solo
+ * uno de los dos está habilitado, para que se envíe un único 'query'.
+ */
+function initDescripcion() {
+	const checkbox = document.getElementById("descripcion");
+	const input = document.getElementById("search-input");
+	const textarea = document.getElementById("descripcion-input");
+
+	if (!checkbox || !input || !textarea) {
+		return;
+	}
+
+	const update = () => {
+		input.hidden = input.disabled = checkbox.checked;
+		textarea.hidden = textarea.disabled = !checkbox.checked;
+	};
+
+	checkbox.addEventListener("change", update);
+	update();
+}
+
+/**
+ * Muestra el valor de los sliders de pesos por campo.
+ */
+function initCampos() {
+	const sliders = document.querySelectorAll(".campo-slider");
+	sliders.forEach((slider) => {
+		const display = document.getElementById(`${slider.id}_display`);
+		if (!display) {
+			return;
+		}
+
+		const update = () => {
+			display.textContent = slider.value;
+		};
+
+		slider.addEventListener("input", update);
+		update();
+	});
+}
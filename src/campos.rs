@@ -0,0 +1,235 @@
+use std::fmt::Display;
+
+use rusqlite::Connection;
+use zerocopy::IntoBytes;
+
+use crate::{hnsw::Indice, metrica::Metrica, openai, sqlite};
+
+/// Columnas de `tnea` que pueden tener un embedding propio, además del de `template`. Cada una se
+/// guarda en la tabla `vec_{campo}`.
+pub const CAMPOS: [&str; 3] = ["estudios", "experiencia", "descripcion"];
+
+/// Clave de `metadata` que indica si `sync_vec_tnea` también genera los embeddings por campo.
+pub const METADATA_CAMPOS: &str = "embeddings_por_campo";
+
+/// Crea las tablas `vec_{campo}` con la misma dimensión y métrica que `vec_tnea`, y los triggers que
+/// eliminan los embeddings de un registro cuando cambia su template o se borra. Los cambios de
+/// template encolan al registro en `embedding_queue`, y al generar su embedding se generan también
+/// los de sus campos.
+#[must_use]
+pub fn create_vec_campos(dimensiones: usize, metrica: Metrica) -> String {
+    CAMPOS
+        .iter()
+        .map(|campo| {
+            format!(
+                "
+                create virtual table if not exists vec_{campo} using vec0(
+                    row_id integer primary key,
+                    embedding float[{dimensiones}] {}
+                );
+
+                create trigger if not exists tnea_vec_{campo}_update after update of template on tnea
+                when old.template is not new.template begin
+                    delete from vec_{campo} where row_id = new.id;
+                end;
+
+                create trigger if not exists tnea_vec_{campo}_delete after delete on tnea begin
+                    delete from vec_{campo} where row_id = old.id;
+                end;
+                ",
+                metrica.opcion()
+            )
+        })
+        .collect()
+}
+
+/// Peso de cada campo en la distancia semántica, entre 0 y 1. El peso restante corresponde al
+/// embedding de `template`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PesosCampos {
+    pub estudios: f32,
+    pub experiencia: f32,
+    pub descripcion: f32,
+}
+
+impl PesosCampos {
+    /// Crea los pesos a partir de los valores de los sliders, entre 0 y 100.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si algún peso está fuera de rango o si entre todos superan 100.
+    pub fn new(estudios: f32, experiencia: f32, descripcion: f32) -> Result<Self, String> {
+        for (campo, peso) in CAMPOS.iter().zip([estudios, experiencia, descripcion]) {
+            if !(0.0..=100.0).contains(&peso) {
+                return Err(format!("El peso de `{campo}` debe estar entre 0 y 100."));
+            }
+        }
+
+        if estudios + experiencia + descripcion > 100.0 {
+            return Err("Los pesos de los campos no pueden sumar más de 100.".to_string());
+        }
+
+        Ok(Self {
+            estudios: estudios / 100.0,
+            experiencia: experiencia / 100.0,
+            descripcion: descripcion / 100.0,
+        })
+    }
+
+    /// Peso del embedding de `template`.
+    #[must_use]
+    pub fn template(&self) -> f32 {
+        (1.0 - self.estudios - self.experiencia - self.descripcion).max(0.0)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.campos().next().is_none()
+    }
+
+    /// Los campos con peso mayor a 0.
+    fn campos(&self) -> impl Iterator<Item = (&'static str, f32)> {
+        CAMPOS
+            .into_iter()
+            .zip([self.estudios, self.experiencia, self.descripcion])
+            .filter(|(_, peso)| *peso > 0.0)
+    }
+
+    /// Consulta que devuelve `row_id` y `distance` de los vecinos más cercanos a `:embedding`,
+    /// para usarla en lugar de la búsqueda KNN sobre `vec_tnea`.
+    ///
+    /// Sin pesos por campo es la búsqueda sobre `vec_tnea`, ver [`Indice::knn_sql`]. Con
+    /// pesos, la distancia es el promedio ponderado de las distancias del template y de cada
+    /// campo. Los candidatos son los `k` vecinos del template, y a los que no están entre los
+    /// vecinos de un campo, por ejemplo porque no tienen experiencia, se les asigna la mayor
+    /// distancia encontrada en ese campo.
+    #[must_use]
+    pub fn vec_matches_sql(&self, k: &str, indice: Indice, metrica: Metrica) -> String {
+        let template = indice.knn_sql(k, metrica);
+
+        if self.is_empty() {
+            return template;
+        }
+
+        // Se materializan para que SQLite no combine `max(distance)` con la consulta KNN de vec0,
+        // que solamente admite ordenar por distancia de forma ascendente.
+        let mut ctes = vec![format!("knn_template as materialized ({template})")];
+        let mut distancia = vec![format!("{} * knn_template.distance", self.template())];
+        let mut joins = Vec::new();
+
+        for (campo, peso) in self.campos() {
+            ctes.push(format!(
+                "knn_{campo} as materialized (
+                    select row_id, distance from vec_{campo}
+                    where embedding match :embedding and k = {k}
+                )"
+            ));
+            distancia.push(format!(
+                "{peso} * coalesce(knn_{campo}.distance, (select max(distance) from knn_{campo}), 0)"
+            ));
+            joins.push(format!(
+                "left join knn_{campo} on knn_{campo}.row_id = knn_template.row_id"
+            ));
+        }
+
+        format!(
+            "with {}
+            select knn_template.row_id, {} as distance
+            from knn_template
+            {}",
+            ctes.join(",\n"),
+            distancia.join(" + "),
+            joins.join("\n")
+        )
+    }
+}
+
+impl Display for PesosCampos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "template={:.2}", self.template())?;
+        for (campo, peso) in self.campos() {
+            write!(f, ", {campo}={peso:.2}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Genera los embeddings de cada campo para los registros que todavía no tienen uno. Los
+/// registros con el campo vacío se omiten.
+pub async fn sync_vec_campos(
+    db: &Connection,
+    dimensiones: usize,
+    client: &reqwest::Client,
+) -> eyre::Result<()> {
+    for campo in CAMPOS {
+        let start = std::time::Instant::now();
+
+        let pendientes: Vec<(u64, String)> = db
+            .prepare(&format!(
+                "select id, {campo} from tnea
+                where trim(coalesce({campo}, '')) != ''
+                and id not in (select row_id from vec_{campo})"
+            ))?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        tracing::info!(
+            "Generando embeddings de `{campo}` para {} registros...",
+            pendientes.len()
+        );
+
+        for chunk in pendientes.chunks(sqlite::CHUNK_COLA) {
+            let (indices, textos): (Vec<u64>, Vec<String>) = chunk.iter().cloned().unzip();
+            let embeddings = openai::embed_vec(indices, textos, dimensiones, client).await?;
+
+            let tx = db.unchecked_transaction()?;
+            for (id, embedding) in embeddings {
+                tx.execute(
+                    &format!("insert into vec_{campo}(row_id, embedding) values (?, ?)"),
+                    rusqlite::params![id, embedding.as_bytes()],
+                )?;
+            }
+            tx.commit()?;
+        }
+
+        tracing::info!(
+            "Generando embeddings de `{campo}`... listo! tomó {} ms",
+            start.elapsed().as_millis()
+        );
+    }
+
+    Ok(())
+}
+
+/// Los campos con texto de un registro, a partir de sus textos en el orden de [`CAMPOS`]. Los
+/// campos vacíos no tienen embedding, igual que en [`sync_vec_campos`].
+#[must_use]
+pub fn con_texto(textos: [String; 3]) -> Vec<(&'static str, String)> {
+    CAMPOS
+        .into_iter()
+        .zip(textos)
+        .filter(|(_, texto)| !texto.trim().is_empty())
+        .collect()
+}
+
+/// Reemplaza los embeddings por campo de un registro. Los campos que no están en `embeddings`
+/// quedan sin embedding.
+pub fn reemplazar(db: &Connection, id: u64, embeddings: &[(&str, Vec<f32>)]) -> eyre::Result<()> {
+    for campo in CAMPOS {
+        db.execute(&format!("delete from vec_{campo} where row_id = ?"), [id])?;
+    }
+
+    for (campo, embedding) in embeddings {
+        db.execute(
+            &format!("insert into vec_{campo}(row_id, embedding) values (?, ?)"),
+            rusqlite::params![id, embedding.as_bytes()],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Si la base de datos se sincronizó con embeddings por campo.
+pub fn habilitados(db: &Connection) -> eyre::Result<bool> {
+    Ok(sqlite::get_metadata(db, METADATA_CAMPOS)?.as_deref() == Some("true"))
+}
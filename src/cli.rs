@@ -1,6 +1,12 @@
-use std::net::IpAddr;
+use std::{net::IpAddr, path::PathBuf};
 
-use clap::{command, Parser, Subcommand, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::{
+    cuantizacion::Cuantizacion,
+    fts::{Bm25Weights, FtsTokenizer},
+    metrica::Metrica,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -24,6 +30,41 @@ pub enum Commands {
 
         #[arg(value_enum, short = 'C', long, default_value_t = Cache::Disabled)]
         cache: Cache,
+
+        /// Cada cuántos segundos se generan los embeddings de los registros encolados.
+        #[arg(long, default_value_t = 10)]
+        queue_interval: u64,
+
+        /// Pesos de cada columna de fts_tnea al calcular `bm25`, por ejemplo `experiencia=3`.
+        /// Las columnas no indicadas conservan su peso por defecto.
+        #[arg(long, default_value = "")]
+        bm25: Bm25Weights,
+
+        /// Archivo con el diccionario de sinónimos. Se crea al guardar reglas desde
+        /// `PUT /sinonimos` si no existe.
+        #[arg(long, default_value = "sinonimos.txt")]
+        sinonimos: PathBuf,
+
+        /// Directorio del cross-encoder para re-ordenar los primeros resultados, con
+        /// `config.json`, `tokenizer.json` y `model.safetensors`.
+        #[cfg(feature = "local")]
+        #[arg(long)]
+        reranker: Option<PathBuf>,
+
+        /// Cantidad de resultados que re-ordena el cross-encoder si la búsqueda no indica otra.
+        #[cfg(feature = "local")]
+        #[arg(long, default_value_t = 50)]
+        reranker_top_n: usize,
+
+        /// Tamaño de la lista de candidatos del índice HNSW. Con un valor mayor la búsqueda es
+        /// más precisa pero más lenta.
+        #[arg(long, default_value_t = 100)]
+        hnsw_ef: usize,
+
+        /// Cantidad mínima de vectores para usar el índice HNSW, con menos se usa la búsqueda
+        /// exacta.
+        #[arg(long, default_value_t = 10_000)]
+        hnsw_min_registros: usize,
     },
     /// Actualiza las bases de datos
     Sync {
@@ -37,6 +78,41 @@ pub enum Commands {
         /// Determina si utilizar un modelo local (actualmente es distilBERT) o remoto (Actualmente solo es "text-embedding-3-small").
         #[arg(value_enum, short = 'M', long, default_value_t = Model::OpenAI)]
         model: Model,
+
+        /// Genera también embeddings separados de `estudios`, `experiencia` y `descripcion`, que
+        /// se pueden ponderar en cada búsqueda. Queda habilitado para las próximas sincronizaciones.
+        #[arg(long, default_value = "false")]
+        campos: bool,
+
+        /// Cuantización de los vectores sobre los que se hace la primera pasada de la búsqueda
+        /// semántica. Queda configurada para las próximas sincronizaciones.
+        #[arg(value_enum, long)]
+        cuantizacion: Option<Cuantizacion>,
+
+        /// Dimensión de los embeddings, menor o igual a la del modelo. Por defecto se conserva la
+        /// de `vec_tnea` o, si no existe, se usa la del modelo. Cambiarla requiere `--force`.
+        #[arg(short = 'D', long)]
+        dimensiones: Option<usize>,
+
+        /// Métrica de distancia de las tablas de vectores. Cambiarla recrea las tablas copiando
+        /// los vectores, sin volver a generar los embeddings. Queda configurada para las próximas
+        /// sincronizaciones.
+        #[arg(value_enum, long)]
+        metrica: Option<Metrica>,
+
+        /// Construye también un índice HNSW de `vec_tnea`, que se guarda junto a la base de datos
+        /// y se usa en `serve` para buscar los vecinos aproximados. Queda habilitado para las
+        /// próximas sincronizaciones.
+        #[arg(long, default_value = "false")]
+        hnsw: bool,
+
+        /// Máxima cantidad de vecinos de cada nodo del índice HNSW.
+        #[arg(long, default_value_t = 16)]
+        hnsw_m: usize,
+
+        /// Tamaño de la lista de candidatos al construir el índice HNSW.
+        #[arg(long, default_value_t = 200)]
+        hnsw_ef_construccion: usize,
     },
 
     /// Genera un embedding en base a una input
@@ -47,6 +123,66 @@ pub enum Commands {
         /// Determina si utilizar un modelo local (actualmente es distilBERT) o remoto (actualmente solo es "text-embedding-3-small").
         #[arg(value_enum, long, default_value_t = Model::OpenAI)]
         model: Model,
+        /// Dimensión del embedding, por defecto la del modelo.
+        #[arg(short = 'D', long)]
+        dimensiones: Option<usize>,
+    },
+
+    /// Herramientas para inspeccionar y reparar la base de datos
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+
+    /// Mantenimiento del índice de búsqueda de texto completo `fts_tnea`
+    Fts {
+        #[command(subcommand)]
+        command: FtsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum FtsCommands {
+    /// Reconstruye el índice a partir del contenido de la tabla `tnea`.
+    Rebuild,
+    /// Verifica que el índice sea consistente con la tabla `tnea`.
+    IntegrityCheck,
+    /// Fusiona incrementalmente los segmentos del índice.
+    Merge {
+        /// Cantidad aproximada de páginas a escribir en la fusión.
+        #[arg(long, default_value_t = 500)]
+        pages: i64,
+    },
+    /// Configura la fusión automática de segmentos del índice.
+    Automerge {
+        /// Segmentos por nivel antes de fusionarlos, 0 la desactiva.
+        #[arg(default_value_t = 4)]
+        segments: u8,
+    },
+    /// Cambia el tokenizer del índice, recreando la tabla `fts_tnea`.
+    Tokenizer {
+        #[arg(value_enum)]
+        tokenizer: FtsTokenizer,
+
+        /// Determina si se eliminan los acentos y diacríticos de los tokens (0, 1 o 2).
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=2))]
+        remove_diacritics: u8,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Muestra la cantidad de registros, el uso de páginas y las versiones de la base de datos.
+    Stats,
+    /// Busca inconsistencias entre `tnea`, `fts_tnea` y `vec_tnea`.
+    Check {
+        /// Repara las inconsistencias que se puedan resolver automáticamente.
+        #[arg(long, default_value = "false")]
+        repair: bool,
+
+        /// Modelo a utilizar para generar los embeddings faltantes durante la reparación.
+        #[arg(value_enum, short = 'M', long, default_value_t = Model::OpenAI)]
+        model: Model,
     },
 }
 
@@ -57,13 +193,46 @@ pub enum SyncStrategy {
     All,
 }
 
-#[derive(Clone, ValueEnum)]
+#[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum Model {
     OpenAI,
     #[cfg(feature = "local")]
     Local,
 }
 
+impl Model {
+    /// Nombre con el que se registra el modelo en la tabla `metadata`.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::OpenAI => "text-embedding-3-small",
+            #[cfg(feature = "local")]
+            Model::Local => "local",
+        }
+    }
+
+    /// Modelo registrado con `name` en la tabla `metadata`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text-embedding-3-small" => Some(Model::OpenAI),
+            #[cfg(feature = "local")]
+            "local" => Some(Model::Local),
+            _ => None,
+        }
+    }
+
+    /// Dimensión completa de los embeddings que genera el modelo.
+    #[must_use]
+    pub fn dimensions(&self) -> usize {
+        match self {
+            Model::OpenAI => 1536,
+            #[cfg(feature = "local")]
+            Model::Local => 512,
+        }
+    }
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum Cache {
     Enabled,
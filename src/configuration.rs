@@ -1,18 +1,44 @@
-use std::net::IpAddr;
+use std::{net::IpAddr, path::PathBuf, time::Duration};
 
-use crate::cli::Cache;
+use crate::{cli::Cache, fts::Bm25Weights, hnsw::HnswSettings};
 
 #[derive(Debug, Clone)]
 pub struct ApplicationSettings {
     pub port: u16,
     pub host: IpAddr,
     pub cache: Cache,
+    pub queue_interval: Duration,
+    pub template: Template,
+    pub bm25: Bm25Weights,
+    pub sinonimos: PathBuf,
+    pub hnsw: HnswSettings,
+    #[cfg(feature = "local")]
+    pub reranker: Option<crate::reranker::RerankerSettings>,
 }
 
 impl ApplicationSettings {
     #[must_use]
-    pub fn new(port: u16, host: IpAddr, cache: Cache) -> Self {
-        Self { port, host, cache }
+    pub fn new(
+        port: u16,
+        host: IpAddr,
+        cache: Cache,
+        queue_interval: Duration,
+        template: Template,
+        bm25: Bm25Weights,
+        sinonimos: PathBuf,
+    ) -> Self {
+        Self {
+            port,
+            host,
+            cache,
+            queue_interval,
+            template,
+            bm25,
+            sinonimos,
+            hnsw: HnswSettings::default(),
+            #[cfg(feature = "local")]
+            reranker: None,
+        }
     }
 }
 
@@ -0,0 +1,127 @@
+/// Cantidad mínima de resultados para buscar un codo. Con menos, no hay una curva de puntajes.
+const MIN_RESULTADOS_CODO: usize = 3;
+
+/// Separación mínima entre la curva de puntajes normalizada y la recta que une sus extremos para
+/// considerar que hay un codo. Con valores menores se corta también en curvas casi rectas.
+pub const SENSIBILIDAD_CODO: f32 = 0.15;
+
+/// Descarta los resultados que no cumplen con el umbral, conservando el orden del resto.
+///
+/// Devuelve la cantidad de resultados que se descartaron.
+pub fn umbral<T>(rows: &mut Vec<T>, cumple: impl Fn(&T) -> bool) -> usize {
+    let antes = rows.len();
+    rows.retain(cumple);
+    antes - rows.len()
+}
+
+/// Punto de la lista de resultados donde se cortó por codo.
+#[derive(Debug, Clone, Copy)]
+pub struct Codo {
+    /// Cantidad de resultados que se conservaron.
+    pub conservados: usize,
+    /// Cantidad de resultados que se descartaron.
+    pub descartados: usize,
+    /// Puntaje del último resultado que se conservó.
+    pub puntaje: f32,
+}
+
+/// Corta la lista de resultados donde los puntajes caen bruscamente, con el método Kneedle: se
+/// normalizan las posiciones y los puntajes entre 0 y 1 y el codo es el resultado más alejado
+/// de la recta que une al primero con el último. Si la lista cae de golpe y luego se aplana, el
+/// codo es el primer resultado después de la caída, y desde él la lista es la cola de
+/// coincidencias débiles.
+///
+/// `relevancia` debe ser mayor para los resultados más relevantes y la lista debe estar ordenada
+/// de mayor a menor relevancia. Si la curva no se separa de la recta al menos
+/// [`SENSIBILIDAD_CODO`], no se corta.
+pub fn codo<T>(rows: &mut Vec<T>, relevancia: impl Fn(&T) -> f32) -> Option<Codo> {
+    let n = rows.len();
+    if n < MIN_RESULTADOS_CODO {
+        return None;
+    }
+
+    let puntajes: Vec<f32> = rows.iter().map(relevancia).collect();
+    let (primero, ultimo) = (puntajes[0], puntajes[n - 1]);
+    let rango = primero - ultimo;
+    if rango <= f32::EPSILON {
+        return None;
+    }
+
+    let (posicion, separacion) = puntajes
+        .iter()
+        .enumerate()
+        .map(|(i, puntaje)| {
+            let x = i as f32 / (n - 1) as f32;
+            let y = (puntaje - ultimo) / rango;
+            (i, (1.0 - x) - y)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    if separacion < SENSIBILIDAD_CODO {
+        return None;
+    }
+
+    // La separación del primer resultado es 0, por lo que se conserva al menos uno.
+    let conservados = posicion;
+    rows.truncate(conservados);
+
+    Some(Codo {
+        conservados,
+        descartados: n - conservados,
+        puntaje: puntajes[posicion - 1],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn umbral_conserva_el_orden() {
+        let mut rows = vec![0.9, 0.2, 0.7, 0.1, 0.5];
+
+        assert_eq!(umbral(&mut rows, |puntaje| *puntaje >= 0.5), 2);
+        assert_eq!(rows, [0.9, 0.7, 0.5]);
+    }
+
+    #[test]
+    fn codo_despues_de_una_caida() {
+        let mut rows = vec![1.0, 0.95, 0.9, 0.2, 0.1, 0.05];
+        let codo = codo(&mut rows, |puntaje| *puntaje).unwrap();
+
+        assert_eq!(codo.conservados, 3);
+        assert_eq!(codo.descartados, 3);
+        assert_eq!(codo.puntaje, 0.9);
+        assert_eq!(rows, [1.0, 0.95, 0.9]);
+    }
+
+    #[test]
+    fn codo_conserva_al_menos_uno() {
+        let mut rows = vec![1.0, 0.2, 0.15, 0.1, 0.05];
+        let codo = codo(&mut rows, |puntaje| *puntaje).unwrap();
+
+        assert_eq!(codo.conservados, 1);
+        assert_eq!(codo.descartados, 4);
+        assert_eq!(codo.puntaje, 1.0);
+        assert_eq!(rows, [1.0]);
+    }
+
+    #[test]
+    fn sin_codo() {
+        // Pocos resultados.
+        let mut rows = vec![1.0, 0.1];
+        assert!(codo(&mut rows, |puntaje| *puntaje).is_none());
+
+        // Todos los puntajes iguales.
+        let mut rows = vec![0.5; 10];
+        assert!(codo(&mut rows, |puntaje| *puntaje).is_none());
+
+        // Una recta, o una curva que se separa menos que la sensibilidad.
+        let mut rows = vec![4.0, 3.0, 2.0, 1.0];
+        assert!(codo(&mut rows, |puntaje| *puntaje).is_none());
+        let mut rows = vec![1.0, 0.85, 0.75, 0.6, 0.5];
+        assert!(codo(&mut rows, |puntaje| *puntaje).is_none());
+
+        assert_eq!(rows.len(), 5);
+    }
+}
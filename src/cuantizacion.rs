@@ -0,0 +1,361 @@
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use clap::ValueEnum;
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{metrica::Metrica, sqlite};
+
+/// Clave de `metadata` con la cuantización elegida al sincronizar.
+pub const METADATA_CUANTIZACION: &str = "cuantizacion";
+
+/// Cantidad de vecinos que se comparan al calcular el recall en `db stats`.
+const K_RECALL: usize = 10;
+/// Cantidad de registros de `vec_tnea` que se usan como consultas al calcular el recall.
+const CONSULTAS_RECALL: usize = 20;
+/// Mayor `k` que admite una consulta KNN de vec0.
+const MAX_K_VEC0: usize = 4096;
+
+/// Cómo se guardan los vectores sobre los que se hace la primera pasada de la búsqueda KNN.
+///
+/// Los vectores cuantizados se guardan en `vec_cuantizado` y se generan a partir de los de
+/// `vec_tnea`, que se conservan para re-calcular la distancia exacta de los candidatos. De esta
+/// forma la distancia que se muestra y se combina con otras búsquedas siempre es la de los
+/// vectores `float`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Cuantizacion {
+    /// Sin cuantización, la búsqueda recorre los vectores `float[]` de `vec_tnea`.
+    #[default]
+    Float,
+    /// Un byte por dimensión con `int8[]`, cuatro veces más chico que `float[]`.
+    Int8,
+    /// Un bit por dimensión con `bit[]`, 32 veces más chico que `float[]`. La distancia es la de
+    /// Hamming, por lo que la primera pasada es menos precisa.
+    Bit,
+}
+
+impl Display for Cuantizacion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cuantizacion::Float => write!(f, "float"),
+            Cuantizacion::Int8 => write!(f, "int8"),
+            Cuantizacion::Bit => write!(f, "bit"),
+        }
+    }
+}
+
+impl FromStr for Cuantizacion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "float" => Ok(Self::Float),
+            "int8" => Ok(Self::Int8),
+            "bit" => Ok(Self::Bit),
+            other => Err(format!("`{other}` no es una cuantización válida.")),
+        }
+    }
+}
+
+impl Cuantizacion {
+    /// Lee la cuantización registrada en `metadata`. Si no hay ninguna, no se cuantiza.
+    pub fn from_db(db: &Connection) -> eyre::Result<Self> {
+        match sqlite::get_metadata(db, METADATA_CUANTIZACION)? {
+            Some(valor) => valor.parse().map_err(|err: String| eyre::eyre!(err)),
+            None => Ok(Self::Float),
+        }
+    }
+
+    /// Tipo de la columna de `vec_cuantizado`. Los vectores `bit[]` siempre usan la distancia
+    /// de Hamming.
+    fn columna(self, dimensiones: usize, metrica: Metrica) -> Option<String> {
+        match self {
+            Cuantizacion::Float => None,
+            Cuantizacion::Int8 => Some(format!("int8[{dimensiones}] {}", metrica.opcion())),
+            Cuantizacion::Bit => Some(format!("bit[{dimensiones}]")),
+        }
+    }
+
+    /// Cuántos candidatos de la primera pasada se re-puntúan por cada resultado pedido.
+    fn sobremuestreo(self) -> usize {
+        match self {
+            Cuantizacion::Float => 1,
+            Cuantizacion::Int8 => 2,
+            Cuantizacion::Bit => 8,
+        }
+    }
+
+    /// Expresión SQL que convierte `vector`, un `float[]`, a la cuantización.
+    fn cuantizar(self, vector: &str) -> String {
+        match self {
+            Cuantizacion::Float => vector.to_string(),
+            Cuantizacion::Int8 => format!("vec_quantize_int8({vector}, 'unit')"),
+            Cuantizacion::Bit => format!("vec_quantize_binary({vector})"),
+        }
+    }
+
+    /// Expresión SQL que marca el blob `vector` como un vector de la cuantización. SQLite pierde
+    /// el subtipo que indica el tipo de vector cuando el valor pasa por una tabla o un parámetro.
+    fn tipo(self, vector: &str) -> String {
+        match self {
+            Cuantizacion::Float => vector.to_string(),
+            Cuantizacion::Int8 => format!("vec_int8({vector})"),
+            Cuantizacion::Bit => format!("vec_bit({vector})"),
+        }
+    }
+
+    /// Consulta que devuelve `row_id` y `distance` de los `k` vecinos de `vec_tnea` más cercanos
+    /// a `:embedding` según la métrica. Con cuantización, se buscan `k` por el sobremuestreo
+    /// candidatos en `vec_cuantizado` y se ordenan por su distancia exacta con los vectores
+    /// `float[]`.
+    #[must_use]
+    pub fn knn_sql(self, k: &str, metrica: Metrica) -> String {
+        match self {
+            Cuantizacion::Float => format!(
+                "select row_id, distance from vec_tnea
+                where template_embedding match :embedding and k = {k}"
+            ),
+            _ => format!(
+                "select
+                    vec_tnea.row_id,
+                    {} as distance
+                from (
+                    select row_id from vec_cuantizado
+                    where embedding match {} and k = min({k} * {}, {MAX_K_VEC0})
+                ) as candidatos
+                join vec_tnea on vec_tnea.row_id = candidatos.row_id
+                order by distance
+                limit {k}",
+                metrica.distancia_entre("vec_tnea.template_embedding", ":embedding"),
+                self.cuantizar(":embedding"),
+                self.sobremuestreo()
+            ),
+        }
+    }
+}
+
+/// Recrea `vec_cuantizado` con la cuantización indicada, la registra en `metadata` y cuantiza
+/// los vectores de `vec_tnea`. Con [`Cuantizacion::Float`] solamente se elimina la tabla.
+pub fn configurar(
+    db: &Connection,
+    cuantizacion: Cuantizacion,
+    dimensiones: usize,
+) -> eyre::Result<()> {
+    db.execute_batch(
+        "
+        drop trigger if exists tnea_vec_cuantizado_delete;
+        drop table if exists vec_cuantizado;
+        ",
+    )?;
+
+    if let Some(tipo) = cuantizacion.columna(dimensiones, Metrica::from_db(db)?) {
+        db.execute_batch(&format!(
+            "
+            create virtual table vec_cuantizado using vec0(
+                row_id integer primary key,
+                embedding {tipo}
+            );
+
+            create trigger tnea_vec_cuantizado_delete after delete on tnea begin
+                delete from vec_cuantizado where row_id = old.id;
+            end;
+            "
+        ))?;
+    }
+
+    sqlite::set_metadata(db, METADATA_CUANTIZACION, &cuantizacion.to_string())?;
+    tracing::info!("Se configuró la cuantización `{cuantizacion}` para vec_tnea.");
+
+    if sqlite::table_exists(db, "vec_tnea")? {
+        sync_vec_cuantizado(db)?;
+    }
+
+    Ok(())
+}
+
+/// Genera en `vec_cuantizado` los vectores de los registros de `vec_tnea` que no lo tienen o
+/// cuyo embedding cambió, y devuelve cuántos generó. No requiere llamar a la API de embeddings.
+///
+/// Recorre todos los vectores de `vec_tnea`, por lo que se usa al sincronizar. Al escribir
+/// algunos embeddings se usa [`cuantizar_registros`].
+pub fn sync_vec_cuantizado(db: &Connection) -> eyre::Result<usize> {
+    let cuantizacion = Cuantizacion::from_db(db)?;
+    if cuantizacion == Cuantizacion::Float {
+        return Ok(0);
+    }
+
+    let start = std::time::Instant::now();
+    let tx = db.unchecked_transaction()?;
+
+    // Los embeddings que cambiaron ya no coinciden con su versión cuantizada.
+    let desactualizados = format!(
+        "select vec_cuantizado.row_id from vec_cuantizado
+        join vec_tnea on vec_tnea.row_id = vec_cuantizado.row_id
+        where vec_cuantizado.embedding != {}",
+        cuantizacion.cuantizar("vec_tnea.template_embedding")
+    );
+    tx.execute(
+        &format!("delete from vec_cuantizado where row_id in ({desactualizados})"),
+        [],
+    )?;
+
+    let pendientes: Vec<(u64, Vec<u8>)> = tx
+        .prepare(&format!(
+            "select row_id, {} from vec_tnea
+            where row_id not in (select row_id from vec_cuantizado)",
+            cuantizacion.cuantizar("template_embedding")
+        ))?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    insertar(&tx, cuantizacion, &pendientes)?;
+    tx.commit()?;
+
+    let insertados = pendientes.len();
+
+    if insertados > 0 {
+        tracing::info!(
+            "Se cuantizaron {insertados} vectores con `{cuantizacion}` en {} ms",
+            start.elapsed().as_millis()
+        );
+    }
+
+    Ok(insertados)
+}
+
+/// Reemplaza en `vec_cuantizado` los vectores de los registros indicados por la cuantización
+/// de sus embeddings actuales en `vec_tnea`. Se llama dentro de la transacción que escribió los
+/// embeddings, y solamente consulta esos registros.
+pub fn cuantizar_registros(db: &Connection, ids: &[u64]) -> eyre::Result<()> {
+    let cuantizacion = Cuantizacion::from_db(db)?;
+    if cuantizacion == Cuantizacion::Float || ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut eliminar = db.prepare("delete from vec_cuantizado where row_id = ?")?;
+    let mut leer = db.prepare(&format!(
+        "select row_id, {} from vec_tnea where row_id = ?",
+        cuantizacion.cuantizar("template_embedding")
+    ))?;
+
+    let mut pendientes = Vec::with_capacity(ids.len());
+    for id in ids {
+        eliminar.execute([id])?;
+        if let Some(vector) = leer
+            .query_row([id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?
+        {
+            pendientes.push(vector);
+        }
+    }
+
+    insertar(db, cuantizacion, &pendientes)
+}
+
+/// Inserta vectores ya cuantizados en `vec_cuantizado`. Se insertan de a uno porque
+/// `insert ... select` no conserva el tipo de los vectores.
+fn insertar(
+    db: &Connection,
+    cuantizacion: Cuantizacion,
+    pendientes: &[(u64, Vec<u8>)],
+) -> eyre::Result<()> {
+    let mut statement = db.prepare(&format!(
+        "insert into vec_cuantizado(row_id, embedding) values (?, {})",
+        cuantizacion.tipo("?")
+    ))?;
+    for (id, vector) in pendientes {
+        statement.execute(rusqlite::params![id, vector])?;
+    }
+
+    Ok(())
+}
+
+/// Comparación de la búsqueda con vectores cuantizados contra la búsqueda exacta.
+#[derive(Debug, Clone)]
+pub struct Comparacion {
+    pub cuantizacion: Cuantizacion,
+    pub consultas: usize,
+    /// Proporción de los `k` vecinos exactos que también encontró la búsqueda cuantizada.
+    pub recall: f64,
+    /// Latencia promedio por consulta, en milisegundos.
+    pub latencia_float: f64,
+    pub latencia_cuantizada: f64,
+}
+
+impl Display for Comparacion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Cuantización: {} ({} consultas con registros de vec_tnea)",
+            self.cuantizacion, self.consultas
+        )?;
+        writeln!(f, "Recall@{K_RECALL} contra float: {:.3}", self.recall)?;
+        write!(
+            f,
+            "Latencia promedio: float {:.2} ms, {} {:.2} ms",
+            self.latencia_float, self.cuantizacion, self.latencia_cuantizada
+        )
+    }
+}
+
+/// Usa como consultas los embeddings de algunos registros de `vec_tnea` y compara los vecinos
+/// que encuentra la búsqueda cuantizada con los de la búsqueda exacta. Devuelve `None` si no se
+/// configuró una cuantización o no hay vectores.
+pub fn comparar(db: &Connection) -> eyre::Result<Option<Comparacion>> {
+    let cuantizacion = Cuantizacion::from_db(db)?;
+    if cuantizacion == Cuantizacion::Float || !sqlite::table_exists(db, "vec_cuantizado")? {
+        return Ok(None);
+    }
+
+    let consultas: Vec<Vec<u8>> = db
+        .prepare("select template_embedding from vec_tnea order by random() limit ?")?
+        .query_map([CONSULTAS_RECALL], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    if consultas.is_empty() {
+        return Ok(None);
+    }
+
+    let k = K_RECALL.to_string();
+    let metrica = Metrica::from_db(db)?;
+    let mut exacta = db.prepare(&format!(
+        "select row_id from ({}) order by distance",
+        Cuantizacion::Float.knn_sql(&k, metrica)
+    ))?;
+    let mut cuantizada = db.prepare(&format!(
+        "select row_id from ({}) order by distance",
+        cuantizacion.knn_sql(&k, metrica)
+    ))?;
+
+    let buscar = |statement: &mut rusqlite::Statement, embedding: &[u8]| {
+        let start = std::time::Instant::now();
+        let ids = statement
+            .query_map(rusqlite::named_params! { ":embedding": embedding }, |row| {
+                row.get::<_, u64>(0)
+            })?
+            .collect::<Result<HashSet<u64>, _>>()?;
+        Ok::<_, eyre::Report>((ids, start.elapsed().as_secs_f64() * 1000.0))
+    };
+
+    let (mut encontrados, mut total) = (0, 0);
+    let (mut latencia_float, mut latencia_cuantizada) = (0.0, 0.0);
+
+    for embedding in &consultas {
+        let (esperados, latencia) = buscar(&mut exacta, embedding)?;
+        latencia_float += latencia;
+        let (obtenidos, latencia) = buscar(&mut cuantizada, embedding)?;
+        latencia_cuantizada += latencia;
+
+        total += esperados.len();
+        encontrados += esperados.intersection(&obtenidos).count();
+    }
+
+    let n = consultas.len() as f64;
+
+    Ok(Some(Comparacion {
+        cuantizacion,
+        consultas: consultas.len(),
+        recall: encontrados as f64 / total.max(1) as f64,
+        latencia_float: latencia_float / n,
+        latencia_cuantizada: latencia_cuantizada / n,
+    }))
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, fmt::Display};
+
+use rusqlite::Connection;
+
+use crate::{fts::TokenizerConfig, fuzzy, query, stemmer};
+
+/// Cantidad máxima de palabras clave que se buscan con FTS.
+pub const MAX_PALABRAS_CLAVE: usize = 10;
+/// Largo máximo, en palabras, de cada fragmento que se convierte en embedding.
+const MAX_PALABRAS_FRAGMENTO: usize = 200;
+/// Cantidad máxima de fragmentos, para acotar el costo de los embeddings. El texto que sobra se
+/// ignora en la búsqueda semántica.
+pub const MAX_FRAGMENTOS: usize = 8;
+/// Las palabras más cortas suelen ser artículos o preposiciones y no se consideran.
+const MIN_LARGO_PALABRA: usize = 3;
+/// Palabras frecuentes del español que no describen al puesto. Con pocos registros, TF-IDF no
+/// alcanza para descartarlas. Se escriben en minúsculas y sin diacríticos.
+const PALABRAS_VACIAS: &[&str] = &[
+    "con", "las", "los", "del", "por", "para", "una", "uno", "unos", "unas", "que", "sus", "como",
+    "mas", "pero", "sin", "sobre", "entre", "este", "esta", "estos", "estas", "ese", "esa", "son",
+    "ser", "sera", "muy", "tambien", "desde", "hasta", "cada", "todo", "toda", "todos", "todas",
+    "nuestro", "nuestra", "nuestros", "nuestras", "buscamos", "busca", "tener", "contar",
+];
+
+#[derive(Debug, Clone)]
+pub struct PalabraClave {
+    pub palabra: String,
+    /// Puntaje TF-IDF de la palabra en la descripción.
+    pub peso: f64,
+}
+
+impl Display for PalabraClave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({:.3})", self.palabra, self.peso)
+    }
+}
+
+/// Elige las palabras más representativas de una descripción de puesto para buscarlas con FTS.
+///
+/// Cada palabra se puntúa con TF-IDF: la frecuencia de la palabra en la descripción por el
+/// logaritmo de la inversa de la proporción de registros de `fts_tnea` que la contienen, que es la
+/// columna `doc` de `fts_tnea_vocab` para el término con el que el tokenizer indexa la palabra.
+/// Las palabras que no aparecen en ningún registro, o que aparecen en todos, se descartan porque
+/// no ayudan a ordenar los resultados.
+pub fn palabras_clave(db: &Connection, texto: &str) -> eyre::Result<Vec<PalabraClave>> {
+    let total: usize = db.query_row("select count(*) from fts_tnea", [], |row| row.get(0))?;
+    if total == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Se agrupan las formas de una misma palabra sin distinguir mayúsculas ni diacríticos, y se
+    // conserva la primera forma en la que aparece.
+    let mut frecuencias: HashMap<String, (String, usize)> = HashMap::new();
+    let mut orden: Vec<String> = Vec::new();
+    let mut palabras = 0;
+
+    for palabra in texto
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|palabra| palabra.chars().count() >= MIN_LARGO_PALABRA)
+        .filter(|palabra| palabra.chars().any(char::is_alphabetic))
+    {
+        palabras += 1;
+        let clave: String = palabra
+            .chars()
+            .flat_map(char::to_lowercase)
+            .map(stemmer::fold)
+            .collect();
+
+        if PALABRAS_VACIAS.contains(&clave.as_str()) {
+            continue;
+        }
+
+        frecuencias
+            .entry(clave.clone())
+            .or_insert_with(|| {
+                orden.push(clave);
+                (palabra.to_string(), 0)
+            })
+            .1 += 1;
+    }
+
+    let docs = docs(db, orden.iter().map(|clave| frecuencias[clave].0.as_str()))?;
+    let mut claves = Vec::new();
+
+    for clave in orden {
+        let (palabra, frecuencia) = &frecuencias[&clave];
+
+        let Some(&docs) = docs.get(palabra.as_str()) else {
+            continue;
+        };
+        if docs == 0 || docs >= total {
+            continue;
+        }
+
+        let tf = *frecuencia as f64 / palabras as f64;
+        let idf = (total as f64 / docs as f64).ln();
+
+        claves.push(PalabraClave {
+            palabra: palabra.clone(),
+            peso: tf * idf,
+        });
+    }
+
+    claves.sort_by(|a, b| b.peso.total_cmp(&a.peso));
+    claves.truncate(MAX_PALABRAS_CLAVE);
+
+    Ok(claves)
+}
+
+/// Cantidad de registros de `fts_tnea` que contienen cada palabra. Con los tokenizers que
+/// [`TokenizerConfig::termino`] puede reproducir se leen todas de `fts_tnea_vocab` en una sola
+/// consulta, con el resto se cuenta cada palabra con FTS. Las palabras que no se pueden buscar
+/// solas, como los operadores, no se incluyen.
+fn docs<'a>(
+    db: &Connection,
+    palabras: impl Iterator<Item = &'a str>,
+) -> eyre::Result<HashMap<&'a str, usize>> {
+    let tokenizer = TokenizerConfig::from_db(db)?;
+    let mut docs = HashMap::new();
+    let mut terminos: HashMap<String, Vec<&str>> = HashMap::new();
+
+    for palabra in palabras {
+        // Los operadores como `OR` no son válidos por sí solos.
+        let Ok(query) = query::parse(palabra) else {
+            continue;
+        };
+        let words = query.words();
+        let [(_, fts)] = words.as_slice() else {
+            continue;
+        };
+
+        match tokenizer.termino(palabra) {
+            Some(termino) => {
+                docs.insert(palabra, 0);
+                terminos.entry(termino).or_default().push(palabra);
+            }
+            None => {
+                docs.insert(palabra, fuzzy::docs(db, fts)?);
+            }
+        }
+    }
+
+    if terminos.is_empty() {
+        return Ok(docs);
+    }
+
+    let claves: Vec<&String> = terminos.keys().collect();
+    let mut statement = db.prepare(
+        "select term, doc from fts_tnea_vocab
+        where term in (select value from json_each(?))",
+    )?;
+    let mut rows = statement.query([serde_json::to_string(&claves)?])?;
+
+    while let Some(row) = rows.next()? {
+        let termino: String = row.get(0)?;
+        for palabra in terminos.get(&termino).into_iter().flatten() {
+            docs.insert(palabra, row.get(1)?);
+        }
+    }
+
+    Ok(docs)
+}
+
+/// Divide una descripción en fragmentos de hasta [`MAX_PALABRAS_FRAGMENTO`] palabras para
+/// generar un embedding de cada uno. Se respetan los párrafos siempre que se pueda: los párrafos
+/// cortos se juntan y los largos se cortan por cantidad de palabras.
+#[must_use]
+pub fn fragmentos(texto: &str) -> Vec<String> {
+    let mut parrafos: Vec<Vec<&str>> = vec![Vec::new()];
+    for linea in texto.lines() {
+        let palabras: Vec<&str> = linea.split_whitespace().collect();
+        if !palabras.is_empty() {
+            if let Some(parrafo) = parrafos.last_mut() {
+                parrafo.extend(palabras);
+            }
+        } else if parrafos.last().is_some_and(|parrafo| !parrafo.is_empty()) {
+            parrafos.push(Vec::new());
+        }
+    }
+
+    let mut fragmentos: Vec<String> = Vec::new();
+    let mut actual: Vec<&str> = Vec::new();
+
+    for parrafo in parrafos.into_iter().filter(|parrafo| !parrafo.is_empty()) {
+        if actual.len() + parrafo.len() <= MAX_PALABRAS_FRAGMENTO {
+            actual.extend(parrafo);
+            continue;
+        }
+
+        for parte in parrafo.chunks(MAX_PALABRAS_FRAGMENTO) {
+            if !actual.is_empty() {
+                fragmentos.push(actual.join(" "));
+                actual.clear();
+            }
+            actual.extend(parte);
+        }
+    }
+
+    if !actual.is_empty() {
+        fragmentos.push(actual.join(" "));
+    }
+
+    if fragmentos.len() > MAX_FRAGMENTOS {
+        tracing::warn!(
+            "La descripción tiene {} fragmentos, se usan solamente los primeros {MAX_FRAGMENTOS}.",
+            fragmentos.len()
+        );
+        fragmentos.truncate(MAX_FRAGMENTOS);
+    }
+
+    fragmentos
+}
@@ -0,0 +1,94 @@
+use rusqlite::Connection;
+
+use crate::{cli::Model, sqlite};
+
+/// Clave de `metadata` con la dimensión de los embeddings de `vec_tnea`.
+pub const METADATA_DIMENSIONES: &str = "dimensiones_embedding";
+
+/// Verifica que el modelo pueda generar embeddings de la dimensión indicada. Los modelos
+/// `text-embedding-3` admiten vectores más cortos que el completo (Matryoshka), conservando las
+/// primeras dimensiones, con poca pérdida de calidad.
+///
+/// # Errors
+///
+/// Devuelve un error si la dimensión es 0 o supera la del modelo, o si el modelo no admite
+/// acortar sus vectores.
+pub fn validar(model: &Model, dimensiones: usize) -> eyre::Result<()> {
+    let maximo = model.dimensions();
+
+    match model {
+        Model::OpenAI if !(1..=maximo).contains(&dimensiones) => Err(eyre::eyre!(
+            "El modelo `{}` admite entre 1 y {maximo} dimensiones, se indicaron {dimensiones}.",
+            model.name()
+        )),
+        #[cfg(feature = "local")]
+        Model::Local if dimensiones != maximo => Err(eyre::eyre!(
+            "El modelo `{}` solamente genera embeddings de {maximo} dimensiones.",
+            model.name()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Dimensión con la que se creó la columna `template_embedding` de `vec_tnea`, leída de su
+/// definición en `sqlite_master`. Devuelve `None` si la tabla no existe.
+pub fn de_vec_tnea(db: &Connection) -> eyre::Result<Option<usize>> {
+    if !sqlite::table_exists(db, "vec_tnea")? {
+        return Ok(None);
+    }
+
+    let sql: String = db.query_row(
+        "select sql from sqlite_master where name = 'vec_tnea'",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let dimensiones = sql
+        .split_once("template_embedding float[")
+        .and_then(|(_, resto)| resto.split_once(']'))
+        .and_then(|(dimensiones, _)| dimensiones.trim().parse().ok())
+        .ok_or_else(|| eyre::eyre!("No se pudo leer la dimensión de `vec_tnea`: {sql}"))?;
+
+    Ok(Some(dimensiones))
+}
+
+/// Dimensión de los embeddings registrada en `metadata`. Las bases de datos sincronizadas antes
+/// de que fuera configurable no la tienen, por lo que se usa la de la columna de `vec_tnea`.
+pub fn from_db(db: &Connection) -> eyre::Result<Option<usize>> {
+    match sqlite::get_metadata(db, METADATA_DIMENSIONES)? {
+        Some(valor) => Ok(Some(valor.parse().map_err(|err| {
+            eyre::eyre!("`{METADATA_DIMENSIONES}` no es un número válido: {valor} ({err})")
+        })?)),
+        None => de_vec_tnea(db),
+    }
+}
+
+/// Verifica al iniciar el servidor que la dimensión registrada coincida con la de `vec_tnea` y
+/// sea válida para el modelo registrado, y la devuelve. Los embeddings de las búsquedas deben
+/// tener la misma dimensión que los de la tabla, o vec0 rechaza la consulta.
+///
+/// # Errors
+///
+/// Devuelve un error si `vec_tnea` no existe o si la dimensión registrada no coincide con la
+/// de la tabla o con el modelo.
+pub fn verificar(db: &Connection) -> eyre::Result<usize> {
+    let columna = de_vec_tnea(db)?.ok_or_else(|| {
+        eyre::eyre!("La tabla `vec_tnea` no existe, ejecuta `sync -S vector` antes de `serve`.")
+    })?;
+
+    let dimensiones = from_db(db)?.unwrap_or(columna);
+    if dimensiones != columna {
+        return Err(eyre::eyre!(
+            "`{METADATA_DIMENSIONES}` indica {dimensiones} dimensiones pero `vec_tnea` tiene \
+            {columna}, vuelve a sincronizar con `sync --force`."
+        ));
+    }
+
+    if let Some(modelo) = sqlite::get_metadata(db, "modelo_embedding")? {
+        let model = Model::from_name(&modelo)
+            .ok_or_else(|| eyre::eyre!("El modelo de embeddings `{modelo}` no es conocido."))?;
+        validar(&model, dimensiones)?;
+    }
+
+    Ok(dimensiones)
+}
@@ -0,0 +1,624 @@
+use std::{
+    ffi::{c_char, c_int, c_void},
+    fmt::Display,
+    ptr,
+};
+
+use clap::ValueEnum;
+use rusqlite::{
+    ffi::{
+        fts5_api, fts5_tokenizer, sqlite3_bind_pointer, sqlite3_finalize, sqlite3_prepare_v2,
+        sqlite3_step, sqlite3_stmt, Fts5Tokenizer, FTS5_TOKENIZE_PREFIX, SQLITE_ERROR, SQLITE_OK,
+    },
+    Connection,
+};
+
+use crate::{sqlite, stemmer};
+
+/// Clave de la tabla `metadata` donde se guarda el tokenizer con el que fue creado `fts_tnea`.
+const TOKENIZER_KEY: &str = "fts_tokenizer";
+
+/// Nombre con el que se registra el tokenizer para español en FTS5.
+pub const SPANISH_TOKENIZER: &str = "spanish";
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FtsTokenizer {
+    /// Tokenizer por defecto de FTS5, separa por espacios y signos de puntuación.
+    Unicode61,
+    /// Aplica el stemmer de Porter (inglés) sobre los tokens de `unicode61`.
+    Porter,
+    /// Indexa secuencias de tres caracteres, permite búsquedas por subcadenas.
+    Trigram,
+    /// Aplica el stemmer de Snowball para el español sobre los tokens de `unicode61`.
+    Spanish,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenizerConfig {
+    pub tokenizer: FtsTokenizer,
+    /// Valor de la opción `remove_diacritics`: 0 los conserva, 1 y 2 los eliminan.
+    pub remove_diacritics: u8,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            tokenizer: FtsTokenizer::Spanish,
+            remove_diacritics: 2,
+        }
+    }
+}
+
+impl TokenizerConfig {
+    #[must_use]
+    pub fn new(tokenizer: FtsTokenizer, remove_diacritics: u8) -> Self {
+        Self {
+            tokenizer,
+            remove_diacritics,
+        }
+    }
+
+    /// Lee el tokenizer guardado en la tabla `metadata`. Si no está registrado pero `fts_tnea`
+    /// ya existe, fue creada antes de que el tokenizer fuera configurable y usa el de FTS5.
+    pub fn from_db(db: &Connection) -> eyre::Result<Self> {
+        match sqlite::get_metadata(db, TOKENIZER_KEY)? {
+            Some(value) => Self::try_from(value.as_str()),
+            None if sqlite::table_exists(db, "fts_tnea")? => {
+                Ok(Self::new(FtsTokenizer::Unicode61, 1))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+impl TokenizerConfig {
+    /// Término con el que el tokenizer indexa una palabra suelta, tal como aparece en la columna
+    /// `term` de `fts_tnea_vocab`. Devuelve `None` con `porter`, cuyo stemmer no está implementado
+    /// en Rust, y con `trigram`, que no indexa palabras completas.
+    #[must_use]
+    pub fn termino(&self, palabra: &str) -> Option<String> {
+        match self.tokenizer {
+            FtsTokenizer::Unicode61 => Some(
+                palabra
+                    .chars()
+                    .flat_map(char::to_lowercase)
+                    .map(|c| {
+                        if self.remove_diacritics > 0 {
+                            stemmer::fold(c)
+                        } else {
+                            c
+                        }
+                    })
+                    .collect(),
+            ),
+            FtsTokenizer::Spanish => Some(stemmer::stem(palabra)),
+            FtsTokenizer::Porter | FtsTokenizer::Trigram => None,
+        }
+    }
+}
+
+impl Display for TokenizerConfig {
+    /// Devuelve el valor de la opción `tokenize` de FTS5.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let remove_diacritics = self.remove_diacritics;
+        match self.tokenizer {
+            FtsTokenizer::Unicode61 => {
+                write!(f, "unicode61 remove_diacritics {remove_diacritics}")
+            }
+            FtsTokenizer::Porter => {
+                write!(f, "porter unicode61 remove_diacritics {remove_diacritics}")
+            }
+            // El tokenizer `trigram` solamente acepta 0 o 1.
+            FtsTokenizer::Trigram => {
+                write!(f, "trigram remove_diacritics {}", remove_diacritics.min(1))
+            }
+            FtsTokenizer::Spanish => {
+                write!(
+                    f,
+                    "{SPANISH_TOKENIZER} remove_diacritics {remove_diacritics}"
+                )
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for TokenizerConfig {
+    type Error = eyre::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut parts = value.split_whitespace();
+
+        let tokenizer = match parts.next() {
+            Some("unicode61") => FtsTokenizer::Unicode61,
+            Some("porter") => FtsTokenizer::Porter,
+            Some("trigram") => FtsTokenizer::Trigram,
+            Some(SPANISH_TOKENIZER) => FtsTokenizer::Spanish,
+            _ => return Err(eyre::eyre!("`{value}` no es un tokenizer soportado")),
+        };
+
+        let remove_diacritics = parts
+            .skip_while(|part| *part != "remove_diacritics")
+            .nth(1)
+            .map_or(Ok(1), str::parse)
+            .map_err(|err| eyre::eyre!("`{value}` tiene un valor inválido: {err}"))?;
+
+        Ok(Self::new(tokenizer, remove_diacritics))
+    }
+}
+
+/// Largos de los prefijos que indexa `fts_tnea`, para que las búsquedas con `prefijo*` no tengan
+/// que recorrer todos los términos que comienzan igual. El autocompletado no lo usa, lee los
+/// términos de `fts_tnea_vocab`. Las tablas creadas sin él funcionan igual, y lo agregan cuando se
+/// vuelven a crear con `fts tokenizer`.
+// https://www.sqlite.org/fts5.html#prefix_indexes
+pub const PREFIX_INDEX: &str = "2 3 4";
+
+/// Genera la sentencia para crear `fts_tnea` utilizando el tokenizer dado. Además del
+/// `template`, se indexan por separado las columnas que se pueden buscar con `campo:valor`.
+#[must_use]
+pub fn create_fts_tnea(tokenizer: &TokenizerConfig) -> String {
+    format!(
+        "
+        create virtual table if not exists fts_tnea using fts5(
+            email, edad, sexo, template, descripcion, estudios, experiencia,
+            content='tnea', content_rowid='id',
+            tokenize='{tokenizer}',
+            prefix='{PREFIX_INDEX}'
+        );
+        "
+    )
+}
+
+/// Triggers que mantienen sincronizado el índice de `fts_tnea` con la tabla `tnea`.
+// https://www.sqlite.org/fts5.html#external_content_tables
+pub const FTS_TNEA_TRIGGERS: &str = "
+    create trigger if not exists tnea_fts_insert after insert on tnea begin
+        insert into fts_tnea(
+            rowid, email, edad, sexo, template, descripcion, estudios, experiencia
+        )
+        values (
+            new.id, new.email, new.edad, new.sexo, new.template,
+            new.descripcion, new.estudios, new.experiencia
+        );
+    end;
+
+    create trigger if not exists tnea_fts_delete after delete on tnea begin
+        insert into fts_tnea(
+            fts_tnea, rowid, email, edad, sexo, template, descripcion, estudios, experiencia
+        )
+        values (
+            'delete', old.id, old.email, old.edad, old.sexo, old.template,
+            old.descripcion, old.estudios, old.experiencia
+        );
+    end;
+
+    create trigger if not exists tnea_fts_update after update on tnea begin
+        insert into fts_tnea(
+            fts_tnea, rowid, email, edad, sexo, template, descripcion, estudios, experiencia
+        )
+        values (
+            'delete', old.id, old.email, old.edad, old.sexo, old.template,
+            old.descripcion, old.estudios, old.experiencia
+        );
+        insert into fts_tnea(
+            rowid, email, edad, sexo, template, descripcion, estudios, experiencia
+        )
+        values (
+            new.id, new.email, new.edad, new.sexo, new.template,
+            new.descripcion, new.estudios, new.experiencia
+        );
+    end;
+";
+
+/// Columnas de `fts_tnea`, en el orden en el que `bm25` recibe sus pesos.
+pub const FTS_COLUMNS: [&str; 7] = [
+    "email",
+    "edad",
+    "sexo",
+    "template",
+    "descripcion",
+    "estudios",
+    "experiencia",
+];
+
+/// Peso de cada columna de `fts_tnea` al calcular el puntaje con `bm25`. Se escriben como
+/// `columna=peso` separados por comas, por ejemplo `experiencia=3,descripcion=1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25Weights([f32; FTS_COLUMNS.len()]);
+
+impl Default for Bm25Weights {
+    /// Las coincidencias en `email`, `edad` y `sexo` no suman puntaje.
+    fn default() -> Self {
+        Self([0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0])
+    }
+}
+
+impl Bm25Weights {
+    /// Devuelve una copia con los pesos dados en `overrides` reemplazados.
+    ///
+    /// # Errors
+    ///
+    /// Falla si alguna columna no existe o si algún peso no es un número mayor o igual a 0.
+    pub fn with_overrides(&self, overrides: &str) -> Result<Self, String> {
+        let mut weights = *self;
+
+        for pair in overrides.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let Some((column, weight)) = pair.split_once('=') else {
+                return Err(format!("`{pair}` debe tener la forma `columna=peso`."));
+            };
+
+            let column = column.trim().to_lowercase();
+            let Some(index) = FTS_COLUMNS.iter().position(|name| *name == column) else {
+                return Err(format!(
+                    "`{column}` no es una columna de fts_tnea, usa {}.",
+                    FTS_COLUMNS.join(", ")
+                ));
+            };
+
+            match weight.trim().parse::<f32>() {
+                Ok(weight) if weight.is_finite() && weight >= 0.0 => weights.0[index] = weight,
+                _ => {
+                    return Err(format!(
+                        "El peso de `{column}` debe ser un número mayor o igual a 0."
+                    ))
+                }
+            }
+        }
+
+        Ok(weights)
+    }
+
+    /// Expresión que calcula el puntaje `bm25` de la fila actual con estos pesos. Al igual que
+    /// `rank`, los valores más negativos son los mejores.
+    #[must_use]
+    pub fn rank_sql(&self) -> String {
+        Self::bm25_sql(&self.0)
+    }
+
+    /// Expresión que describe el aporte de cada columna con peso al puntaje, calculando `bm25`
+    /// con solamente esa columna.
+    #[must_use]
+    pub fn desglose_sql(&self) -> String {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0.0)
+            .map(|(index, weight)| {
+                let mut weights = [0.0; FTS_COLUMNS.len()];
+                weights[index] = *weight;
+                format!(
+                    "printf('{} %.2f', -{})",
+                    FTS_COLUMNS[index],
+                    Self::bm25_sql(&weights)
+                )
+            })
+            .collect();
+
+        match parts.as_slice() {
+            [] => "''".to_string(),
+            parts => format!("concat_ws(' · ', {})", parts.join(", ")),
+        }
+    }
+
+    fn bm25_sql(weights: &[f32]) -> String {
+        let weights: Vec<String> = weights.iter().map(|weight| format!("{weight:?}")).collect();
+        format!("bm25(fts_tnea, {})", weights.join(", "))
+    }
+}
+
+impl std::str::FromStr for Bm25Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::default().with_overrides(s)
+    }
+}
+
+impl Display for Bm25Weights {
+    /// Muestra solamente las columnas con peso.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let weights: Vec<String> = FTS_COLUMNS
+            .iter()
+            .zip(self.0)
+            .filter(|(_, weight)| *weight > 0.0)
+            .map(|(column, weight)| format!("{column}={weight}"))
+            .collect();
+
+        write!(f, "{}", weights.join(", "))
+    }
+}
+
+pub fn rebuild(db: &Connection) -> eyre::Result<()> {
+    let start = std::time::Instant::now();
+    tracing::info!("Reconstruyendo el índice de fts_tnea...");
+
+    db.execute("insert into fts_tnea(fts_tnea) values('rebuild')", [])?;
+
+    tracing::info!(
+        "Reconstruyendo el índice de fts_tnea... listo! tomó {} ms",
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// Verifica que el índice sea consistente consigo mismo y con la tabla `tnea`.
+pub fn integrity_check(db: &Connection) -> eyre::Result<()> {
+    db.execute(
+        "insert into fts_tnea(fts_tnea, rank) values('integrity-check', 1)",
+        [],
+    )
+    .map_err(|err| eyre::eyre!("El índice de fts_tnea está corrupto o desactualizado: {err}"))?;
+
+    tracing::info!("El índice de fts_tnea es consistente con la tabla `tnea`.");
+    Ok(())
+}
+
+/// Fusiona segmentos del índice hasta escribir aproximadamente `pages` páginas.
+pub fn merge(db: &Connection, pages: i64) -> eyre::Result<()> {
+    let start = std::time::Instant::now();
+    db.execute(
+        "insert into fts_tnea(fts_tnea, rank) values('merge', ?)",
+        [pages],
+    )?;
+
+    tracing::info!(
+        "Se fusionaron segmentos de fts_tnea, tomó {} ms",
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// Configura cuántos segmentos por nivel acumula el índice antes de fusionarlos automáticamente.
+/// Un valor de 0 desactiva la fusión automática.
+pub fn automerge(db: &Connection, segments: u8) -> eyre::Result<()> {
+    if segments == 1 || segments > 16 {
+        return Err(eyre::eyre!(
+            "`automerge` debe ser 0 para desactivarlo o un valor entre 2 y 16"
+        ));
+    }
+
+    db.execute(
+        "insert into fts_tnea(fts_tnea, rank) values('automerge', ?)",
+        [segments],
+    )?;
+
+    tracing::info!("Se configuró automerge={segments} en fts_tnea.");
+    Ok(())
+}
+
+/// Recrea `fts_tnea` con un nuevo tokenizer y reconstruye su índice. Como `fts_tnea` es una tabla
+/// con contenido externo, los datos de `tnea` no se ven afectados. Si algún paso falla se
+/// revierte la transacción y se conserva la tabla anterior.
+pub fn set_tokenizer(db: &Connection, tokenizer: &TokenizerConfig) -> eyre::Result<()> {
+    let start = std::time::Instant::now();
+    tracing::info!("Recreando fts_tnea con el tokenizer `{tokenizer}`...");
+
+    let tx = db.unchecked_transaction()?;
+
+    tx.execute_batch(&format!(
+        "
+        drop table if exists fts_tnea;
+        {}
+        insert into fts_tnea(fts_tnea) values('rebuild');
+        ",
+        create_fts_tnea(tokenizer)
+    ))?;
+    sqlite::set_metadata(&tx, TOKENIZER_KEY, &tokenizer.to_string())?;
+
+    tx.commit()?;
+
+    tracing::info!(
+        "Recreando fts_tnea con el tokenizer `{tokenizer}`... listo! tomó {} ms",
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+type XToken = unsafe extern "C" fn(
+    ctx: *mut c_void,
+    flags: c_int,
+    token: *const c_char,
+    n_token: c_int,
+    start: c_int,
+    end: c_int,
+) -> c_int;
+
+/// Tokenizer que envuelve a `unicode61` y reemplaza cada token por su raíz en español.
+/// Los offsets de los tokens no se modifican, por lo que `highlight()` sigue marcando el texto
+/// original.
+struct SpanishTokenizer {
+    parent: fts5_tokenizer,
+    parent_instance: *mut Fts5Tokenizer,
+}
+
+struct TokenizeContext {
+    ctx: *mut c_void,
+    x_token: XToken,
+    stem: bool,
+}
+
+/// Registra el tokenizer `spanish` en la conexión. Como FTS5 guarda los tokenizers por
+/// conexión, debe llamarse cada vez que se abre la base de datos.
+pub fn register_spanish_tokenizer(db: &Connection) -> eyre::Result<()> {
+    // SAFETY: `fts5_api` es válido mientras la conexión esté abierta y FTS5 copia la estructura
+    // `fts5_tokenizer` al registrarla.
+    unsafe {
+        let api = fts5_api(db)?;
+        let Some(create_tokenizer) = (*api).xCreateTokenizer else {
+            return Err(eyre::eyre!("FTS5 no permite registrar tokenizers"));
+        };
+
+        let mut tokenizer = fts5_tokenizer {
+            xCreate: Some(spanish_create),
+            xDelete: Some(spanish_delete),
+            xTokenize: Some(spanish_tokenize),
+        };
+
+        let rc = create_tokenizer(
+            api,
+            c"spanish".as_ptr(),
+            api.cast(),
+            ptr::addr_of_mut!(tokenizer),
+            None,
+        );
+        if rc != SQLITE_OK {
+            return Err(eyre::eyre!(
+                "Fallo al registrar el tokenizer `{SPANISH_TOKENIZER}`, código {rc}"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// https://www.sqlite.org/fts5.html#extending_fts5
+unsafe fn fts5_api(db: &Connection) -> eyre::Result<*mut fts5_api> {
+    let mut api: *mut fts5_api = ptr::null_mut();
+    let mut statement: *mut sqlite3_stmt = ptr::null_mut();
+
+    let rc = sqlite3_prepare_v2(
+        db.handle(),
+        c"select fts5(?1)".as_ptr(),
+        -1,
+        ptr::addr_of_mut!(statement),
+        ptr::null_mut(),
+    );
+    if rc != SQLITE_OK {
+        return Err(eyre::eyre!("FTS5 no está disponible, código {rc}"));
+    }
+
+    sqlite3_bind_pointer(
+        statement,
+        1,
+        ptr::addr_of_mut!(api).cast(),
+        c"fts5_api_ptr".as_ptr(),
+        None,
+    );
+    sqlite3_step(statement);
+    sqlite3_finalize(statement);
+
+    if api.is_null() {
+        return Err(eyre::eyre!("No se pudo obtener la API de FTS5"));
+    }
+
+    Ok(api)
+}
+
+unsafe extern "C" fn spanish_create(
+    user_data: *mut c_void,
+    args: *mut *const c_char,
+    n_args: c_int,
+    out: *mut *mut Fts5Tokenizer,
+) -> c_int {
+    let api = user_data.cast::<fts5_api>();
+    let Some(find_tokenizer) = (*api).xFindTokenizer else {
+        return SQLITE_ERROR;
+    };
+
+    let mut parent_user_data = ptr::null_mut();
+    let mut parent = fts5_tokenizer {
+        xCreate: None,
+        xDelete: None,
+        xTokenize: None,
+    };
+
+    let rc = find_tokenizer(
+        api,
+        c"unicode61".as_ptr(),
+        ptr::addr_of_mut!(parent_user_data),
+        ptr::addr_of_mut!(parent),
+    );
+    if rc != SQLITE_OK {
+        return rc;
+    }
+
+    let Some(parent_create) = parent.xCreate else {
+        return SQLITE_ERROR;
+    };
+
+    // Los argumentos, como `remove_diacritics`, se pasan directamente a `unicode61`.
+    let mut parent_instance = ptr::null_mut();
+    let rc = parent_create(
+        parent_user_data,
+        args,
+        n_args,
+        ptr::addr_of_mut!(parent_instance),
+    );
+    if rc != SQLITE_OK {
+        return rc;
+    }
+
+    let tokenizer = Box::new(SpanishTokenizer {
+        parent,
+        parent_instance,
+    });
+    *out = Box::into_raw(tokenizer).cast();
+
+    SQLITE_OK
+}
+
+unsafe extern "C" fn spanish_delete(tokenizer: *mut Fts5Tokenizer) {
+    let tokenizer = Box::from_raw(tokenizer.cast::<SpanishTokenizer>());
+    if let Some(parent_delete) = tokenizer.parent.xDelete {
+        parent_delete(tokenizer.parent_instance);
+    }
+}
+
+unsafe extern "C" fn spanish_tokenize(
+    tokenizer: *mut Fts5Tokenizer,
+    ctx: *mut c_void,
+    flags: c_int,
+    text: *const c_char,
+    n_text: c_int,
+    x_token: Option<XToken>,
+) -> c_int {
+    let tokenizer = &*tokenizer.cast::<SpanishTokenizer>();
+    let (Some(parent_tokenize), Some(x_token)) = (tokenizer.parent.xTokenize, x_token) else {
+        return SQLITE_ERROR;
+    };
+
+    // Los prefijos de una consulta (`progra*`) no se reducen a su raíz, ya que no son palabras
+    // completas y el stemmer podría recortarlos aún más.
+    let mut context = TokenizeContext {
+        ctx,
+        x_token,
+        stem: flags & FTS5_TOKENIZE_PREFIX == 0,
+    };
+
+    parent_tokenize(
+        tokenizer.parent_instance,
+        ptr::addr_of_mut!(context).cast(),
+        flags,
+        text,
+        n_text,
+        Some(stem_token),
+    )
+}
+
+unsafe extern "C" fn stem_token(
+    ctx: *mut c_void,
+    flags: c_int,
+    token: *const c_char,
+    n_token: c_int,
+    start: c_int,
+    end: c_int,
+) -> c_int {
+    let context = &*ctx.cast::<TokenizeContext>();
+    let bytes = std::slice::from_raw_parts(token.cast::<u8>(), n_token as usize);
+
+    match std::str::from_utf8(bytes) {
+        Ok(word) if context.stem => {
+            let stem = stemmer::stem(word);
+            (context.x_token)(
+                context.ctx,
+                flags,
+                stem.as_ptr().cast(),
+                stem.len() as c_int,
+                start,
+                end,
+            )
+        }
+        _ => (context.x_token)(context.ctx, flags, token, n_token, start, end),
+    }
+}
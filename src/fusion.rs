@@ -0,0 +1,113 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// Cómo se llevan a una misma escala los puntajes de FTS y de la búsqueda semántica antes de
+/// combinarlos.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Normalizacion {
+    /// `(x - min) / (max - min)`, deja los puntajes entre 0 y 1.
+    #[default]
+    MinMax,
+    /// `(x - media) / desvío`, deja los puntajes con media 0 y desvío 1.
+    ZScore,
+}
+
+impl Display for Normalizacion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Normalizacion::MinMax => write!(f, "min-max"),
+            Normalizacion::ZScore => write!(f, "z-score"),
+        }
+    }
+}
+
+impl Normalizacion {
+    /// Normaliza los puntajes de una búsqueda, donde un puntaje mayor es mejor. Los registros
+    /// que no fueron encontrados por esa búsqueda (`None`) reciben 0 con min-max y el menor
+    /// valor normalizado con z-score, para que no sumen al combinarlos.
+    #[must_use]
+    pub fn normalizar(&self, scores: &[Option<f32>]) -> Vec<f32> {
+        let presentes: Vec<f32> = scores.iter().flatten().copied().collect();
+        if presentes.is_empty() {
+            return vec![0.0; scores.len()];
+        }
+
+        let normalizado: Box<dyn Fn(f32) -> f32> = match self {
+            Normalizacion::MinMax => {
+                let min = presentes.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = presentes.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+                // Si todos los puntajes son iguales, todos los registros son igual de buenos.
+                if max - min <= f32::EPSILON {
+                    Box::new(|_| 1.0)
+                } else {
+                    Box::new(move |x| (x - min) / (max - min))
+                }
+            }
+            Normalizacion::ZScore => {
+                let n = presentes.len() as f32;
+                let media = presentes.iter().sum::<f32>() / n;
+                let desvio =
+                    (presentes.iter().map(|x| (x - media).powi(2)).sum::<f32>() / n).sqrt();
+
+                if desvio <= f32::EPSILON {
+                    Box::new(|_| 0.0)
+                } else {
+                    Box::new(move |x| (x - media) / desvio)
+                }
+            }
+        };
+
+        let minimo = match self {
+            Normalizacion::MinMax => 0.0,
+            Normalizacion::ZScore => presentes
+                .iter()
+                .map(|x| normalizado(*x))
+                .fold(f32::INFINITY, f32::min),
+        };
+
+        scores
+            .iter()
+            .map(|score| score.map_or(minimo, &normalizado))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_max() {
+        assert_eq!(
+            Normalizacion::MinMax.normalizar(&[Some(1.0), Some(3.0), None, Some(2.0)]),
+            [0.0, 1.0, 0.0, 0.5]
+        );
+    }
+
+    #[test]
+    fn z_score() {
+        // Media 2 y desvío 1.
+        assert_eq!(
+            Normalizacion::ZScore.normalizar(&[Some(1.0), Some(3.0), None]),
+            [-1.0, 1.0, -1.0]
+        );
+    }
+
+    #[test]
+    fn puntajes_iguales() {
+        let scores = [Some(2.5), None, Some(2.5)];
+
+        assert_eq!(Normalizacion::MinMax.normalizar(&scores), [1.0, 0.0, 1.0]);
+        assert_eq!(Normalizacion::ZScore.normalizar(&scores), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn sin_puntajes() {
+        for normalizacion in [Normalizacion::MinMax, Normalizacion::ZScore] {
+            assert_eq!(normalizacion.normalizar(&[None, None]), [0.0, 0.0]);
+            assert!(normalizacion.normalizar(&[]).is_empty());
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use std::{collections::HashMap, fmt::Display, sync::Mutex};
+
+use rusqlite::Connection;
+
+use crate::{
+    fts::{FtsTokenizer, TokenizerConfig, FTS_COLUMNS},
+    query::{self, SearchQuery},
+    stemmer,
+};
+
+/// Tablas `fts5vocab` de `fts_tnea`: `fts_tnea_vocab` tiene los términos indexados y la cantidad
+/// de registros que contienen cada uno, y `fts_tnea_vocab_instance` la posición de cada aparición
+/// de un término. Las crea `setup_sqlite`, y el servidor al iniciar para las bases de datos
+/// sincronizadas antes de que existieran.
+pub const CREATE_FTS_VOCAB: &str = "
+    create virtual table if not exists fts_tnea_vocab using fts5vocab(fts_tnea, 'row');
+    create virtual table if not exists fts_tnea_vocab_instance
+        using fts5vocab(fts_tnea, 'instance');
+";
+
+/// Si una palabra aparece en menos registros que este valor, se buscan variantes.
+const MIN_DOCS: usize = 2;
+/// Cantidad máxima de variantes que se agregan por palabra.
+const MAX_VARIANTES: usize = 3;
+/// Similitud mínima, entre 0 y 1, para aceptar una variante.
+const MIN_SIMILITUD: f32 = 0.75;
+/// Distancia de edición máxima para aceptar una variante.
+const MAX_DISTANCIA: usize = 2;
+/// Similitud mínima para sugerir una corrección cuando la búsqueda no tuvo resultados. Es menor
+/// que [`MIN_SIMILITUD`] porque la sugerencia no cambia la búsqueda, solamente se ofrece.
+const MIN_SIMILITUD_SUGERENCIA: f32 = 0.6;
+/// Similitud mínima entre la búsqueda y una búsqueda anterior para sugerirla.
+const MIN_SIMILITUD_HISTORIAL: f32 = 0.5;
+/// Cantidad máxima de búsquedas anteriores que se sugieren.
+const MAX_SUGERENCIAS_HISTORIAL: usize = 3;
+
+/// Cantidad máxima de términos en [`Formas`]. Al superarla se vacía.
+const MAX_FORMAS: usize = 10_000;
+
+/// Formas originales de los términos del vocabulario que ya se buscaron con [`forma_original`],
+/// para que el autocompletado no haga una búsqueda en `fts_tnea` por cada término en cada tecla.
+/// Una forma desactualizada solamente cambia cómo se muestra la sugerencia.
+#[derive(Debug, Default)]
+pub struct Formas(Mutex<HashMap<String, String>>);
+
+impl Formas {
+    fn obtener(&self, db: &Connection, tokenizer: &TokenizerConfig, term: String) -> String {
+        let Ok(mut formas) = self.0.lock() else {
+            return forma_original(db, tokenizer, &term).unwrap_or(term);
+        };
+
+        if let Some(forma) = formas.get(&term) {
+            return forma.clone();
+        }
+
+        let forma = forma_original(db, tokenizer, &term).unwrap_or_else(|| term.clone());
+        if formas.len() >= MAX_FORMAS {
+            formas.clear();
+        }
+        formas.insert(term, forma.clone());
+
+        forma
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Correccion {
+    pub original: String,
+    /// Las variantes agregadas y su similitud con la palabra original.
+    pub variantes: Vec<(String, f32)>,
+}
+
+impl Display for Correccion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let variantes: Vec<String> = self
+            .variantes
+            .iter()
+            .map(|(variante, similitud)| format!("{variante} ({similitud:.2})"))
+            .collect();
+
+        write!(f, "{} → {}", self.original, variantes.join(", "))
+    }
+}
+
+/// Busca variantes para las palabras de la búsqueda que tienen pocas coincidencias en
+/// `fts_tnea`, comparándolas por distancia de edición con el vocabulario del índice. Las
+/// variantes se agregan a `query` como alternativas de la palabra original.
+pub fn corregir(db: &Connection, query: &mut SearchQuery) -> eyre::Result<Vec<Correccion>> {
+    let tokenizer = TokenizerConfig::from_db(db)?;
+
+    // El tokenizer `trigram` ya encuentra coincidencias parciales.
+    if matches!(tokenizer.tokenizer, FtsTokenizer::Trigram) {
+        return Ok(Vec::new());
+    }
+
+    let mut correcciones = Vec::new();
+
+    for (word, fts) in query.words() {
+        if docs(db, &fts)? >= MIN_DOCS {
+            continue;
+        }
+
+        let candidatos = candidatos(db, &word, &tokenizer, MIN_SIMILITUD)?;
+        if candidatos.is_empty() {
+            continue;
+        }
+
+        let terms: Vec<String> = candidatos.iter().map(|(term, _)| term.clone()).collect();
+        query.add_variants(&word, &terms);
+
+        // Varias raíces pueden corresponder a la misma palabra, se muestra solamente la primera.
+        let mut variantes: Vec<(String, f32)> = Vec::new();
+        for (term, similitud) in candidatos {
+            let variante = forma_original(db, &tokenizer, &term).unwrap_or(term);
+            if !variantes
+                .iter()
+                .any(|(existente, _)| *existente == variante)
+            {
+                variantes.push((variante, similitud));
+            }
+        }
+
+        tracing::debug!("Se agregaron variantes para `{word}`: {variantes:?}");
+
+        correcciones.push(Correccion {
+            original: word,
+            variantes,
+        });
+    }
+
+    Ok(correcciones)
+}
+
+/// Arma una versión corregida de `input` reemplazando cada palabra con pocas coincidencias por el
+/// término más parecido del vocabulario. El resto de la búsqueda (frases, filtros, exclusiones y
+/// operadores) se mantiene igual. Devuelve `None` si no hay nada para corregir.
+pub fn sugerir_correccion(db: &Connection, input: &str) -> eyre::Result<Option<String>> {
+    let tokenizer = TokenizerConfig::from_db(db)?;
+
+    if matches!(tokenizer.tokenizer, FtsTokenizer::Trigram) {
+        return Ok(None);
+    }
+
+    let mut corregida = false;
+    let mut tokens = Vec::new();
+
+    for token in input.split_whitespace() {
+        match corregir_palabra(db, token, &tokenizer)? {
+            Some(reemplazo) => {
+                corregida = true;
+                tokens.push(reemplazo);
+            }
+            None => tokens.push(token.to_string()),
+        }
+    }
+
+    Ok(corregida.then(|| tokens.join(" ")))
+}
+
+/// Corrige `token` si es una palabra suelta con pocas coincidencias en `fts_tnea`.
+fn corregir_palabra(
+    db: &Connection,
+    token: &str,
+    tokenizer: &TokenizerConfig,
+) -> eyre::Result<Option<String>> {
+    if !token.chars().all(char::is_alphanumeric) {
+        return Ok(None);
+    }
+
+    // Los operadores como `OR` no son válidos por sí solos.
+    let Ok(query) = query::parse(token) else {
+        return Ok(None);
+    };
+
+    let words = query.words();
+    let [(word, fts)] = words.as_slice() else {
+        return Ok(None);
+    };
+
+    if docs(db, fts)? >= MIN_DOCS {
+        return Ok(None);
+    }
+
+    Ok(candidatos(db, word, tokenizer, MIN_SIMILITUD_SUGERENCIA)?
+        .into_iter()
+        .next()
+        .map(|(term, _)| forma_original(db, tokenizer, &term).unwrap_or(term)))
+}
+
+/// Devuelve las búsquedas anteriores que tuvieron resultados y se parecen a `input`, ordenadas
+/// por similitud y por la cantidad de veces que se realizaron.
+pub fn historial_similar(db: &Connection, input: &str) -> eyre::Result<Vec<String>> {
+    let input = normalizar(input);
+    let palabras: Vec<&str> = input.split_whitespace().collect();
+
+    let mut statement =
+        db.prepare("select query, frecuencia from historial where resultados > 0")?;
+
+    let mut similares: Vec<(String, f32)> = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?))
+        })?
+        .filter_map(Result::ok)
+        .filter_map(|(query, frecuencia)| {
+            let normalizada = normalizar(&query);
+            if normalizada == input {
+                return None;
+            }
+
+            // Se toma la mayor entre la similitud de los textos completos y la proporción de
+            // palabras en común, para no depender del orden de las palabras.
+            let a: Vec<char> = input.chars().collect();
+            let b: Vec<char> = normalizada.chars().collect();
+            let edicion = 1.0 - levenshtein(&a, &b) as f32 / a.len().max(b.len()).max(1) as f32;
+
+            let otras: Vec<&str> = normalizada.split_whitespace().collect();
+            let comunes = palabras.iter().filter(|p| otras.contains(p)).count();
+            let total = palabras.len() + otras.len() - comunes;
+            let solapamiento = comunes as f32 / total.max(1) as f32;
+
+            let similitud = edicion.max(solapamiento);
+
+            (similitud >= MIN_SIMILITUD_HISTORIAL)
+                .then(|| (query, similitud * (1.0 + frecuencia as f32).ln()))
+        })
+        .collect();
+
+    similares.sort_by(|a, b| b.1.total_cmp(&a.1));
+    similares.truncate(MAX_SUGERENCIAS_HISTORIAL);
+
+    Ok(similares.into_iter().map(|(query, _)| query).collect())
+}
+
+/// Términos del vocabulario de `fts_tnea` que comienzan con `prefix`, tal como aparecen escritos
+/// en algún registro, junto con la cantidad de registros que los contienen.
+pub fn completar_termino(
+    db: &Connection,
+    formas: &Formas,
+    prefix: &str,
+    limit: usize,
+) -> eyre::Result<Vec<(String, usize)>> {
+    let tokenizer = TokenizerConfig::from_db(db)?;
+
+    // El vocabulario del tokenizer `trigram` no tiene palabras completas.
+    if matches!(tokenizer.tokenizer, FtsTokenizer::Trigram) {
+        return Ok(Vec::new());
+    }
+
+    let prefix: String = if tokenizer.remove_diacritics > 0 {
+        normalizar(prefix)
+    } else {
+        prefix.to_lowercase()
+    };
+
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // `fts5vocab` resuelve los rangos sobre `term` sin recorrer todo el vocabulario.
+    let mut statement = db.prepare(
+        "select term, doc from fts_tnea_vocab
+        where term >= ?1 and term < ?1 || char(1114111)
+        order by doc desc
+        limit ?2",
+    )?;
+
+    let terminos: Vec<(String, usize)> = statement
+        .query_map(rusqlite::params![prefix, limit], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut completados: Vec<(String, usize)> = Vec::new();
+    for (term, docs) in terminos {
+        let forma = formas.obtener(db, &tokenizer, term);
+        if !completados.iter().any(|(existente, _)| *existente == forma) {
+            completados.push((forma, docs));
+        }
+    }
+
+    Ok(completados)
+}
+
+/// Minúsculas y sin diacríticos, para comparar búsquedas.
+fn normalizar(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(stemmer::fold)
+        .collect()
+}
+
+/// Cantidad de registros de `fts_tnea` que coinciden con la expresión `fts`.
+pub fn docs(db: &Connection, fts: &str) -> eyre::Result<usize> {
+    Ok(db.query_row(
+        "select count(*) from fts_tnea where fts_tnea match ?",
+        [fts],
+        |row| row.get(0),
+    )?)
+}
+
+/// Devuelve los términos del vocabulario más parecidos a `word`, ordenados por similitud y por
+/// la cantidad de registros que los contienen.
+fn candidatos(
+    db: &Connection,
+    word: &str,
+    tokenizer: &TokenizerConfig,
+    min_similitud: f32,
+) -> eyre::Result<Vec<(String, f32)>> {
+    let normalized: Vec<char> = word
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| {
+            if tokenizer.remove_diacritics > 0 {
+                stemmer::fold(c)
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    let Some(first) = normalized.first().map(char::to_string) else {
+        return Ok(Vec::new());
+    };
+
+    // El vocabulario de los tokenizers con stemming contiene raíces, que se comparan con el
+    // comienzo de la palabra para no depender de que el sufijo esté bien escrito. El stemmer de
+    // `porter` no está implementado en Rust, por lo que se compara la palabra sin reducir.
+    let stemming = matches!(
+        tokenizer.tokenizer,
+        FtsTokenizer::Spanish | FtsTokenizer::Porter
+    );
+    let word: String = normalized.iter().collect();
+    let stem = match tokenizer.tokenizer {
+        FtsTokenizer::Spanish => stemmer::stem(&word),
+        _ => word.clone(),
+    };
+
+    // Los términos que comienzan con la misma letra, como un rango sobre `term` para que
+    // `fts5vocab` no recorra todo el vocabulario.
+    let mut statement = db.prepare(
+        "select term, doc from fts_tnea_vocab
+        where term >= ?1 and term < ?1 || char(1114111)
+        and length(term) between ?2 and ?3",
+    )?;
+
+    let min_len = if stemming {
+        4
+    } else {
+        normalized.len().saturating_sub(MAX_DISTANCIA)
+    };
+    let max_len = normalized.len() + MAX_DISTANCIA;
+
+    let mut candidatos: Vec<(String, f32, f32)> = statement
+        .query_map(rusqlite::params![first, min_len, max_len], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?))
+        })?
+        .filter_map(Result::ok)
+        .filter(|(term, _)| *term != word && *term != stem)
+        .filter_map(|(term, docs)| {
+            let term_chars: Vec<char> = term.chars().collect();
+
+            let (distancia, largo) = if stemming {
+                if term_chars.len() + 3 < stem.chars().count() {
+                    return None;
+                }
+                (prefix_distance(&normalized, &term_chars), term_chars.len())
+            } else {
+                (
+                    levenshtein(&normalized, &term_chars),
+                    term_chars.len().max(normalized.len()),
+                )
+            };
+
+            let similitud = 1.0 - distancia as f32 / largo as f32;
+
+            (distancia <= MAX_DISTANCIA && similitud >= min_similitud).then(|| {
+                let peso = similitud * (1.0 + docs as f32).ln();
+                (term, similitud, peso)
+            })
+        })
+        .collect();
+
+    candidatos.sort_by(|a, b| b.2.total_cmp(&a.2));
+    candidatos.truncate(MAX_VARIANTES);
+
+    Ok(candidatos
+        .into_iter()
+        .map(|(term, similitud, _)| (term, similitud))
+        .collect())
+}
+
+/// Busca cómo aparece escrito `term` en algún registro, ya que el vocabulario de los tokenizers
+/// con stemming solamente tiene la raíz. `fts_tnea_vocab_instance` indica en qué columna de qué
+/// registro aparece, y la palabra se busca entre las de esa columna: la que el tokenizer indexa
+/// como `term` o, si no se puede reproducir el tokenizer, la que está en la posición indicada.
+fn forma_original(db: &Connection, tokenizer: &TokenizerConfig, term: &str) -> Option<String> {
+    let (doc, col, offset): (i64, String, usize) = db
+        .query_row(
+            "select doc, col, offset from fts_tnea_vocab_instance where term = ? limit 1",
+            [term],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .ok()?;
+
+    // El nombre de la columna se interpola, por lo que solo se aceptan las de `fts_tnea`.
+    if !FTS_COLUMNS.contains(&col.as_str()) {
+        return None;
+    }
+
+    let text: String = db
+        .query_row(
+            &format!("select {col} from fts_tnea where rowid = ?"),
+            [doc],
+            |row| row.get(0),
+        )
+        .ok()?;
+
+    // Separa las palabras como `unicode61`, que es la base de todos los tokenizers con palabras.
+    let mut palabras = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|palabra| !palabra.is_empty());
+
+    let palabra = match tokenizer.tokenizer {
+        FtsTokenizer::Porter | FtsTokenizer::Trigram => palabras.nth(offset),
+        FtsTokenizer::Unicode61 | FtsTokenizer::Spanish => {
+            palabras.find(|palabra| tokenizer.termino(palabra).as_deref() == Some(term))
+        }
+    }?;
+
+    Some(palabra.to_lowercase())
+}
+
+/// La menor distancia de edición entre `term` y algún comienzo de `word` de largo similar.
+fn prefix_distance(word: &[char], term: &[char]) -> usize {
+    let min = term.len().saturating_sub(1).max(1);
+    let max = (term.len() + 1).min(word.len());
+
+    (min..=max)
+        .map(|len| levenshtein(&word[..len], term))
+        .chain(std::iter::once(levenshtein(word, term)))
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
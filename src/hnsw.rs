@@ -0,0 +1,1110 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc, RwLock,
+    },
+};
+
+use rusqlite::{functions::FunctionFlags, Connection};
+
+use crate::{cuantizacion::Cuantizacion, dimensiones, metrica::Metrica, sqlite};
+
+/// Clave de `metadata` que indica si `sync_vec_tnea` también construye el índice HNSW.
+pub const METADATA_HNSW: &str = "hnsw";
+/// Claves de `metadata` con los parámetros de construcción del índice.
+const METADATA_M: &str = "hnsw_m";
+const METADATA_EF_CONSTRUCCION: &str = "hnsw_ef_construccion";
+
+/// Encabezado y versión del formato del archivo del índice.
+const MAGIA: &[u8; 4] = b"HNSW";
+const VERSION: u32 = 2;
+
+/// Semilla del generador de niveles, para que el mismo `vec_tnea` genere el mismo índice.
+const SEMILLA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+#[derive(Debug, Clone)]
+pub struct HnswSettings {
+    /// Tamaño de la lista de candidatos al buscar. Con un valor mayor la búsqueda es más precisa
+    /// pero más lenta. Nunca es menor a la cantidad de vecinos pedidos.
+    pub ef: usize,
+    /// Con menos registros, o cuando la búsqueda tiene filtros, se usa la búsqueda exacta.
+    pub min_registros: usize,
+}
+
+impl Default for HnswSettings {
+    fn default() -> Self {
+        Self {
+            ef: 100,
+            min_registros: 10_000,
+        }
+    }
+}
+
+/// Cómo se buscan los vecinos de `:embedding` en `vec_tnea`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indice {
+    /// Búsqueda exacta de vec0, sobre los vectores cuantizados si se configuró una cuantización.
+    Exacto(Cuantizacion),
+    /// Búsqueda aproximada con el índice HNSW cargado en memoria.
+    Hnsw,
+}
+
+impl Indice {
+    /// Consulta que devuelve `row_id` y `distance` de los `k` vecinos de `vec_tnea` más cercanos
+    /// a `:embedding`. El índice HNSW solamente propone los candidatos, cuya distancia se calcula
+    /// con los vectores de `vec_tnea`, por lo que es la misma que la de la búsqueda exacta aunque
+    /// el embedding haya cambiado después de construir el índice.
+    #[must_use]
+    pub fn knn_sql(self, k: &str, metrica: Metrica) -> String {
+        match self {
+            Indice::Exacto(cuantizacion) => cuantizacion.knn_sql(k, metrica),
+            Indice::Hnsw => format!(
+                "select
+                    vec_tnea.row_id,
+                    {} as distance
+                from json_each(hnsw_knn(:embedding, {k})) as candidatos
+                join vec_tnea on vec_tnea.row_id = candidatos.value
+                order by distance
+                limit {k}",
+                metrica.distancia_entre("vec_tnea.template_embedding", ":embedding")
+            ),
+        }
+    }
+}
+
+/// Un nodo del grafo junto con su distancia a la consulta, ordenado por distancia.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidato {
+    distancia: f32,
+    nodo: u32,
+}
+
+impl Eq for Candidato {}
+
+impl PartialOrd for Candidato {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidato {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distancia
+            .total_cmp(&other.distancia)
+            .then(self.nodo.cmp(&other.nodo))
+    }
+}
+
+/// Índice HNSW (Hierarchical Navigable Small World) sobre los embeddings de `vec_tnea`.
+///
+/// Cada nodo está en los niveles `0..=n`, con `n` elegido al azar con probabilidad decreciente,
+/// y en cada nivel se conecta con hasta `m` de sus vecinos más cercanos (`2 * m` en el nivel 0).
+/// Una búsqueda baja desde el nivel más alto acercándose a la consulta, y en el nivel 0 explora
+/// los `ef` candidatos más cercanos que encuentra, por lo que no recorre todos los vectores.
+///
+/// Los nodos eliminados siguen en el grafo para no desconectar a sus vecinos, pero no se
+/// devuelven en las búsquedas. Se descartan al volver a construir el índice en la sincronización.
+pub struct Hnsw {
+    dimensiones: usize,
+    metrica: Metrica,
+    m: usize,
+    ef_construccion: usize,
+    /// Tamaño de la lista de candidatos al buscar, no se guarda con el índice.
+    pub ef: usize,
+    /// Cantidad de vectores a partir de la cual se usa el índice, no se guarda con el índice.
+    pub min_registros: usize,
+    ids: Vec<u64>,
+    posiciones: HashMap<u64, u32>,
+    /// Vectores de todos los nodos uno detrás del otro, normalizados con la métrica coseno.
+    vectores: Vec<f32>,
+    /// Vecinos de cada nodo en cada uno de sus niveles.
+    vecinos: Vec<Vec<Vec<u32>>>,
+    /// Nodos de los registros eliminados.
+    eliminados: HashSet<u32>,
+    entrada: Option<u32>,
+    semilla: u64,
+    /// Si cambió desde que se cargó o se guardó con [`persistir`], no se guarda con el índice.
+    modificado: AtomicBool,
+}
+
+impl std::fmt::Debug for Hnsw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hnsw")
+            .field("vectores", &self.len())
+            .field("eliminados", &self.eliminados.len())
+            .field("dimensiones", &self.dimensiones)
+            .field("metrica", &self.metrica)
+            .field("m", &self.m)
+            .field("ef_construccion", &self.ef_construccion)
+            .field("ef", &self.ef)
+            .field("min_registros", &self.min_registros)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hnsw {
+    #[must_use]
+    pub fn new(dimensiones: usize, metrica: Metrica, m: usize, ef_construccion: usize) -> Self {
+        Self {
+            dimensiones,
+            metrica,
+            m: m.max(2),
+            ef_construccion: ef_construccion.max(1),
+            ef: ef_construccion.max(1),
+            min_registros: 0,
+            ids: Vec::new(),
+            posiciones: HashMap::new(),
+            vectores: Vec::new(),
+            vecinos: Vec::new(),
+            eliminados: HashSet::new(),
+            entrada: None,
+            semilla: SEMILLA,
+            modificado: AtomicBool::new(false),
+        }
+    }
+
+    /// Cantidad de vectores, sin contar los eliminados.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ids.len() - self.eliminados.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[must_use]
+    pub fn contiene(&self, id: u64) -> bool {
+        self.posiciones
+            .get(&id)
+            .is_some_and(|nodo| !self.eliminados.contains(nodo))
+    }
+
+    fn vector(&self, nodo: u32) -> &[f32] {
+        let inicio = nodo as usize * self.dimensiones;
+        &self.vectores[inicio..inicio + self.dimensiones]
+    }
+
+    /// Con la métrica coseno los vectores se normalizan, para que la distancia sea `1 - a·b`.
+    fn preparar(&self, vector: &[f32]) -> Vec<f32> {
+        match self.metrica {
+            Metrica::L2 => vector.to_vec(),
+            Metrica::Coseno => {
+                let norma = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+                if norma <= f32::EPSILON {
+                    vector.to_vec()
+                } else {
+                    vector.iter().map(|x| x / norma).collect()
+                }
+            }
+        }
+    }
+
+    /// Distancia entre dos vectores preparados. Solamente se usa para ordenar, por lo que con
+    /// L2 no se calcula la raíz cuadrada.
+    fn distancia(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.metrica {
+            Metrica::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+            Metrica::Coseno => 1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+        }
+    }
+
+    /// Máxima cantidad de vecinos de un nodo en el nivel.
+    fn max_vecinos(&self, nivel: usize) -> usize {
+        if nivel == 0 {
+            self.m * 2
+        } else {
+            self.m
+        }
+    }
+
+    /// Nivel máximo de un nodo nuevo, con distribución geométrica de parámetro `1 / ln(m)`.
+    fn nivel_aleatorio(&mut self) -> usize {
+        // splitmix64
+        self.semilla = self.semilla.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.semilla;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        // Entre 0 (exclusivo) y 1.
+        let uniforme = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniforme.ln() / (self.m as f64).ln()).floor() as usize
+    }
+
+    /// Busca en un nivel los `ef` nodos más cercanos a la consulta partiendo de `entradas`, y
+    /// los devuelve ordenados del más cercano al más lejano. Los nodos eliminados se recorren
+    /// igual, pero solamente se devuelven con `con_eliminados`.
+    fn buscar_en_nivel(
+        &self,
+        consulta: &[f32],
+        entradas: &[u32],
+        ef: usize,
+        nivel: usize,
+        con_eliminados: bool,
+    ) -> Vec<Candidato> {
+        let mut visitados: HashSet<u32> = entradas.iter().copied().collect();
+        let mut candidatos: BinaryHeap<Reverse<Candidato>> = BinaryHeap::new();
+        let mut resultados: BinaryHeap<Candidato> = BinaryHeap::new();
+
+        for &nodo in entradas {
+            let candidato = Candidato {
+                distancia: self.distancia(consulta, self.vector(nodo)),
+                nodo,
+            };
+            candidatos.push(Reverse(candidato));
+            if con_eliminados || !self.eliminados.contains(&nodo) {
+                resultados.push(candidato);
+            }
+        }
+
+        while let Some(Reverse(actual)) = candidatos.pop() {
+            if resultados
+                .peek()
+                .is_some_and(|peor| actual.distancia > peor.distancia && resultados.len() >= ef)
+            {
+                break;
+            }
+
+            for &vecino in &self.vecinos[actual.nodo as usize][nivel] {
+                if !visitados.insert(vecino) {
+                    continue;
+                }
+
+                let candidato = Candidato {
+                    distancia: self.distancia(consulta, self.vector(vecino)),
+                    nodo: vecino,
+                };
+
+                if resultados.len() < ef
+                    || resultados
+                        .peek()
+                        .is_some_and(|peor| candidato.distancia < peor.distancia)
+                {
+                    candidatos.push(Reverse(candidato));
+                    if con_eliminados || !self.eliminados.contains(&vecino) {
+                        resultados.push(candidato);
+                        if resultados.len() > ef {
+                            resultados.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        resultados.into_sorted_vec()
+    }
+
+    /// Baja por los niveles mayores a `hasta` quedándose con el nodo más cercano a la consulta.
+    fn descender(&self, consulta: &[f32], entrada: u32, hasta: usize) -> u32 {
+        let mut actual = entrada;
+        let tope = self.vecinos[entrada as usize].len() - 1;
+
+        for nivel in (hasta + 1..=tope).rev() {
+            if let Some(mejor) = self
+                .buscar_en_nivel(consulta, &[actual], 1, nivel, true)
+                .first()
+            {
+                actual = mejor.nodo;
+            }
+        }
+
+        actual
+    }
+
+    /// Agrega un vector al índice. Si el id ya estaba, aunque se haya eliminado, se reemplaza su
+    /// vector y se vuelven a elegir sus vecinos, ya que los anteriores se eligieron con el vector
+    /// viejo.
+    pub fn insertar(&mut self, id: u64, vector: &[f32]) {
+        let vector = self.preparar(vector);
+        *self.modificado.get_mut() = true;
+
+        if let Some(&nodo) = self.posiciones.get(&id) {
+            self.eliminados.remove(&nodo);
+            self.reconectar(nodo, &vector);
+            return;
+        }
+
+        let nodo = self.ids.len() as u32;
+        let nivel = self.nivel_aleatorio();
+
+        self.ids.push(id);
+        self.posiciones.insert(id, nodo);
+        self.vectores.extend_from_slice(&vector);
+        self.vecinos.push(vec![Vec::new(); nivel + 1]);
+
+        let Some(entrada) = self.entrada else {
+            self.entrada = Some(nodo);
+            return;
+        };
+
+        let tope = self.vecinos[entrada as usize].len() - 1;
+        self.enlazar(nodo, &vector, entrada);
+
+        if nivel > tope {
+            self.entrada = Some(nodo);
+        }
+    }
+
+    /// Marca como eliminado el vector del id, que deja de aparecer en las búsquedas. Devuelve
+    /// `false` si el id no estaba en el índice.
+    pub fn eliminar(&mut self, id: u64) -> bool {
+        let Some(&nodo) = self.posiciones.get(&id) else {
+            return false;
+        };
+
+        *self.modificado.get_mut() = true;
+        self.eliminados.insert(nodo)
+    }
+
+    /// Reemplaza el vector de un nodo y lo vuelve a enlazar en sus niveles, que no cambian. Las
+    /// conexiones de otros nodos hacia él se conservan para que ninguno quede inalcanzable, y se
+    /// descartan al podar esas listas, que compara las distancias con el vector nuevo.
+    fn reconectar(&mut self, nodo: u32, vector: &[f32]) {
+        let inicio = nodo as usize * self.dimensiones;
+        self.vectores[inicio..inicio + self.dimensiones].copy_from_slice(vector);
+
+        if let Some(entrada) = self.entrada {
+            self.enlazar(nodo, vector, entrada);
+        }
+    }
+
+    /// Elige los vecinos del nodo en cada uno de sus niveles, buscándolos desde `entrada`, y lo
+    /// agrega a las listas de esos vecinos.
+    fn enlazar(&mut self, nodo: u32, vector: &[f32], entrada: u32) {
+        let nivel = self.vecinos[nodo as usize].len() - 1;
+        let tope = self.vecinos[entrada as usize].len() - 1;
+        let mut entradas = vec![self.descender(vector, entrada, nivel)];
+
+        for nivel_actual in (0..=nivel.min(tope)).rev() {
+            let cercanos = self.buscar_en_nivel(
+                vector,
+                &entradas,
+                self.ef_construccion + 1,
+                nivel_actual,
+                true,
+            );
+            // Al reconectar, el nodo ya está en el grafo y se encuentra a sí mismo.
+            let elegidos: Vec<u32> = cercanos
+                .iter()
+                .map(|candidato| candidato.nodo)
+                .filter(|&vecino| vecino != nodo)
+                .take(self.m)
+                .collect();
+
+            for &vecino in &elegidos {
+                if !self.vecinos[vecino as usize][nivel_actual].contains(&nodo) {
+                    self.conectar(vecino, nodo, nivel_actual);
+                }
+            }
+            self.vecinos[nodo as usize][nivel_actual] = elegidos;
+
+            entradas = cercanos.iter().map(|candidato| candidato.nodo).collect();
+        }
+    }
+
+    /// Agrega `nuevo` a los vecinos de `nodo` en el nivel. Si supera el máximo, se conservan los
+    /// más cercanos.
+    fn conectar(&mut self, nodo: u32, nuevo: u32, nivel: usize) {
+        self.vecinos[nodo as usize][nivel].push(nuevo);
+
+        let maximo = self.max_vecinos(nivel);
+        if self.vecinos[nodo as usize][nivel].len() <= maximo {
+            return;
+        }
+
+        let base = self.vector(nodo);
+        let mut vecinos: Vec<Candidato> = self.vecinos[nodo as usize][nivel]
+            .iter()
+            .map(|&vecino| Candidato {
+                distancia: self.distancia(base, self.vector(vecino)),
+                nodo: vecino,
+            })
+            .collect();
+        vecinos.sort();
+        vecinos.truncate(maximo);
+
+        self.vecinos[nodo as usize][nivel] = vecinos.into_iter().map(|c| c.nodo).collect();
+    }
+
+    /// Devuelve los ids de los `k` vectores aproximadamente más cercanos a la consulta, del más
+    /// cercano al más lejano, sin los eliminados.
+    #[must_use]
+    pub fn buscar(&self, consulta: &[f32], k: usize) -> Vec<u64> {
+        let Some(entrada) = self.entrada else {
+            return Vec::new();
+        };
+        if consulta.len() != self.dimensiones {
+            return Vec::new();
+        }
+
+        let consulta = self.preparar(consulta);
+        let entrada = self.descender(&consulta, entrada, 0);
+
+        self.buscar_en_nivel(&consulta, &[entrada], self.ef.max(k), 0, false)
+            .into_iter()
+            .take(k)
+            .map(|candidato| self.ids[candidato.nodo as usize])
+            .collect()
+    }
+
+    /// Guarda el índice en un archivo binario con enteros y flotantes en little endian.
+    pub fn guardar(&self, ruta: &Path) -> eyre::Result<()> {
+        let mut archivo = BufWriter::new(File::create(ruta)?);
+
+        archivo.write_all(MAGIA)?;
+        for valor in [
+            VERSION,
+            self.dimensiones as u32,
+            self.m as u32,
+            self.ef_construccion as u32,
+        ] {
+            archivo.write_all(&valor.to_le_bytes())?;
+        }
+        archivo.write_all(self.metrica.to_string().as_bytes())?;
+        archivo.write_all(b"\n")?;
+        archivo.write_all(&(self.ids.len() as u64).to_le_bytes())?;
+        archivo.write_all(&self.entrada.map_or(u32::MAX, |e| e).to_le_bytes())?;
+        archivo.write_all(&self.semilla.to_le_bytes())?;
+
+        for (nodo, id) in self.ids.iter().enumerate() {
+            archivo.write_all(&id.to_le_bytes())?;
+            for valor in self.vector(nodo as u32) {
+                archivo.write_all(&valor.to_le_bytes())?;
+            }
+
+            let niveles = &self.vecinos[nodo];
+            archivo.write_all(&(niveles.len() as u32).to_le_bytes())?;
+            for vecinos in niveles {
+                archivo.write_all(&(vecinos.len() as u32).to_le_bytes())?;
+                for vecino in vecinos {
+                    archivo.write_all(&vecino.to_le_bytes())?;
+                }
+            }
+        }
+
+        let mut eliminados: Vec<u32> = self.eliminados.iter().copied().collect();
+        eliminados.sort_unstable();
+        archivo.write_all(&(eliminados.len() as u64).to_le_bytes())?;
+        for nodo in eliminados {
+            archivo.write_all(&nodo.to_le_bytes())?;
+        }
+
+        archivo.flush()?;
+        Ok(())
+    }
+
+    /// Lee un índice guardado con [`Hnsw::guardar`].
+    pub fn cargar(ruta: &Path) -> eyre::Result<Self> {
+        let mut archivo = BufReader::new(File::open(ruta)?);
+
+        let mut magia = [0; 4];
+        archivo.read_exact(&mut magia)?;
+        if &magia != MAGIA {
+            return Err(eyre::eyre!("`{}` no es un índice HNSW.", ruta.display()));
+        }
+
+        let version = leer_u32(&mut archivo)?;
+        if version != VERSION {
+            return Err(eyre::eyre!(
+                "El índice `{}` tiene la versión {version}, se esperaba la {VERSION}.",
+                ruta.display()
+            ));
+        }
+
+        let dimensiones = leer_u32(&mut archivo)? as usize;
+        let m = leer_u32(&mut archivo)? as usize;
+        let ef_construccion = leer_u32(&mut archivo)? as usize;
+
+        let mut metrica = Vec::new();
+        loop {
+            let mut byte = [0];
+            archivo.read_exact(&mut byte)?;
+            if byte[0] == b'\n' {
+                break;
+            }
+            metrica.push(byte[0]);
+        }
+        let metrica: Metrica = String::from_utf8(metrica)?
+            .parse()
+            .map_err(|err: String| eyre::eyre!(err))?;
+
+        let mut hnsw = Self::new(dimensiones, metrica, m, ef_construccion);
+
+        let cantidad = leer_u64(&mut archivo)? as usize;
+        hnsw.entrada = Some(leer_u32(&mut archivo)?).filter(|entrada| *entrada != u32::MAX);
+        hnsw.semilla = leer_u64(&mut archivo)?;
+
+        hnsw.ids.reserve(cantidad);
+        hnsw.vectores.reserve(cantidad * dimensiones);
+        hnsw.vecinos.reserve(cantidad);
+
+        for nodo in 0..cantidad {
+            let id = leer_u64(&mut archivo)?;
+            hnsw.ids.push(id);
+            hnsw.posiciones.insert(id, nodo as u32);
+
+            for _ in 0..dimensiones {
+                hnsw.vectores.push(f32::from_bits(leer_u32(&mut archivo)?));
+            }
+
+            let niveles = leer_u32(&mut archivo)? as usize;
+            let mut vecinos = Vec::with_capacity(niveles);
+            for _ in 0..niveles {
+                let cantidad = leer_u32(&mut archivo)? as usize;
+                vecinos.push(
+                    (0..cantidad)
+                        .map(|_| leer_u32(&mut archivo))
+                        .collect::<std::io::Result<Vec<_>>>()?,
+                );
+            }
+            hnsw.vecinos.push(vecinos);
+        }
+
+        let eliminados = leer_u64(&mut archivo)? as usize;
+        for _ in 0..eliminados {
+            hnsw.eliminados.insert(leer_u32(&mut archivo)?);
+        }
+
+        Ok(hnsw)
+    }
+}
+
+fn leer_u32(archivo: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    archivo.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn leer_u64(archivo: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    archivo.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn a_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// Archivo del índice, junto al de la base de datos.
+pub fn ruta() -> eyre::Result<PathBuf> {
+    let path = std::env::var("DATABASE_URL").map_err(|err| {
+        eyre::eyre!(
+            "La variable de ambiente `DATABASE_URL` no fue encontrada. {}",
+            err
+        )
+    })?;
+    Ok(PathBuf::from(format!("{path}.hnsw")))
+}
+
+/// Si la base de datos se sincronizó con el índice HNSW.
+pub fn habilitado(db: &Connection) -> eyre::Result<bool> {
+    Ok(sqlite::get_metadata(db, METADATA_HNSW)?.as_deref() == Some("true"))
+}
+
+/// Habilita el índice HNSW y registra sus parámetros de construcción en `metadata`.
+pub fn configurar(db: &Connection, m: usize, ef_construccion: usize) -> eyre::Result<()> {
+    if m < 2 || ef_construccion == 0 {
+        return Err(eyre::eyre!(
+            "El índice HNSW requiere `m` mayor a 1 y `ef_construccion` mayor a 0."
+        ));
+    }
+
+    sqlite::set_metadata(db, METADATA_HNSW, "true")?;
+    sqlite::set_metadata(db, METADATA_M, &m.to_string())?;
+    sqlite::set_metadata(db, METADATA_EF_CONSTRUCCION, &ef_construccion.to_string())?;
+
+    Ok(())
+}
+
+/// Construye el índice con todos los vectores de `vec_tnea`.
+pub fn construir(db: &Connection) -> eyre::Result<Hnsw> {
+    let parametro = |clave: &str, defecto: usize| -> eyre::Result<usize> {
+        Ok(sqlite::get_metadata(db, clave)?
+            .and_then(|valor| valor.parse().ok())
+            .unwrap_or(defecto))
+    };
+
+    let dimensiones = dimensiones::de_vec_tnea(db)?
+        .ok_or_else(|| eyre::eyre!("La tabla `vec_tnea` no existe."))?;
+    let mut hnsw = Hnsw::new(
+        dimensiones,
+        Metrica::from_db(db)?,
+        parametro(METADATA_M, 16)?,
+        parametro(METADATA_EF_CONSTRUCCION, 200)?,
+    );
+
+    let mut statement = db.prepare("select row_id, template_embedding from vec_tnea")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let bytes: Vec<u8> = row.get(1)?;
+        hnsw.insertar(row.get(0)?, &a_vector(&bytes));
+    }
+
+    Ok(hnsw)
+}
+
+/// Si está habilitado, construye el índice HNSW a partir de `vec_tnea` y lo guarda junto a la
+/// base de datos. Se llama luego de cada sincronización de `vec_tnea`.
+pub fn sincronizar(db: &Connection) -> eyre::Result<()> {
+    if !habilitado(db)? {
+        return Ok(());
+    }
+
+    let start = std::time::Instant::now();
+    tracing::info!("Construyendo el índice HNSW...");
+
+    let hnsw = construir(db)?;
+    let ruta = ruta()?;
+    hnsw.guardar(&ruta)?;
+
+    tracing::info!(
+        "Construyendo el índice HNSW... listo! {} vectores en `{}`, tomó {} ms",
+        hnsw.len(),
+        ruta.display(),
+        start.elapsed().as_millis()
+    );
+
+    Ok(())
+}
+
+/// Guarda el índice del servidor si cambió desde que se cargó, para que los perfiles creados,
+/// modificados o eliminados se conserven al reiniciarlo. Se escribe en un archivo temporal que
+/// luego reemplaza al anterior, por lo que un fallo no deja el índice a medio escribir. Devuelve
+/// `true` si se guardó.
+pub fn persistir(hnsw: &RwLock<Hnsw>) -> eyre::Result<bool> {
+    let hnsw = hnsw.read().map_err(|err| eyre::eyre!("{err}"))?;
+    if !hnsw.modificado.load(AtomicOrdering::Relaxed) {
+        return Ok(false);
+    }
+
+    let ruta = ruta()?;
+    let temporal = ruta.with_extension("hnsw.tmp");
+    hnsw.guardar(&temporal)?;
+    std::fs::rename(&temporal, &ruta)?;
+
+    // Mientras se tiene el lock de lectura el índice no puede cambiar.
+    hnsw.modificado.store(false, AtomicOrdering::Relaxed);
+
+    Ok(true)
+}
+
+/// Agrega o vuelve a enlazar en el índice los vectores de `vec_tnea` con los ids indicados, que
+/// son los que se reescribieron después de construirlo. Los registros eliminados se descartan al
+/// unir los candidatos con `vec_tnea`.
+pub fn actualizar(db: &Connection, hnsw: &RwLock<Hnsw>, ids: &[u64]) -> eyre::Result<usize> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let vectores: Vec<(u64, Vec<u8>)> = db
+        .prepare(
+            "select row_id, template_embedding from vec_tnea
+            where row_id in (select value from json_each(?))",
+        )?
+        .query_map([serde_json::Value::from(ids).to_string()], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut hnsw = hnsw.write().map_err(|err| eyre::eyre!("{err}"))?;
+    for (id, bytes) in &vectores {
+        hnsw.insertar(*id, &a_vector(bytes));
+    }
+
+    Ok(vectores.len())
+}
+
+/// Carga el índice para el servidor si la base de datos se sincronizó con él. Si el archivo no
+/// existe, no se puede leer o no coincide con la dimensión o la métrica de `vec_tnea`, se usa la
+/// búsqueda exacta.
+pub fn cargar(db: &Connection, settings: &HnswSettings) -> eyre::Result<Option<Hnsw>> {
+    if !habilitado(db)? {
+        return Ok(None);
+    }
+
+    let ruta = ruta()?;
+    if !ruta.exists() {
+        tracing::warn!(
+            "El índice HNSW `{}` no existe, ejecuta `sync -S vector`. Se usará la búsqueda exacta.",
+            ruta.display()
+        );
+        return Ok(None);
+    }
+
+    let start = std::time::Instant::now();
+    let mut hnsw = match Hnsw::cargar(&ruta) {
+        Ok(hnsw) => hnsw,
+        Err(err) => {
+            tracing::warn!(
+                "No se pudo leer el índice HNSW: {err} Ejecuta `sync -S vector` para \
+                reconstruirlo. Se usará la búsqueda exacta."
+            );
+            return Ok(None);
+        }
+    };
+
+    let dimensiones = dimensiones::de_vec_tnea(db)?;
+    let metrica = Metrica::from_db(db)?;
+    if Some(hnsw.dimensiones) != dimensiones || hnsw.metrica != metrica {
+        tracing::warn!(
+            "El índice HNSW `{}` fue construido con {} dimensiones y la métrica `{}`, que no \
+            coinciden con las de `vec_tnea`. Ejecuta `sync -S vector` para reconstruirlo. Se \
+            usará la búsqueda exacta.",
+            ruta.display(),
+            hnsw.dimensiones,
+            hnsw.metrica
+        );
+        return Ok(None);
+    }
+
+    hnsw.ef = settings.ef;
+    hnsw.min_registros = settings.min_registros;
+
+    tracing::info!(
+        "Se cargó el índice HNSW `{}` con {} vectores en {} ms",
+        ruta.display(),
+        hnsw.len(),
+        start.elapsed().as_millis()
+    );
+
+    Ok(Some(hnsw))
+}
+
+/// Registra en la conexión la función `hnsw_knn(embedding, k)`, que devuelve un arreglo JSON
+/// con los ids de los `k` vecinos aproximados del embedding según el índice.
+pub fn registrar(db: &Connection, hnsw: Arc<RwLock<Hnsw>>) -> eyre::Result<()> {
+    db.create_scalar_function("hnsw_knn", 2, FunctionFlags::SQLITE_UTF8, move |ctx| {
+        let bytes: Vec<u8> = ctx.get(0)?;
+        let k: i64 = ctx.get(1)?;
+
+        let hnsw = hnsw
+            .read()
+            .map_err(|err| rusqlite::Error::UserFunctionError(err.to_string().into()))?;
+        let ids = hnsw.buscar(&a_vector(&bytes), usize::try_from(k).unwrap_or_default());
+
+        Ok(serde_json::Value::from(ids).to_string())
+    })?;
+
+    Ok(())
+}
+
+/// Cantidad de vecinos que se comparan al calcular el recall en `db stats`.
+const K_RECALL: usize = 10;
+/// Cantidad de registros de `vec_tnea` que se usan como consultas al calcular el recall.
+const CONSULTAS_RECALL: usize = 20;
+
+/// Comparación de la búsqueda con el índice HNSW contra la búsqueda exacta.
+#[derive(Debug, Clone)]
+pub struct Comparacion {
+    pub vectores: usize,
+    pub m: usize,
+    pub ef: usize,
+    pub consultas: usize,
+    /// Proporción de los `k` vecinos exactos que también encontró el índice.
+    pub recall: f64,
+    /// Latencia promedio por consulta, en milisegundos.
+    pub latencia_exacta: f64,
+    pub latencia_hnsw: f64,
+}
+
+impl std::fmt::Display for Comparacion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Índice HNSW: {} vectores, m={}, ef={} ({} consultas con registros de vec_tnea)",
+            self.vectores, self.m, self.ef, self.consultas
+        )?;
+        writeln!(
+            f,
+            "Recall@{K_RECALL} contra la búsqueda exacta: {:.3}",
+            self.recall
+        )?;
+        write!(
+            f,
+            "Latencia promedio: exacta {:.2} ms, HNSW {:.2} ms",
+            self.latencia_exacta, self.latencia_hnsw
+        )
+    }
+}
+
+/// Carga el índice guardado y compara los vecinos que encuentra con los de la búsqueda exacta,
+/// usando como consultas los embeddings de algunos registros de `vec_tnea`. Devuelve `None` si
+/// el índice no está habilitado o no existe.
+pub fn comparar(db: &Connection, ef: usize) -> eyre::Result<Option<Comparacion>> {
+    let ruta = ruta()?;
+    if !habilitado(db)? || !ruta.exists() {
+        return Ok(None);
+    }
+
+    let mut hnsw = Hnsw::cargar(&ruta)?;
+    hnsw.ef = ef;
+
+    let consultas: Vec<Vec<u8>> = db
+        .prepare("select template_embedding from vec_tnea order by random() limit ?")?
+        .query_map([CONSULTAS_RECALL], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    if consultas.is_empty() {
+        return Ok(None);
+    }
+
+    let mut exacta = db.prepare(&format!(
+        "select row_id from ({})",
+        Cuantizacion::Float.knn_sql(&K_RECALL.to_string(), hnsw.metrica)
+    ))?;
+
+    let (mut encontrados, mut total) = (0, 0);
+    let (mut latencia_exacta, mut latencia_hnsw) = (0.0, 0.0);
+
+    for bytes in &consultas {
+        let start = std::time::Instant::now();
+        let esperados = exacta
+            .query_map(rusqlite::named_params! { ":embedding": bytes }, |row| {
+                row.get::<_, u64>(0)
+            })?
+            .collect::<Result<HashSet<u64>, _>>()?;
+        latencia_exacta += start.elapsed().as_secs_f64() * 1000.0;
+
+        let start = std::time::Instant::now();
+        let obtenidos: HashSet<u64> = hnsw
+            .buscar(&a_vector(bytes), K_RECALL)
+            .into_iter()
+            .collect();
+        latencia_hnsw += start.elapsed().as_secs_f64() * 1000.0;
+
+        total += esperados.len();
+        encontrados += esperados.intersection(&obtenidos).count();
+    }
+
+    let n = consultas.len() as f64;
+
+    Ok(Some(Comparacion {
+        vectores: hnsw.len(),
+        m: hnsw.m,
+        ef,
+        consultas: consultas.len(),
+        recall: encontrados as f64 / total.max(1) as f64,
+        latencia_exacta: latencia_exacta / n,
+        latencia_hnsw: latencia_hnsw / n,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONES: usize = 8;
+
+    /// Vectores pseudoaleatorios con valores entre -1 y 1, siempre los mismos para una semilla.
+    fn aleatorios(cantidad: usize, semilla: u64) -> Vec<Vec<f32>> {
+        let mut estado = semilla;
+        let mut siguiente = move || {
+            estado = estado
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (estado >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        };
+
+        (0..cantidad)
+            .map(|_| (0..DIMENSIONES).map(|_| siguiente()).collect())
+            .collect()
+    }
+
+    fn indice(metrica: Metrica, vectores: &[Vec<f32>]) -> Hnsw {
+        let mut hnsw = Hnsw::new(DIMENSIONES, metrica, 8, 64);
+        for (id, vector) in vectores.iter().enumerate() {
+            hnsw.insertar(id as u64, vector);
+        }
+        hnsw
+    }
+
+    /// Los ids de los `k` vectores del índice más cercanos a la consulta, recorriéndolos todos.
+    fn exactos(hnsw: &Hnsw, vectores: &[Vec<f32>], consulta: &[f32], k: usize) -> Vec<u64> {
+        let consulta = hnsw.preparar(consulta);
+        let mut distancias: Vec<(f32, u64)> = vectores
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| hnsw.contiene(*id as u64))
+            .map(|(id, vector)| {
+                let distancia = hnsw.distancia(&consulta, &hnsw.preparar(vector));
+                (distancia, id as u64)
+            })
+            .collect();
+        distancias.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        distancias.into_iter().take(k).map(|(_, id)| id).collect()
+    }
+
+    /// Proporción de los vecinos exactos de las consultas que devuelve el índice.
+    fn recall(hnsw: &Hnsw, vectores: &[Vec<f32>], consultas: &[Vec<f32>], k: usize) -> f32 {
+        let encontrados: usize = consultas
+            .iter()
+            .map(|consulta| {
+                let exactos = exactos(hnsw, vectores, consulta, k);
+                hnsw.buscar(consulta, k)
+                    .iter()
+                    .filter(|id| exactos.contains(id))
+                    .count()
+            })
+            .sum();
+
+        encontrados as f32 / (consultas.len() * k) as f32
+    }
+
+    #[test]
+    fn encuentra_los_vecinos_exactos() {
+        let vectores = aleatorios(1000, 1);
+        let consultas = aleatorios(50, 2);
+
+        for metrica in [Metrica::L2, Metrica::Coseno] {
+            let hnsw = indice(metrica, &vectores);
+            assert_eq!(hnsw.len(), vectores.len());
+
+            let recall = recall(&hnsw, &vectores, &consultas, 10);
+            assert!(recall >= 0.95, "recall de {recall} con {metrica}");
+
+            for (id, vector) in vectores.iter().enumerate() {
+                assert_eq!(hnsw.buscar(vector, 1), [id as u64]);
+            }
+        }
+    }
+
+    #[test]
+    fn busquedas_sin_resultados() {
+        let hnsw = Hnsw::new(DIMENSIONES, Metrica::L2, 8, 64);
+        assert!(hnsw.buscar(&[0.0; DIMENSIONES], 5).is_empty());
+
+        let hnsw = indice(Metrica::L2, &aleatorios(10, 1));
+        assert!(hnsw.buscar(&[0.0; 3], 5).is_empty());
+        assert_eq!(hnsw.buscar(&[0.0; DIMENSIONES], 20).len(), 10);
+    }
+
+    #[test]
+    fn eliminar_omite_los_vectores() {
+        let vectores = aleatorios(1000, 9);
+        let mut hnsw = indice(Metrica::L2, &vectores);
+
+        for id in (0..vectores.len()).step_by(3) {
+            assert!(hnsw.eliminar(id as u64));
+        }
+        assert!(!hnsw.eliminar(0));
+        assert!(!hnsw.eliminar(5000));
+        assert_eq!(hnsw.len(), 666);
+        assert!(!hnsw.contiene(0));
+        assert!(hnsw.contiene(1));
+
+        // Los eliminados no aparecen, pero se siguen devolviendo `k` vecinos. Casi todos los que
+        // quedan se encuentran a sí mismos, aunque sus vecinos eliminados ya no se devuelven.
+        let mut propios = 0;
+        for (id, vector) in vectores.iter().enumerate() {
+            let encontrados = hnsw.buscar(vector, 10);
+            assert_eq!(encontrados.len(), 10);
+            assert!(encontrados.iter().all(|id| id % 3 != 0));
+            propios += usize::from(encontrados[0] == id as u64);
+        }
+        assert!(
+            propios >= 660,
+            "{propios} de 666 se encontraron a sí mismos"
+        );
+
+        let recall = recall(&hnsw, &vectores, &aleatorios(50, 10), 10);
+        assert!(recall >= 0.95, "recall de {recall}");
+
+        // Al volver a insertarlo, el vector deja de estar eliminado.
+        hnsw.insertar(0, &vectores[0]);
+        assert!(hnsw.contiene(0));
+        assert_eq!(hnsw.len(), 667);
+        assert_eq!(hnsw.buscar(&vectores[0], 1), [0]);
+    }
+
+    #[test]
+    fn eliminar_todos() {
+        let vectores = aleatorios(20, 11);
+        let mut hnsw = indice(Metrica::Coseno, &vectores);
+
+        for id in 0..vectores.len() {
+            hnsw.eliminar(id as u64);
+        }
+
+        assert!(hnsw.is_empty());
+        assert!(hnsw.buscar(&vectores[0], 5).is_empty());
+    }
+
+    #[test]
+    fn guardar_y_cargar() {
+        let vectores = aleatorios(300, 3);
+        let mut hnsw = indice(Metrica::Coseno, &vectores);
+        for id in [0, 7, 150] {
+            hnsw.eliminar(id);
+        }
+
+        let ruta = std::env::temp_dir().join(format!("hnsw-guardar-{}", std::process::id()));
+        hnsw.guardar(&ruta).unwrap();
+        let cargado = Hnsw::cargar(&ruta);
+        std::fs::remove_file(&ruta).unwrap();
+        let mut cargado = cargado.unwrap();
+
+        assert_eq!(cargado.dimensiones, hnsw.dimensiones);
+        assert_eq!(cargado.metrica, hnsw.metrica);
+        assert_eq!(cargado.m, hnsw.m);
+        assert_eq!(cargado.ef_construccion, hnsw.ef_construccion);
+        assert_eq!(cargado.ids, hnsw.ids);
+        assert_eq!(cargado.posiciones, hnsw.posiciones);
+        assert_eq!(cargado.vectores, hnsw.vectores);
+        assert_eq!(cargado.vecinos, hnsw.vecinos);
+        assert_eq!(cargado.eliminados, hnsw.eliminados);
+        assert_eq!(cargado.entrada, hnsw.entrada);
+
+        for consulta in aleatorios(20, 4) {
+            assert_eq!(cargado.buscar(&consulta, 10), hnsw.buscar(&consulta, 10));
+        }
+
+        // Se conserva la semilla, por lo que los nodos nuevos tienen los mismos niveles.
+        for (id, vector) in aleatorios(20, 5).iter().enumerate() {
+            hnsw.insertar(1000 + id as u64, vector);
+            cargado.insertar(1000 + id as u64, vector);
+        }
+        assert_eq!(cargado.vecinos, hnsw.vecinos);
+    }
+
+    #[test]
+    fn reinsertar_reemplaza_el_vector() {
+        let mut vectores = aleatorios(500, 6);
+        let mut hnsw = indice(Metrica::L2, &vectores);
+
+        for (i, vector) in aleatorios(100, 7).into_iter().enumerate() {
+            let id = i * 5;
+            hnsw.insertar(id as u64, &vector);
+            vectores[id] = vector;
+        }
+        assert_eq!(hnsw.len(), 500);
+
+        // Los nodos se encuentran por su vector nuevo y ninguno quedó inalcanzable.
+        for (id, vector) in vectores.iter().enumerate() {
+            assert_eq!(hnsw.buscar(vector, 1), [id as u64]);
+        }
+
+        let recall = recall(&hnsw, &vectores, &aleatorios(50, 8), 10);
+        assert!(recall >= 0.95, "recall de {recall}");
+    }
+
+    #[test]
+    fn cargar_rechaza_otros_archivos() {
+        let ruta = std::env::temp_dir().join(format!("hnsw-invalido-{}", std::process::id()));
+        std::fs::write(&ruta, b"SQLite format 3").unwrap();
+        let cargado = Hnsw::cargar(&ruta);
+        std::fs::remove_file(&ruta).unwrap();
+
+        assert!(cargado.is_err());
+    }
+}
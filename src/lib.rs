@@ -1,11 +1,28 @@
+pub mod campos;
 pub mod cli;
 pub mod configuration;
+pub mod corte;
+pub mod cuantizacion;
+pub mod descripcion;
+pub mod dimensiones;
+pub mod fts;
+pub mod fusion;
+pub mod fuzzy;
+pub mod hnsw;
+pub mod metrica;
+pub mod mmr;
 pub mod openai;
+pub mod orden;
+pub mod query;
 pub mod routes;
+pub mod sinonimos;
 pub mod sqlite;
 pub mod startup;
+pub mod stemmer;
 pub mod templates;
 pub mod utils;
 
 #[cfg(feature = "local")]
 pub mod embeddings;
+#[cfg(feature = "local")]
+pub mod reranker;
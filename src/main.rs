@@ -1,7 +1,8 @@
 use clap::Parser;
 use querysense::{
-    cli::{Cli, Commands, SyncStrategy},
-    configuration, openai, sqlite, startup,
+    campos,
+    cli::{Cli, Commands, DbCommands, FtsCommands, SyncStrategy},
+    configuration, cuantizacion, dimensiones, fts, hnsw, metrica, openai, sqlite, startup,
 };
 use tracing::{level_filters::LevelFilter, Level};
 use tracing_error::ErrorLayer;
@@ -48,8 +49,38 @@ fn main() -> eyre::Result<()> {
             interface,
             port,
             cache,
+            queue_interval,
+            bm25,
+            sinonimos,
+            #[cfg(feature = "local")]
+            reranker,
+            #[cfg(feature = "local")]
+            reranker_top_n,
+            hnsw_ef,
+            hnsw_min_registros,
         } => {
-            let configuration = configuration::ApplicationSettings::new(port, interface, cache);
+            let mut configuration = configuration::ApplicationSettings::new(
+                port,
+                interface,
+                cache,
+                std::time::Duration::from_secs(queue_interval),
+                template,
+                bm25,
+                sinonimos,
+            );
+            configuration.hnsw = hnsw::HnswSettings {
+                ef: hnsw_ef,
+                min_registros: hnsw_min_registros,
+            };
+
+            #[cfg(feature = "local")]
+            {
+                configuration.reranker =
+                    reranker.map(|path| querysense::reranker::RerankerSettings {
+                        path,
+                        top_n: reranker_top_n,
+                    });
+            }
 
             tracing::debug!("{:?}", &configuration);
             let rt = tokio::runtime::Runtime::new()?;
@@ -63,9 +94,27 @@ fn main() -> eyre::Result<()> {
             sync_strat,
             force: hard,
             model,
+            campos: por_campo,
+            cuantizacion,
+            dimensiones,
+            metrica,
+            hnsw: con_hnsw,
+            hnsw_m,
+            hnsw_ef_construccion,
         } => {
             let db = sqlite::init_sqlite()?;
 
+            let actuales = dimensiones::de_vec_tnea(&db)?;
+            let dimensiones = dimensiones.or(actuales).unwrap_or(model.dimensions());
+            dimensiones::validar(&model, dimensiones)?;
+
+            if let Some(actuales) = actuales.filter(|actuales| !hard && *actuales != dimensiones) {
+                return Err(eyre::eyre!(
+                    "`vec_tnea` tiene embeddings de {actuales} dimensiones, usa `--force` para \
+                    recrearla con {dimensiones}."
+                ));
+            }
+
             if hard {
                 let exists: String = db.query_row(
                     "select name from sqlite_master where type='table' and name=?",
@@ -77,14 +126,44 @@ fn main() -> eyre::Result<()> {
                     db.execute("drop table tnea", [])?;
                     db.execute("drop table tnea_raw", [])?;
                     db.execute("drop table vec_tnea", [])?;
+                    db.execute("drop table if exists embedding_queue", [])?;
+                    db.execute("drop table if exists fts_tnea", [])?;
+                    for campo in campos::CAMPOS {
+                        db.execute(&format!("drop table if exists vec_{campo}"), [])?;
+                    }
+                    db.execute("drop table if exists vec_cuantizado", [])?;
+                }
+            }
+
+            // Una métrica registrada que ya no existe, como `producto-interno`, impide crear las
+            // tablas, por lo que la pedida se registra antes. Las tablas se convierten después.
+            if let Some(metrica) = metrica {
+                if sqlite::table_exists(&db, "metadata")? {
+                    sqlite::set_metadata(&db, metrica::METADATA_METRICA, &metrica.to_string())?;
                 }
             }
 
             let start = std::time::Instant::now();
 
-            sqlite::setup_sqlite(&db, &model)?;
+            sqlite::setup_sqlite(&db, &model, dimensiones)?;
             sqlite::insert_base_data(&db, &template)?;
 
+            if por_campo {
+                sqlite::set_metadata(&db, campos::METADATA_CAMPOS, "true")?;
+            }
+
+            if let Some(cuantizacion) = cuantizacion {
+                cuantizacion::configurar(&db, cuantizacion, dimensiones)?;
+            }
+
+            if con_hnsw {
+                hnsw::configurar(&db, hnsw_m, hnsw_ef_construccion)?;
+            }
+
+            if let Some(metrica) = metrica {
+                metrica::configurar(&db, metrica)?;
+            }
+
             match sync_strat {
                 SyncStrategy::Fts => sqlite::sync_fts_tnea(&db),
                 SyncStrategy::Vector => {
@@ -103,11 +182,19 @@ fn main() -> eyre::Result<()> {
                 start.elapsed().as_millis()
             );
         }
-        Commands::Embed { input, model } => match model {
+        Commands::Embed {
+            input,
+            model,
+            dimensiones: dimensiones_embedding,
+        } => match model {
             querysense::cli::Model::OpenAI => {
+                let dimensiones_embedding = dimensiones_embedding.unwrap_or(model.dimensions());
+                dimensiones::validar(&model, dimensiones_embedding)?;
+
                 let client = reqwest::Client::new();
                 let rt = tokio::runtime::Runtime::new()?;
-                let output = rt.block_on(openai::embed_single(input, &client))?;
+                let output =
+                    rt.block_on(openai::embed_single(input, dimensiones_embedding, &client))?;
                 println!("{output:?}");
             }
 
@@ -116,6 +203,47 @@ fn main() -> eyre::Result<()> {
                 todo!()
             }
         },
+        Commands::Db { command } => {
+            let db = sqlite::init_sqlite()?;
+
+            match command {
+                DbCommands::Stats => {
+                    let stats = sqlite::db_stats(&db)?;
+                    println!("{stats}");
+                }
+                DbCommands::Check { repair, model } => {
+                    let check = sqlite::db_check(&db)?;
+                    println!("{check}");
+
+                    if check.is_ok() {
+                        tracing::info!("No se encontraron inconsistencias.");
+                    } else if repair {
+                        let rt = tokio::runtime::Runtime::new()?;
+                        rt.block_on(sqlite::db_repair(&db, &check, &template, model))?;
+
+                        let check = sqlite::db_check(&db)?;
+                        println!("Luego de la reparación:\n{check}");
+                    }
+                }
+            }
+        }
+        Commands::Fts { command } => {
+            let db = sqlite::init_sqlite()?;
+
+            match command {
+                FtsCommands::Rebuild => fts::rebuild(&db)?,
+                FtsCommands::IntegrityCheck => fts::integrity_check(&db)?,
+                FtsCommands::Merge { pages } => fts::merge(&db, pages)?,
+                FtsCommands::Automerge { segments } => fts::automerge(&db, segments)?,
+                FtsCommands::Tokenizer {
+                    tokenizer,
+                    remove_diacritics,
+                } => {
+                    let tokenizer = fts::TokenizerConfig::new(tokenizer, remove_diacritics);
+                    fts::set_tokenizer(&db, &tokenizer)?;
+                }
+            }
+        }
     }
 
     Ok(())
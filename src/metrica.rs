@@ -0,0 +1,198 @@
+use std::{fmt::Display, str::FromStr};
+
+use clap::ValueEnum;
+use rusqlite::Connection;
+
+use crate::{
+    campos,
+    cuantizacion::{self, Cuantizacion},
+    dimensiones, hnsw, sqlite,
+};
+
+/// Clave de `metadata` con la métrica de distancia de las tablas de vectores.
+pub const METADATA_METRICA: &str = "metrica_distancia";
+
+/// Métrica que se calculaba como la distancia coseno menos 1. Las bases de datos sincronizadas con
+/// ella tienen las tablas de vec0 creadas con `cosine`.
+const METRICA_ANTERIOR: &str = "producto-interno";
+
+/// Métrica con la que se mide la distancia entre embeddings. Se configura con
+/// `distance_metric=` al crear las tablas vec0, por lo que todas las estrategias la comparten.
+/// En todas, una distancia menor indica un registro más parecido a la búsqueda.
+///
+/// vec0 no implementa el producto interno, por lo que no se ofrece. Con embeddings normalizados,
+/// como los de OpenAI, la distancia coseno ordena los resultados igual.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Metrica {
+    /// Distancia euclídea, la que usa vec0 por defecto.
+    #[default]
+    L2,
+    /// `1 - similitud coseno`, entre 0 y 2.
+    Coseno,
+}
+
+impl Display for Metrica {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Metrica::L2 => write!(f, "l2"),
+            Metrica::Coseno => write!(f, "coseno"),
+        }
+    }
+}
+
+impl FromStr for Metrica {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "l2" => Ok(Self::L2),
+            "coseno" => Ok(Self::Coseno),
+            METRICA_ANTERIOR => Err(format!(
+                "La métrica `{METRICA_ANTERIOR}` ya no existe porque vec0 no implementa el \
+                producto interno. Las tablas usan la distancia coseno, vuelve a sincronizar con \
+                `sync --metrica coseno`."
+            )),
+            other => Err(format!("`{other}` no es una métrica de distancia válida.")),
+        }
+    }
+}
+
+impl Metrica {
+    /// Lee la métrica registrada en `metadata`. Las bases de datos sincronizadas antes de que
+    /// fuera configurable no la tienen, por lo que se usa la de `vec_tnea`.
+    pub fn from_db(db: &Connection) -> eyre::Result<Self> {
+        match sqlite::get_metadata(db, METADATA_METRICA)? {
+            Some(valor) => valor.parse().map_err(|err: String| eyre::eyre!(err)),
+            None => match de_vec_tnea(db)?.as_deref() {
+                Some("cosine") => Ok(Self::Coseno),
+                _ => Ok(Self::L2),
+            },
+        }
+    }
+
+    /// Valor de `distance_metric` en vec0.
+    fn vec0(self) -> &'static str {
+        match self {
+            Metrica::L2 => "l2",
+            Metrica::Coseno => "cosine",
+        }
+    }
+
+    /// Opción que se agrega a la definición de una columna `float[]` o `int8[]` de vec0.
+    #[must_use]
+    pub fn opcion(self) -> String {
+        format!("distance_metric={}", self.vec0())
+    }
+
+    /// Expresión SQL con la distancia entre dos vectores `float[]`, igual a la que devuelve una
+    /// consulta KNN sobre `vec_tnea`.
+    #[must_use]
+    pub fn distancia_entre(self, a: &str, b: &str) -> String {
+        match self {
+            Metrica::L2 => format!("vec_distance_l2({a}, {b})"),
+            Metrica::Coseno => format!("vec_distance_cosine({a}, {b})"),
+        }
+    }
+}
+
+/// Valor de `distance_metric` con el que se creó `vec_tnea`, leído de su definición en
+/// `sqlite_master`. Sin la opción, vec0 usa `l2`. Devuelve `None` si la tabla no existe.
+pub fn de_vec_tnea(db: &Connection) -> eyre::Result<Option<String>> {
+    if !sqlite::table_exists(db, "vec_tnea")? {
+        return Ok(None);
+    }
+
+    let sql: String = db.query_row(
+        "select sql from sqlite_master where name = 'vec_tnea'",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let metrica = sql
+        .split_once("distance_metric=")
+        .map(|(_, resto)| {
+            resto
+                .chars()
+                .take_while(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .unwrap_or_else(|| "l2".to_string());
+
+    Ok(Some(metrica))
+}
+
+/// Cambia la métrica de las tablas de vectores y la registra en `metadata`. vec0 no permite
+/// modificar una tabla, así que `vec_tnea` y las tablas por campo se recrean copiando sus
+/// vectores, sin volver a generar los embeddings, y `vec_cuantizado` y el índice HNSW se vuelven
+/// a construir.
+pub fn configurar(db: &Connection, metrica: Metrica) -> eyre::Result<()> {
+    sqlite::set_metadata(db, METADATA_METRICA, &metrica.to_string())?;
+
+    let (Some(actual), Some(dimensiones)) = (de_vec_tnea(db)?, dimensiones::de_vec_tnea(db)?)
+    else {
+        return Ok(());
+    };
+
+    if actual == metrica.vec0() {
+        tracing::info!("Se configuró la métrica de distancia `{metrica}`.");
+        return Ok(());
+    }
+
+    let start = std::time::Instant::now();
+    let tablas = std::iter::once(("vec_tnea".to_string(), "template_embedding"))
+        .chain(campos::CAMPOS.map(|campo| (format!("vec_{campo}"), "embedding")));
+
+    let tx = db.unchecked_transaction()?;
+    for (tabla, columna) in tablas {
+        if !sqlite::table_exists(&tx, &tabla)? {
+            continue;
+        }
+
+        tx.execute_batch(&format!(
+            "
+            create temp table copia as select row_id, {columna} as embedding from {tabla};
+            drop table {tabla};
+            create virtual table {tabla} using vec0(
+                row_id integer primary key,
+                {columna} float[{dimensiones}] {}
+            );
+            insert into {tabla}(row_id, {columna}) select row_id, embedding from copia;
+            drop table copia;
+            ",
+            metrica.opcion()
+        ))?;
+    }
+    tx.commit()?;
+
+    cuantizacion::configurar(db, Cuantizacion::from_db(db)?, dimensiones)?;
+    hnsw::sincronizar(db)?;
+
+    tracing::info!(
+        "Se cambió la métrica de distancia de `{actual}` a `{metrica}` en {} ms",
+        start.elapsed().as_millis()
+    );
+
+    Ok(())
+}
+
+/// Verifica al iniciar el servidor que la métrica registrada coincida con la de `vec_tnea`, y
+/// la devuelve.
+///
+/// # Errors
+///
+/// Devuelve un error si la métrica registrada no es la de la tabla.
+pub fn verificar(db: &Connection) -> eyre::Result<Metrica> {
+    let metrica = Metrica::from_db(db)?;
+
+    if let Some(actual) = de_vec_tnea(db)? {
+        if actual != metrica.vec0() {
+            return Err(eyre::eyre!(
+                "`{METADATA_METRICA}` indica `{metrica}` pero `vec_tnea` usa `{actual}`, vuelve a \
+                sincronizar con `sync --metrica {metrica}`."
+            ));
+        }
+    }
+
+    Ok(metrica)
+}
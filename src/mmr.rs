@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use rusqlite::Connection;
+
+use crate::sqlite;
+
+/// Cantidad de resultados, desde el primero, que se re-ordenan. El resto queda a continuación
+/// en su orden original.
+pub const MAX_CANDIDATOS: usize = 100;
+
+/// Re-ordena los primeros resultados con Maximal Marginal Relevance: en cada paso se elige el
+/// resultado que maximiza `lambda * relevancia - (1 - lambda) * similitud`, donde la similitud
+/// es la mayor similitud coseno con los resultados ya elegidos, según sus embeddings en
+/// `vec_tnea`.
+///
+/// Como cada estrategia tiene un puntaje distinto, la relevancia se toma de la posición en el
+/// orden original, de 1 para el primero a 0 para el último. Con `lambda = 1` se conserva el
+/// orden, con valores menores se priorizan los resultados distintos a los ya elegidos.
+///
+/// Devuelve la cantidad de resultados que se consideraron.
+pub fn diversificar<T>(
+    db: &Connection,
+    rows: &mut Vec<T>,
+    id: fn(&T) -> u64,
+    lambda: f32,
+) -> eyre::Result<usize> {
+    let n = rows.len().min(MAX_CANDIDATOS);
+    if n < 2 {
+        return Ok(n);
+    }
+
+    let ids: Vec<u64> = rows[..n].iter().map(id).collect();
+    let embeddings = sqlite::get_embeddings(db, &ids)?;
+    reordenar(rows, &ids, embeddings, lambda);
+
+    Ok(n)
+}
+
+/// Re-ordena con MMR los primeros `ids.len()` resultados, cuyos ids son `ids`, según sus
+/// `embeddings`. Los resultados sin embedding no se parecen a ningún otro.
+fn reordenar<T>(rows: &mut Vec<T>, ids: &[u64], embeddings: HashMap<u64, Vec<f32>>, lambda: f32) {
+    let n = ids.len();
+
+    // Los embeddings se normalizan una vez para que la similitud sea un producto interno.
+    let embeddings: HashMap<u64, Vec<f32>> = embeddings
+        .into_iter()
+        .map(|(id, embedding)| {
+            let norma = embedding
+                .iter()
+                .map(|x| x * x)
+                .sum::<f32>()
+                .sqrt()
+                .max(f32::EPSILON);
+            (id, embedding.into_iter().map(|x| x / norma).collect())
+        })
+        .collect();
+
+    let similitud = |a: u64, b: u64| match (embeddings.get(&a), embeddings.get(&b)) {
+        (Some(a), Some(b)) => a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+        _ => 0.0,
+    };
+
+    let relevancia = |posicion: usize| 1.0 - posicion as f32 / (n - 1) as f32;
+
+    let mut restantes: Vec<usize> = (0..n).collect();
+    let mut elegidos: Vec<usize> = Vec::with_capacity(n);
+    // La mayor similitud de cada candidato con los elegidos, se actualiza en cada paso.
+    let mut max_similitud = vec![0.0f32; n];
+
+    while !restantes.is_empty() {
+        let (indice, &mejor) = restantes
+            .iter()
+            .enumerate()
+            .max_by(|(_, &a), (_, &b)| {
+                let puntaje = |i: usize| lambda * relevancia(i) - (1.0 - lambda) * max_similitud[i];
+                // Ante un empate se prefiere el que estaba primero.
+                puntaje(a).total_cmp(&puntaje(b)).then(b.cmp(&a))
+            })
+            .expect("Quedan candidatos por elegir");
+
+        restantes.remove(indice);
+        elegidos.push(mejor);
+
+        for &candidato in &restantes {
+            max_similitud[candidato] =
+                max_similitud[candidato].max(similitud(ids[mejor], ids[candidato]));
+        }
+    }
+
+    let mut top: Vec<Option<T>> = rows.drain(..n).map(Some).collect();
+    let reordenados: Vec<T> = elegidos
+        .into_iter()
+        .filter_map(|posicion| top[posicion].take())
+        .collect();
+    rows.splice(0..0, reordenados);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embeddings(vectores: &[(u64, [f32; 2])]) -> HashMap<u64, Vec<f32>> {
+        vectores
+            .iter()
+            .map(|(id, vector)| (*id, vector.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn lambda_uno_conserva_el_orden() {
+        let mut rows = vec![1, 2, 3, 4];
+        let embeddings = embeddings(&[(1, [1.0, 0.0]), (2, [1.0, 0.0]), (3, [0.0, 1.0])]);
+
+        reordenar(&mut rows, &[1, 2, 3, 4], embeddings, 1.0);
+
+        assert_eq!(rows, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn aleja_los_resultados_parecidos() {
+        // El 2 es igual al 1, por lo que baja por debajo del 3 aunque sea más relevante.
+        let mut rows = vec![1, 2, 3, 4];
+        let embeddings = embeddings(&[
+            (1, [1.0, 0.0]),
+            (2, [2.0, 0.0]),
+            (3, [0.0, 1.0]),
+            (4, [1.0, 1.0]),
+        ]);
+
+        reordenar(&mut rows, &[1, 2, 3, 4], embeddings, 0.5);
+
+        assert_eq!(rows, [1, 3, 2, 4]);
+    }
+
+    #[test]
+    fn conserva_los_resultados_fuera_de_los_candidatos() {
+        let mut rows = vec![1, 2, 3, 9, 8];
+        let embeddings = embeddings(&[(1, [1.0, 0.0]), (2, [1.0, 0.0]), (3, [0.0, 1.0])]);
+
+        reordenar(&mut rows, &[1, 2, 3], embeddings, 0.5);
+
+        assert_eq!(rows, [1, 3, 2, 9, 8]);
+    }
+
+    #[test]
+    fn sin_embeddings_conserva_el_orden() {
+        let mut rows = vec![1, 2, 3];
+
+        reordenar(&mut rows, &[1, 2, 3], HashMap::new(), 0.3);
+
+        assert_eq!(rows, [1, 2, 3]);
+    }
+}
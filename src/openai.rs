@@ -41,6 +41,7 @@ pub struct RequestBody {
 pub async fn embed_vec(
     indices: Vec<u64>,
     input: Vec<String>,
+    dimensiones: usize,
     client: &reqwest::Client,
 ) -> eyre::Result<Vec<(u64, Vec<f32>)>> {
     let global_start = std::time::Instant::now();
@@ -49,7 +50,7 @@ pub async fn embed_vec(
         input,
         model: "text-embedding-3-small".to_string(),
         encoding_format: Some(EncodingFormat::Float),
-        dimensions: Some(1536),
+        dimensions: Some(dimensiones as u64),
     };
 
     let token = std::env::var("OPENAI_KEY").expect("`OPENAI_KEY debería estar definido en el .env");
@@ -94,7 +95,11 @@ pub async fn embed_vec(
     Ok(embedding)
 }
 #[instrument(name = "Generando embedding del query", skip(input, client))]
-pub async fn embed_single(input: String, client: &reqwest::Client) -> eyre::Result<Vec<f32>> {
+pub async fn embed_single(
+    input: String,
+    dimensiones: usize,
+    client: &reqwest::Client,
+) -> eyre::Result<Vec<f32>> {
     let global_start = std::time::Instant::now();
 
     #[derive(Serialize, Deserialize)]
@@ -109,7 +114,7 @@ pub async fn embed_single(input: String, client: &reqwest::Client) -> eyre::Resu
         input,
         model: "text-embedding-3-small".to_string(),
         encoding_format: Some(EncodingFormat::Float),
-        dimensions: Some(1536),
+        dimensions: Some(dimensiones as u64),
     };
 
     let token = std::env::var("OPENAI_KEY").expect("`OPENAI_KEY debería estar definido en el .env");
@@ -0,0 +1,223 @@
+use std::{cmp::Reverse, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+/// Orden en que se muestran los resultados. Se aplica al final de la búsqueda, después de
+/// cortar, re-ordenar y diversificar, por lo que cambia el orden de los resultados pero no
+/// cuáles son. Los resultados con el mismo valor conservan el orden por relevancia.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Orden {
+    /// El puntaje de cada estrategia.
+    #[default]
+    Relevancia,
+    EdadAsc,
+    EdadDesc,
+    Email,
+    /// Alfabético, con los registros sin provincia al final.
+    Provincia,
+}
+
+impl Display for Orden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Orden::Relevancia => write!(f, "relevancia"),
+            Orden::EdadAsc => write!(f, "edad ascendente"),
+            Orden::EdadDesc => write!(f, "edad descendente"),
+            Orden::Email => write!(f, "correo"),
+            Orden::Provincia => write!(f, "provincia"),
+        }
+    }
+}
+
+/// Un resultado que se puede ordenar por las columnas de [`Orden`].
+pub trait Ordenable {
+    fn edad(&self) -> u64;
+    fn email(&self) -> &str;
+    fn provincia(&self) -> &str;
+}
+
+/// Ordena los resultados, que deben estar ordenados por relevancia.
+pub fn ordenar<T: Ordenable>(rows: &mut [T], orden: Orden) {
+    match orden {
+        Orden::Relevancia => {}
+        Orden::EdadAsc => rows.sort_by_key(Ordenable::edad),
+        Orden::EdadDesc => rows.sort_by_key(|row| Reverse(row.edad())),
+        Orden::Email => rows.sort_by_cached_key(|row| row.email().to_lowercase()),
+        Orden::Provincia => rows.sort_by_cached_key(|row| {
+            let provincia = row.provincia().trim();
+            (provincia.is_empty(), provincia.to_lowercase())
+        }),
+    }
+}
+
+/// Enlaces de los encabezados de una tabla de resultados, que repiten la búsqueda con otro
+/// orden. Las columnas son `relevancia`, `edad`, `email` y `provincia`.
+#[derive(Debug, Clone)]
+pub struct Ordenamiento {
+    /// Enlace a `/search` con todos los parámetros de la búsqueda salvo el orden.
+    base: String,
+    actual: Orden,
+}
+
+impl Ordenamiento {
+    #[must_use]
+    pub fn new(base: String, actual: Orden) -> Self {
+        Self { base, actual }
+    }
+
+    /// Orden que aplica el encabezado de la columna. El de la edad alterna entre ascendente y
+    /// descendente.
+    fn orden(&self, columna: &str) -> Orden {
+        match columna {
+            "edad" if self.actual == Orden::EdadAsc => Orden::EdadDesc,
+            "edad" => Orden::EdadAsc,
+            "email" => Orden::Email,
+            "provincia" => Orden::Provincia,
+            _ => Orden::Relevancia,
+        }
+    }
+
+    #[must_use]
+    pub fn href(&self, columna: &str) -> String {
+        let orden =
+            serde_urlencoded::to_string([("sort", self.orden(columna))]).unwrap_or_default();
+        format!("{}&{orden}", self.base)
+    }
+
+    /// Flecha que indica si la tabla está ordenada por la columna.
+    #[must_use]
+    pub fn indicador(&self, columna: &str) -> &'static str {
+        match (columna, self.actual) {
+            ("relevancia", Orden::Relevancia) | ("edad", Orden::EdadDesc) => " ▼",
+            ("edad", Orden::EdadAsc)
+            | ("email", Orden::Email)
+            | ("provincia", Orden::Provincia) => " ▲",
+            _ => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Fila(u64, &'static str, &'static str);
+
+    impl Ordenable for Fila {
+        fn edad(&self) -> u64 {
+            self.0
+        }
+
+        fn email(&self) -> &str {
+            self.1
+        }
+
+        fn provincia(&self) -> &str {
+            self.2
+        }
+    }
+
+    fn filas() -> Vec<Fila> {
+        vec![
+            Fila(30, "carla@example.com", "Salta"),
+            Fila(25, "Ana@example.com", ""),
+            Fila(40, "bruno@example.com", "cordoba"),
+            Fila(25, "diego@example.com", "Buenos Aires"),
+        ]
+    }
+
+    fn emails(rows: &[Fila]) -> Vec<&str> {
+        rows.iter().map(|row| row.1).collect()
+    }
+
+    #[test]
+    fn ordena_por_columna() {
+        let mut rows = filas();
+        ordenar(&mut rows, Orden::Relevancia);
+        assert_eq!(rows, filas());
+
+        // Los resultados con la misma edad conservan el orden por relevancia.
+        ordenar(&mut rows, Orden::EdadAsc);
+        assert_eq!(
+            emails(&rows),
+            [
+                "Ana@example.com",
+                "diego@example.com",
+                "carla@example.com",
+                "bruno@example.com"
+            ]
+        );
+
+        let mut rows = filas();
+        ordenar(&mut rows, Orden::EdadDesc);
+        assert_eq!(
+            emails(&rows),
+            [
+                "bruno@example.com",
+                "carla@example.com",
+                "Ana@example.com",
+                "diego@example.com"
+            ]
+        );
+
+        let mut rows = filas();
+        ordenar(&mut rows, Orden::Email);
+        assert_eq!(
+            emails(&rows),
+            [
+                "Ana@example.com",
+                "bruno@example.com",
+                "carla@example.com",
+                "diego@example.com"
+            ]
+        );
+
+        let mut rows = filas();
+        ordenar(&mut rows, Orden::Provincia);
+        assert_eq!(
+            rows.iter().map(|row| row.2).collect::<Vec<_>>(),
+            ["Buenos Aires", "cordoba", "Salta", ""]
+        );
+    }
+
+    #[test]
+    fn encabezado_de_edad_alterna() {
+        let href = |actual: Orden, columna: &str| {
+            Ordenamiento::new("/search?query=contador".to_string(), actual).href(columna)
+        };
+
+        assert_eq!(
+            href(Orden::Relevancia, "edad"),
+            "/search?query=contador&sort=EdadAsc"
+        );
+        assert_eq!(
+            href(Orden::EdadAsc, "edad"),
+            "/search?query=contador&sort=EdadDesc"
+        );
+        assert_eq!(
+            href(Orden::EdadDesc, "edad"),
+            "/search?query=contador&sort=EdadAsc"
+        );
+        assert_eq!(
+            href(Orden::EdadAsc, "email"),
+            "/search?query=contador&sort=Email"
+        );
+        assert_eq!(
+            href(Orden::Email, "relevancia"),
+            "/search?query=contador&sort=Relevancia"
+        );
+    }
+
+    #[test]
+    fn indicador() {
+        let ordenamiento = Ordenamiento::new(String::new(), Orden::EdadDesc);
+
+        assert_eq!(ordenamiento.indicador("edad"), " ▼");
+        assert_eq!(ordenamiento.indicador("relevancia"), "");
+        assert_eq!(
+            Ordenamiento::new(String::new(), Orden::EdadAsc).indicador("edad"),
+            " ▲"
+        );
+    }
+}
@@ -0,0 +1,740 @@
+use std::fmt::Display;
+
+/// Búsqueda del usuario ya validada.
+///
+/// La sintaxis soportada es:
+/// - Palabras sueltas, que deben aparecer todas en el registro.
+/// - `"frases entre comillas"`, que deben aparecer en ese orden.
+/// - `-palabra` o `-"una frase"`, que excluye los registros que la contengan.
+/// - `a OR b`, que acepta registros con cualquiera de los dos términos.
+/// - `prefijo*`, que busca palabras que comiencen con el prefijo.
+/// - `campo:valor` o `campo:"una frase"`, que restringe la búsqueda a un campo del perfil. Ver
+///   [`Field`] para los campos disponibles.
+///
+/// Cualquier otro caracter se escapa, por lo que `C++` o `AND` se buscan de forma literal.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchQuery {
+    /// Expresión válida para usar con `match` en FTS5.
+    pub fts: String,
+    /// Los términos positivos de la búsqueda en texto plano, para generar el embedding.
+    pub text: String,
+    /// Expresión FTS5 con los términos restringidos a una columna, para aplicarlos como filtro
+    /// en las estrategias que no utilizan `fts`.
+    pub columnas: Option<String>,
+    /// Expresión FTS5 con los términos excluidos.
+    pub exclusiones: Option<String>,
+    /// Expresión FTS5 de los términos positivos sin sus sinónimos, si se agregó alguno, para
+    /// reconocer los registros que coinciden con la búsqueda original.
+    pub sin_sinonimos: Option<String>,
+    pub provincias: Vec<String>,
+    pub ciudades: Vec<String>,
+    pub sexos: Vec<String>,
+    /// Términos positivos, agrupados por `OR`.
+    groups: Vec<Vec<Term>>,
+    exclusions: Vec<Term>,
+}
+
+impl SearchQuery {
+    /// Las palabras sueltas de la búsqueda, sin frases ni prefijos, junto con la expresión FTS5
+    /// que las busca. Son las candidatas a recibir variantes con [`SearchQuery::add_variants`].
+    #[must_use]
+    pub fn words(&self) -> Vec<(String, String)> {
+        let mut words: Vec<(String, String)> = Vec::new();
+
+        for term in self.groups.iter().flatten() {
+            let is_word = !term.prefix
+                && !term.text.contains(char::is_whitespace)
+                && term.text.chars().any(char::is_alphabetic);
+
+            if is_word && !words.iter().any(|(text, _)| *text == term.text) {
+                words.push((term.text.clone(), term.to_fts()));
+            }
+        }
+
+        words
+    }
+
+    /// Agrega `variants` como alternativas de la palabra `word`, de forma que los registros que
+    /// contengan cualquiera de ellas también coincidan.
+    pub fn add_variants(&mut self, word: &str, variants: &[String]) {
+        for term in self.groups.iter_mut().flatten() {
+            if term.text == word && !term.prefix {
+                term.variants.extend(variants.iter().cloned());
+            }
+        }
+
+        self.build();
+    }
+
+    /// Los términos positivos de la búsqueda, palabras o frases, que no son prefijos. Son los
+    /// candidatos a recibir sinónimos con [`SearchQuery::add_synonyms`].
+    #[must_use]
+    pub fn terms(&self) -> Vec<String> {
+        let mut terms: Vec<String> = Vec::new();
+
+        for term in self.groups.iter().flatten() {
+            if !term.prefix && !terms.contains(&term.text) {
+                terms.push(term.text.clone());
+            }
+        }
+
+        terms
+    }
+
+    /// Agrega `synonyms` como alternativas del término `text`. A diferencia de las variantes,
+    /// quedan fuera de [`SearchQuery::sin_sinonimos`].
+    pub fn add_synonyms(&mut self, text: &str, synonyms: &[String]) {
+        for term in self.groups.iter_mut().flatten() {
+            if term.text == text && !term.prefix {
+                term.synonyms.extend(synonyms.iter().cloned());
+            }
+        }
+
+        self.build();
+    }
+
+    /// Genera las expresiones FTS5 y el texto a partir de los términos.
+    fn build(&mut self) {
+        let column_groups: Vec<&Vec<Term>> = self
+            .groups
+            .iter()
+            .filter(|group| group.iter().all(Term::has_column))
+            .collect();
+
+        self.columnas = (!column_groups.is_empty()).then(|| and_groups(column_groups, true));
+
+        self.exclusiones = (!self.exclusions.is_empty()).then(|| {
+            self.exclusions
+                .iter()
+                .map(Term::to_fts)
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        });
+
+        let positives = and_groups(self.groups.iter().collect(), true);
+
+        self.sin_sinonimos = self
+            .groups
+            .iter()
+            .flatten()
+            .any(|term| !term.synonyms.is_empty())
+            .then(|| and_groups(self.groups.iter().collect(), false));
+        self.fts = match &self.exclusiones {
+            Some(exclusiones) => format!("({positives}) NOT ({exclusiones})"),
+            None => positives,
+        };
+
+        self.text = self
+            .groups
+            .iter()
+            .flatten()
+            .map(|term| term.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+}
+
+/// Campos del perfil que se pueden usar con la sintaxis `campo:valor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    /// Filtro exacto, sin distinguir mayúsculas.
+    Provincia,
+    /// Filtro exacto, sin distinguir mayúsculas.
+    Ciudad,
+    /// Filtro exacto, acepta `M`, `F`, `masculino` o `femenino`.
+    Sexo,
+    /// Búsqueda FTS sobre la columna `descripcion` de `fts_tnea`.
+    Descripcion,
+    /// Búsqueda FTS sobre la columna `estudios` de `fts_tnea`.
+    Estudios,
+    /// Búsqueda FTS sobre la columna `experiencia` de `fts_tnea`.
+    Experiencia,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "provincia" => Some(Self::Provincia),
+            "ciudad" => Some(Self::Ciudad),
+            "sexo" => Some(Self::Sexo),
+            "descripcion" => Some(Self::Descripcion),
+            "estudios" => Some(Self::Estudios),
+            "experiencia" => Some(Self::Experiencia),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Provincia => "provincia",
+            Self::Ciudad => "ciudad",
+            Self::Sexo => "sexo",
+            Self::Descripcion => "descripcion",
+            Self::Estudios => "estudios",
+            Self::Experiencia => "experiencia",
+        }
+    }
+
+    /// Los campos que se filtran por igualdad en lugar de buscarse en `fts_tnea`.
+    #[must_use]
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Self::Provincia | Self::Ciudad | Self::Sexo)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    Empty,
+    UnbalancedQuote,
+    EmptyPhrase,
+    DanglingExclusion,
+    DanglingPrefix,
+    DanglingOr,
+    ExclusionInOr,
+    OnlyExclusions,
+    MissingValue(Field),
+    InvalidFilter(Field),
+    InvalidSexo(String),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Empty => write!(
+                f,
+                "La búsqueda debe contener al menos una palabra, además de los filtros."
+            ),
+            QueryError::UnbalancedQuote => write!(f, "Falta cerrar las comillas de una frase."),
+            QueryError::EmptyPhrase => write!(f, "Hay una frase entre comillas sin palabras."),
+            QueryError::DanglingExclusion => write!(
+                f,
+                "El `-` debe ir pegado a la palabra o frase que se quiere excluir."
+            ),
+            QueryError::DanglingPrefix => {
+                write!(f, "El `*` debe ir pegado al final de una palabra.")
+            }
+            QueryError::DanglingOr => {
+                write!(f, "El `OR` debe estar entre dos palabras o frases.")
+            }
+            QueryError::ExclusionInOr => {
+                write!(f, "No se puede excluir un término dentro de un `OR`.")
+            }
+            QueryError::OnlyExclusions => write!(
+                f,
+                "La búsqueda debe tener al menos un término que no sea una exclusión."
+            ),
+            QueryError::MissingValue(field) => write!(
+                f,
+                "Falta el valor de `{0}:`, por ejemplo `{0}:\"un valor\"`.",
+                field.name()
+            ),
+            QueryError::InvalidFilter(field) => write!(
+                f,
+                "El filtro `{}:` no admite `-`, `*` ni `OR`.",
+                field.name()
+            ),
+            QueryError::InvalidSexo(value) => write!(
+                f,
+                "`{value}` no es un valor válido para `sexo:`, usa `M` o `F`."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Term(Term),
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    text: String,
+    prefix: bool,
+    negated: bool,
+    field: Option<Field>,
+    /// Palabras alternativas que también se aceptan, por ejemplo correcciones ortográficas.
+    variants: Vec<String>,
+    /// Sinónimos del término según el diccionario de [`crate::sinonimos::Sinonimos`].
+    synonyms: Vec<String>,
+}
+
+impl Term {
+    /// Los términos sin campo se buscan en `template` y en las columnas de texto, para que los
+    /// pesos de `bm25` de cada columna tengan efecto.
+    fn to_fts(&self) -> String {
+        self.fts(true)
+    }
+
+    fn fts(&self, with_synonyms: bool) -> String {
+        let column = self
+            .field
+            .map_or("{template descripcion estudios experiencia}", |field| {
+                field.name()
+            });
+        let phrase = |text: &str| format!("{column} : \"{}\"", text.replace('"', "\"\""));
+
+        if self.prefix {
+            return format!("{} *", phrase(&self.text));
+        }
+
+        let synonyms: &[String] = if with_synonyms { &self.synonyms } else { &[] };
+
+        if self.variants.is_empty() && synonyms.is_empty() {
+            return phrase(&self.text);
+        }
+
+        let alternatives: Vec<String> = std::iter::once(&self.text)
+            .chain(&self.variants)
+            .chain(synonyms)
+            .map(|text| phrase(text))
+            .collect();
+
+        format!("({})", alternatives.join(" OR "))
+    }
+
+    fn has_column(&self) -> bool {
+        self.field.is_some()
+    }
+}
+
+/// El último token procesado, para validar a qué se le aplica un `OR`.
+enum Previous {
+    Nothing,
+    Positive,
+    Exclusion,
+    Filter(Field),
+}
+
+/// Convierte la búsqueda del usuario en una expresión FTS5 segura.
+///
+/// # Errors
+///
+/// Devuelve un error si la sintaxis avanzada está mal formada, por ejemplo comillas sin cerrar o
+/// un `OR` sin términos a los dos lados.
+pub fn parse(input: &str) -> Result<SearchQuery, QueryError> {
+    parse_with(input, true)
+}
+
+/// Igual que [`parse`], pero acepta búsquedas que solamente tienen filtros o exclusiones, para
+/// cuando los términos no se usan.
+///
+/// # Errors
+///
+/// Devuelve un error si la sintaxis avanzada está mal formada.
+pub fn parse_filters(input: &str) -> Result<SearchQuery, QueryError> {
+    parse_with(input, false)
+}
+
+fn parse_with(input: &str, require_terms: bool) -> Result<SearchQuery, QueryError> {
+    let tokens = tokenize(input)?;
+
+    let mut query = SearchQuery::default();
+    let mut groups: Vec<Vec<Term>> = Vec::new();
+    let mut exclusions: Vec<Term> = Vec::new();
+    let mut pending_or = false;
+    let mut previous = Previous::Nothing;
+
+    for token in tokens {
+        match token {
+            Token::Or => {
+                match previous {
+                    _ if pending_or => return Err(QueryError::DanglingOr),
+                    Previous::Nothing => return Err(QueryError::DanglingOr),
+                    Previous::Exclusion => return Err(QueryError::ExclusionInOr),
+                    Previous::Filter(field) => return Err(QueryError::InvalidFilter(field)),
+                    Previous::Positive => (),
+                }
+                pending_or = true;
+            }
+            Token::Term(Term {
+                text,
+                prefix,
+                negated,
+                field: Some(field),
+                ..
+            }) if field.is_exact() => {
+                if prefix || negated || pending_or {
+                    return Err(QueryError::InvalidFilter(field));
+                }
+                match field {
+                    Field::Provincia => query.provincias.push(text),
+                    Field::Ciudad => query.ciudades.push(text),
+                    _ => query.sexos.push(parse_sexo(&text)?),
+                }
+                previous = Previous::Filter(field);
+            }
+            Token::Term(term) if term.negated => {
+                if pending_or {
+                    return Err(QueryError::ExclusionInOr);
+                }
+                exclusions.push(term);
+                previous = Previous::Exclusion;
+            }
+            Token::Term(term) => {
+                match groups.last_mut() {
+                    Some(group) if pending_or => group.push(term),
+                    _ => groups.push(vec![term]),
+                }
+                pending_or = false;
+                previous = Previous::Positive;
+            }
+        }
+    }
+
+    if pending_or {
+        return Err(QueryError::DanglingOr);
+    }
+
+    if require_terms && groups.is_empty() {
+        return Err(if exclusions.is_empty() {
+            QueryError::Empty
+        } else {
+            QueryError::OnlyExclusions
+        });
+    }
+
+    query.groups = groups;
+    query.exclusions = exclusions;
+    query.build();
+
+    Ok(query)
+}
+
+fn and_groups(groups: Vec<&Vec<Term>>, with_synonyms: bool) -> String {
+    groups
+        .into_iter()
+        .map(|group| match group.as_slice() {
+            [term] => term.fts(with_synonyms),
+            terms => format!(
+                "({})",
+                terms
+                    .iter()
+                    .map(|term| term.fts(with_synonyms))
+                    .collect::<Vec<_>>()
+                    .join(" OR ")
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn parse_sexo(value: &str) -> Result<String, QueryError> {
+    match value.to_lowercase().as_str() {
+        "m" | "masculino" => Ok("M".to_string()),
+        "f" | "femenino" => Ok("F".to_string()),
+        _ => Err(QueryError::InvalidSexo(value.to_string())),
+    }
+}
+
+/// Si `input` comienza con `campo:`, devuelve el campo y el largo del prefijo.
+fn field_prefix(input: &str) -> Option<(Field, usize)> {
+    let (name, _) = input.split_once(':')?;
+
+    if name.contains(|c: char| c.is_whitespace() || c == '"') {
+        return None;
+    }
+
+    Field::from_name(name).map(|field| (field, name.len() + 1))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+
+    while !rest.is_empty() {
+        let negated = rest.starts_with('-');
+        if negated {
+            rest = &rest[1..];
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                return Err(QueryError::DanglingExclusion);
+            }
+        }
+
+        let field = field_prefix(rest).map(|(field, len)| {
+            rest = &rest[len..];
+            field
+        });
+
+        if let Some(field) = field {
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                return Err(QueryError::MissingValue(field));
+            }
+        }
+
+        let (text, prefix) = if let Some(phrase) = rest.strip_prefix('"') {
+            let end = phrase.find('"').ok_or(QueryError::UnbalancedQuote)?;
+            let text = &phrase[..end];
+            rest = &phrase[end + 1..];
+
+            if !text.chars().any(char::is_alphanumeric) {
+                return Err(QueryError::EmptyPhrase);
+            }
+
+            let prefix = rest.starts_with('*');
+            if prefix {
+                rest = &rest[1..];
+            }
+
+            (text.trim(), prefix)
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+
+            if word == "OR" && !negated && field.is_none() {
+                tokens.push(Token::Or);
+                rest = rest.trim_start();
+                continue;
+            }
+
+            let text = word.trim_end_matches('*');
+
+            if text.is_empty() {
+                return Err(QueryError::DanglingPrefix);
+            }
+
+            // Las palabras sin letras ni números no generan ningún token en FTS5.
+            if !text.chars().any(char::is_alphanumeric) {
+                if let Some(field) = field {
+                    return Err(QueryError::MissingValue(field));
+                }
+                rest = rest.trim_start();
+                continue;
+            }
+
+            (text, word.ends_with('*'))
+        };
+
+        tokens.push(Token::Term(Term {
+            text: text.to_string(),
+            prefix,
+            negated,
+            field,
+            variants: Vec::new(),
+            synonyms: Vec::new(),
+        }));
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Las columnas en las que se buscan los términos sin campo.
+    const TODAS: &str = "{template descripcion estudios experiencia}";
+
+    fn term(text: &str) -> Term {
+        Term {
+            text: text.to_string(),
+            prefix: false,
+            negated: false,
+            field: None,
+            variants: Vec::new(),
+            synonyms: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn palabras_y_frases() {
+        let query = parse(r#"contador "comercio exterior""#).unwrap();
+
+        assert_eq!(
+            query.fts,
+            format!(r#"{TODAS} : "contador" AND {TODAS} : "comercio exterior""#)
+        );
+        assert_eq!(query.text, "contador comercio exterior");
+        assert_eq!(query.exclusiones, None);
+        assert_eq!(query.columnas, None);
+        assert_eq!(query.terms(), ["contador", "comercio exterior"]);
+        assert_eq!(
+            query.words(),
+            [("contador".to_string(), format!(r#"{TODAS} : "contador""#))]
+        );
+    }
+
+    #[test]
+    fn exclusiones() {
+        let query = parse(r#"contador -junior -"medio tiempo""#).unwrap();
+        let exclusiones = format!(r#"{TODAS} : "junior" OR {TODAS} : "medio tiempo""#);
+
+        assert_eq!(
+            query.fts,
+            format!(r#"({TODAS} : "contador") NOT ({exclusiones})"#)
+        );
+        assert_eq!(query.exclusiones, Some(exclusiones));
+        assert_eq!(query.text, "contador");
+    }
+
+    #[test]
+    fn or() {
+        let query = parse("contador OR contable auditor").unwrap();
+
+        assert_eq!(
+            query.fts,
+            format!(r#"({TODAS} : "contador" OR {TODAS} : "contable") AND {TODAS} : "auditor""#)
+        );
+        assert_eq!(query.text, "contador contable auditor");
+
+        // En minúsculas es una palabra más.
+        let query = parse("contador or auditor").unwrap();
+        assert_eq!(query.text, "contador or auditor");
+    }
+
+    #[test]
+    fn campos() {
+        let query = parse(
+            r#"Estudios:"ingenieria civil" provincia:Cordoba ciudad:"Villa Maria" sexo:femenino python"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.fts,
+            format!(r#"estudios : "ingenieria civil" AND {TODAS} : "python""#)
+        );
+        assert_eq!(
+            query.columnas.as_deref(),
+            Some(r#"estudios : "ingenieria civil""#)
+        );
+        assert_eq!(query.provincias, ["Cordoba"]);
+        assert_eq!(query.ciudades, ["Villa Maria"]);
+        assert_eq!(query.sexos, ["F"]);
+        assert_eq!(query.text, "ingenieria civil python");
+
+        // Un `OR` solamente cuenta como columna si todos sus términos tienen campo.
+        let query = parse("experiencia:ventas OR comercial").unwrap();
+        assert_eq!(query.columnas, None);
+
+        // Los nombres desconocidos no son campos.
+        let query = parse("url:https://example.com").unwrap();
+        assert_eq!(query.fts, format!(r#"{TODAS} : "url:https://example.com""#));
+    }
+
+    #[test]
+    fn prefijos() {
+        let query = parse(r#"progra* "desarrollador web"*"#).unwrap();
+
+        assert_eq!(
+            query.fts,
+            format!(r#"{TODAS} : "progra" * AND {TODAS} : "desarrollador web" *"#)
+        );
+        assert!(query.words().is_empty());
+
+        let query = parse("descripcion:admin*").unwrap();
+        assert_eq!(query.fts, r#"descripcion : "admin" *"#);
+    }
+
+    #[test]
+    fn escapa_la_sintaxis_de_fts() {
+        let query = parse("C++ AND NOT (java) NEAR").unwrap();
+
+        assert_eq!(
+            query.fts,
+            [
+                r#""C++""#,
+                r#""AND""#,
+                r#""NOT""#,
+                r#""(java)""#,
+                r#""NEAR""#
+            ]
+            .map(|text| format!("{TODAS} : {text}"))
+            .join(" AND ")
+        );
+
+        let mut comillas = term(r#"12" pulgadas"#);
+        comillas.field = Some(Field::Descripcion);
+        assert_eq!(comillas.to_fts(), r#"descripcion : "12"" pulgadas""#);
+
+        // Las palabras sin letras ni números se ignoran.
+        let query = parse("contador & / auditor").unwrap();
+        assert_eq!(query.text, "contador auditor");
+    }
+
+    #[test]
+    fn variantes_y_sinonimos() {
+        let mut query = parse("contadro").unwrap();
+        query.add_variants("contadro", &["contador".to_string()]);
+        query.add_synonyms("contadro", &["contable".to_string()]);
+
+        assert_eq!(
+            query.fts,
+            format!(r#"({TODAS} : "contadro" OR {TODAS} : "contador" OR {TODAS} : "contable")"#)
+        );
+        assert_eq!(
+            query.sin_sinonimos,
+            Some(format!(r#"({TODAS} : "contadro" OR {TODAS} : "contador")"#))
+        );
+        assert_eq!(query.text, "contadro");
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize(r#"ciudad:"villa maria" OR  -c* "#).unwrap();
+
+        let mut ciudad = term("villa maria");
+        ciudad.field = Some(Field::Ciudad);
+        let mut prefijo = term("c");
+        prefijo.prefix = true;
+        prefijo.negated = true;
+
+        assert_eq!(
+            tokens,
+            [Token::Term(ciudad), Token::Or, Token::Term(prefijo)]
+        );
+    }
+
+    #[test]
+    fn errores() {
+        let casos = [
+            ("", QueryError::Empty),
+            ("   ", QueryError::Empty),
+            ("provincia:Cordoba", QueryError::Empty),
+            (r#""contador publico"#, QueryError::UnbalancedQuote),
+            (r#"contador " ""#, QueryError::EmptyPhrase),
+            ("contador - junior", QueryError::DanglingExclusion),
+            ("contador -", QueryError::DanglingExclusion),
+            ("contador *", QueryError::DanglingPrefix),
+            ("OR contador", QueryError::DanglingOr),
+            ("contador OR", QueryError::DanglingOr),
+            ("contador OR OR auditor", QueryError::DanglingOr),
+            ("contador OR -junior", QueryError::ExclusionInOr),
+            ("-junior OR contador", QueryError::ExclusionInOr),
+            ("-junior", QueryError::OnlyExclusions),
+            ("ciudad: rosario", QueryError::MissingValue(Field::Ciudad)),
+            ("estudios:", QueryError::MissingValue(Field::Estudios)),
+            ("provincia:&", QueryError::MissingValue(Field::Provincia)),
+            (
+                "provincia:Cor*",
+                QueryError::InvalidFilter(Field::Provincia),
+            ),
+            ("-ciudad:Rosario", QueryError::InvalidFilter(Field::Ciudad)),
+            ("sexo:M OR contador", QueryError::InvalidFilter(Field::Sexo)),
+            ("sexo:x", QueryError::InvalidSexo("x".to_string())),
+        ];
+
+        for (input, error) in casos {
+            assert_eq!(parse(input), Err(error), "`{input}`");
+        }
+    }
+
+    #[test]
+    fn solamente_filtros() {
+        let query = parse_filters("provincia:Cordoba -junior").unwrap();
+
+        assert_eq!(query.provincias, ["Cordoba"]);
+        assert_eq!(query.exclusiones, Some(format!(r#"{TODAS} : "junior""#)));
+        assert_eq!(
+            parse_filters("sexo:x"),
+            Err(QueryError::InvalidSexo("x".into()))
+        );
+    }
+}
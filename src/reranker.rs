@@ -0,0 +1,151 @@
+// Implementado en base a los ejemplos en:
+// https://github.com/huggingface/candle/tree/main/candle-examples/examples/bert
+use std::path::{Path, PathBuf};
+
+use candle_core::{DType, Device, IndexOp, Tensor};
+use candle_nn::{Linear, Module, VarBuilder};
+use candle_transformers::models::bert::{BertModel, Config};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+/// Cantidad de pares que se procesan juntos, para acotar la memoria usada.
+const BATCH_SIZE: usize = 16;
+/// Largo máximo en tokens de cada par (búsqueda, template).
+const MAX_TOKENS: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct RerankerSettings {
+    /// Directorio con `config.json`, `tokenizer.json` y `model.safetensors` del cross-encoder, por
+    /// ejemplo una copia de `cross-encoder/ms-marco-MiniLM-L-6-v2`.
+    pub path: PathBuf,
+    /// Cantidad de resultados que se re-ordenan si la búsqueda no indica otra.
+    pub top_n: usize,
+}
+
+/// Cross-encoder con la arquitectura de `BertForSequenceClassification`, que puntúa cada par
+/// (búsqueda, template) leyendo ambos textos juntos. Es más preciso que comparar embeddings,
+/// pero mucho más lento, por lo que solamente se usa sobre los primeros resultados.
+pub struct CrossEncoder {
+    model: BertModel,
+    pooler: Linear,
+    classifier: Linear,
+    tokenizer: Tokenizer,
+    device: Device,
+    pub top_n: usize,
+}
+
+impl std::fmt::Debug for CrossEncoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CrossEncoder")
+            .field("top_n", &self.top_n)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CrossEncoder {
+    /// Carga el modelo desde el directorio de `settings`. Siempre se ejecuta en la CPU.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si falta alguno de los archivos del modelo o no tiene la arquitectura
+    /// esperada.
+    pub fn load(settings: &RerankerSettings) -> eyre::Result<Self> {
+        let start = std::time::Instant::now();
+        let path: &Path = &settings.path;
+        tracing::info!("Cargando el cross-encoder de `{}`...", path.display());
+
+        let device = Device::Cpu;
+
+        let config = std::fs::read_to_string(path.join("config.json"))?;
+        let hidden_size = serde_json::from_str::<serde_json::Value>(&config)?["hidden_size"]
+            .as_u64()
+            .ok_or_else(|| eyre::eyre!("`config.json` no tiene `hidden_size`"))?
+            as usize;
+        let config: Config = serde_json::from_str(&config)?;
+
+        let mut tokenizer =
+            Tokenizer::from_file(path.join("tokenizer.json")).map_err(|err| eyre::eyre!(err))?;
+        tokenizer
+            .with_padding(Some(PaddingParams::default()))
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(|err| eyre::eyre!(err))?;
+
+        // SAFETY: el archivo no se modifica mientras el servidor está en ejecución.
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(
+                &[path.join("model.safetensors")],
+                DType::F32,
+                &device,
+            )?
+        };
+
+        let model = BertModel::load(vb.pp("bert"), &config)?;
+        let pooler = candle_nn::linear(hidden_size, hidden_size, vb.pp("bert.pooler.dense"))?;
+        let classifier = candle_nn::linear(hidden_size, 1, vb.pp("classifier"))?;
+
+        tracing::info!(
+            "Cargando el cross-encoder de `{}`... listo! tomó {} ms",
+            path.display(),
+            start.elapsed().as_millis()
+        );
+
+        Ok(Self {
+            model,
+            pooler,
+            classifier,
+            tokenizer,
+            device,
+            top_n: settings.top_n,
+        })
+    }
+
+    /// Devuelve el puntaje de relevancia de cada template para la búsqueda, en el mismo orden.
+    /// Un puntaje mayor es mejor.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si falla la tokenización o la inferencia.
+    pub fn score(&self, query: &str, templates: &[String]) -> eyre::Result<Vec<f32>> {
+        let mut scores = Vec::with_capacity(templates.len());
+
+        for chunk in templates.chunks(BATCH_SIZE) {
+            let pairs: Vec<(String, String)> = chunk
+                .iter()
+                .map(|template| (query.to_string(), template.clone()))
+                .collect();
+
+            let encodings = self
+                .tokenizer
+                .encode_batch(pairs, true)
+                .map_err(|err| eyre::eyre!(err))?;
+
+            let tensor = |values: Vec<&[u32]>| -> eyre::Result<Tensor> {
+                let rows = values
+                    .into_iter()
+                    .map(|row| Tensor::new(row, &self.device))
+                    .collect::<candle_core::Result<Vec<_>>>()?;
+                Ok(Tensor::stack(&rows, 0)?)
+            };
+
+            let input_ids = tensor(encodings.iter().map(|e| e.get_ids()).collect())?;
+            let type_ids = tensor(encodings.iter().map(|e| e.get_type_ids()).collect())?;
+            let attention_mask =
+                tensor(encodings.iter().map(|e| e.get_attention_mask()).collect())?;
+
+            let output = self
+                .model
+                .forward(&input_ids, &type_ids, Some(&attention_mask))?;
+
+            // El puntaje sale del token `[CLS]`, igual que en `BertForSequenceClassification`.
+            let cls = output.i((.., 0))?;
+            let pooled = self.pooler.forward(&cls)?.tanh()?;
+            let logits = self.classifier.forward(&pooled)?.squeeze(1)?;
+
+            scores.extend(logits.to_vec1::<f32>()?);
+        }
+
+        Ok(scores)
+    }
+}
@@ -0,0 +1,44 @@
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use http::StatusCode;
+
+/// Extractor que exige el token de administración en el header `Authorization: Bearer <token>`.
+/// El token se define con la variable de entorno `ADMIN_TOKEN`; si no está definida, las rutas de
+/// administración quedan deshabilitadas.
+#[derive(Debug)]
+pub struct Admin;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Admin
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Ok(token) = std::env::var("ADMIN_TOKEN") else {
+            tracing::warn!("Se intentó acceder a una ruta de administración sin `ADMIN_TOKEN`.");
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Las rutas de administración están deshabilitadas.",
+            ));
+        };
+
+        let provided = parts
+            .headers
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match provided {
+            Some(provided) if constant_time_eq(provided.as_bytes(), token.as_bytes()) => Ok(Admin),
+            _ => Err((
+                StatusCode::UNAUTHORIZED,
+                "Token de administración inválido.",
+            )),
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,28 +1,38 @@
 mod assets;
+mod auth;
 mod fallback;
 mod health_check;
 mod historial;
 mod index;
+mod perfiles;
 mod search;
+mod sinonimos;
+mod suggest;
 
 use askama_axum::{IntoResponse, Response};
 pub use assets::*;
+pub use auth::*;
 pub use fallback::*;
 pub use health_check::*;
 pub use historial::*;
 use http::StatusCode;
 pub use index::*;
+pub use perfiles::*;
 pub use search::*;
+pub use sinonimos::*;
+pub use suggest::*;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum SearchStrategy {
     Fts,
     Semantic,
     HybridKf,
     HybridRrf,
     HybridReRank,
+    /// Combina los puntajes de FTS y semántica normalizados, con los pesos de la búsqueda.
+    HybridConvex,
 }
 
 impl TryFrom<String> for SearchStrategy {
@@ -35,8 +45,9 @@ impl TryFrom<String> for SearchStrategy {
             "rrf" => Ok(Self::HybridRrf),
             "hkf" => Ok(Self::HybridKf),
             "rrs" => Ok(Self::HybridReRank),
+            "convex" => Ok(Self::HybridConvex),
             other => Err(format!(
-                "{other} No es una estrategia de búsqueda soportada, usa 'fts', 'semantic_search', 'HKF' o 'rrf'",
+                "{other} No es una estrategia de búsqueda soportada, usa 'fts', 'semantic_search', 'HKF', 'rrf' o 'convex'",
             )),
         }
     }
@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use http::StatusCode;
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::{campos, sqlite, startup::AppState, utils::TneaData};
+
+use super::{Admin, ReportError};
+
+#[derive(Serialize, Debug)]
+pub struct PerfilResponse {
+    id: u64,
+    template: String,
+}
+
+#[instrument(name = "Creando un perfil", skip(app, client, data))]
+pub async fn create_perfil(
+    _admin: Admin,
+    State(app): State<AppState>,
+    client: Extension<reqwest::Client>,
+    Json(data): Json<TneaData>,
+) -> eyre::Result<Response, ReportError> {
+    let (id, template) = upsert(&app, &client, None, &data)
+        .await?
+        .ok_or_else(|| eyre::eyre!("No se pudo crear el perfil"))?;
+    tracing::info!("Se creó el perfil {id}.");
+
+    Ok((StatusCode::CREATED, Json(PerfilResponse { id, template })).into_response())
+}
+
+#[instrument(name = "Actualizando un perfil", skip(app, client, data))]
+pub async fn update_perfil(
+    _admin: Admin,
+    Path(id): Path<u64>,
+    State(app): State<AppState>,
+    client: Extension<reqwest::Client>,
+    Json(data): Json<TneaData>,
+) -> eyre::Result<Response, ReportError> {
+    match upsert(&app, &client, Some(id), &data).await? {
+        Some((id, template)) => {
+            tracing::info!("Se actualizó el perfil {id}.");
+            Ok(Json(PerfilResponse { id, template }).into_response())
+        }
+        None => Ok((StatusCode::NOT_FOUND, format!("El perfil {id} no existe.")).into_response()),
+    }
+}
+
+#[instrument(name = "Eliminando un perfil", skip(app))]
+pub async fn delete_perfil(
+    _admin: Admin,
+    Path(id): Path<u64>,
+    State(app): State<AppState>,
+) -> eyre::Result<Response, ReportError> {
+    let db = app.db.lock().await;
+
+    if sqlite::delete_perfil(&db, id)? {
+        if let Some(hnsw) = &app.hnsw {
+            hnsw.write()
+                .map_err(|err| eyre::eyre!("{err}"))?
+                .eliminar(id);
+        }
+        tracing::info!("Se eliminó el perfil {id}.");
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok((StatusCode::NOT_FOUND, format!("El perfil {id} no existe.")).into_response())
+    }
+}
+
+/// Genera el template y su embedding, y los de cada campo si la base de datos tiene embeddings
+/// por campo, antes de escribir en la base de datos, para que un fallo de OpenAI no deje el
+/// perfil a medio actualizar. El embedding también se agrega o se vuelve a enlazar en el índice
+/// HNSW. Devuelve `None` si se pidió actualizar un perfil que no existe.
+async fn upsert(
+    app: &AppState,
+    client: &reqwest::Client,
+    id: Option<u64>,
+    data: &TneaData,
+) -> eyre::Result<Option<(u64, String)>> {
+    let (template, campos) = {
+        let db = app.db.lock().await;
+        let template = sqlite::render_template(&db, data, &app.template)?;
+        let campos = if campos::habilitados(&db)? {
+            campos::con_texto(sqlite::textos_campos(data))
+        } else {
+            Vec::new()
+        };
+        (template, campos)
+    };
+
+    let textos = std::iter::once(template.clone())
+        .chain(campos.iter().map(|(_, texto)| texto.clone()))
+        .collect();
+    let mut embeddings = sqlite::embed_textos(app.modelo, textos, app.dimensiones, client)
+        .await?
+        .into_iter();
+
+    let embedding = embeddings
+        .next()
+        .ok_or_else(|| eyre::eyre!("No se generó ningún embedding del template"))?;
+    let embeddings_campos: Vec<(&str, Vec<f32>)> = campos
+        .iter()
+        .map(|(campo, _)| *campo)
+        .zip(embeddings)
+        .collect();
+
+    let db = app.db.lock().await;
+    let Some(id) =
+        sqlite::upsert_perfil(&db, id, data, &app.template, &embedding, &embeddings_campos)?
+    else {
+        return Ok(None);
+    };
+
+    if let Some(hnsw) = &app.hnsw {
+        hnsw.write()
+            .map_err(|err| eyre::eyre!("{err}"))?
+            .insertar(id, &embedding);
+    }
+
+    Ok(Some((id, template)))
+}
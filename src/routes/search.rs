@@ -1,21 +1,140 @@
+use std::collections::HashMap;
+
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     Extension,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_aux::prelude::deserialize_option_number_from_string;
 use tracing::instrument;
 use zerocopy::IntoBytes;
 
 use crate::{
+    campos::{self, PesosCampos},
     cli::Cache,
-    openai,
+    corte::{self, Codo},
+    cuantizacion::Cuantizacion,
+    descripcion,
+    fts::Bm25Weights,
+    fusion::Normalizacion,
+    fuzzy,
+    hnsw::Indice,
+    metrica::Metrica,
+    mmr, openai,
+    orden::{self, Orden, Ordenamiento},
+    query::{self, SearchQuery},
     routes::{ReportError, SearchStrategy},
-    sqlite,
+    sinonimos, sqlite,
     startup::AppState,
-    templates::{DisplayableContent, ReRankDisplay, RrfTable, Sexo, Table, TableData, TneaDisplay},
+    templates::{
+        ConvexDisplay, ConvexTable, DisplayableContent, Filtrable, ReRankDisplay, RrfTable, Sexo,
+        Sugerencia, Table, TableData, TneaDisplay,
+    },
 };
 
-#[derive(Deserialize, Debug)]
+/// Condiciones sobre `tnea` que aplican los filtros de la búsqueda en todas las estrategias.
+const FILTROS: &str = "
+    (:provincias is null
+        or lower(tnea.provincia) in (select lower(value) from json_each(:provincias)))
+    and (:ciudades is null
+        or lower(tnea.ciudad) in (select lower(value) from json_each(:ciudades)))
+    and (:sexos is null
+        or tnea.sexo in (select value from json_each(:sexos)))
+    and (:columnas is null
+        or tnea.id in (select rowid from fts_tnea where fts_tnea match :columnas))
+    and (:exclusiones is null
+        or tnea.id not in (select rowid from fts_tnea where fts_tnea match :exclusiones))
+";
+
+/// Valores de los parámetros de [`FILTROS`]. Los filtros exactos se pasan como arrays en JSON.
+struct Filtros {
+    provincias: Option<String>,
+    ciudades: Option<String>,
+    sexos: Option<String>,
+    columnas: Option<String>,
+    exclusiones: Option<String>,
+}
+
+impl Filtros {
+    fn new(query: &SearchQuery) -> Self {
+        let json = |values: &Vec<String>| {
+            (!values.is_empty()).then(|| serde_json::Value::from(values.clone()).to_string())
+        };
+
+        Self {
+            provincias: json(&query.provincias),
+            ciudades: json(&query.ciudades),
+            sexos: json(&query.sexos),
+            columnas: query.columnas.clone(),
+            exclusiones: query.exclusiones.clone(),
+        }
+    }
+
+    /// Agrega los parámetros de los filtros a los de la consulta.
+    fn with<'a>(
+        &'a self,
+        params: &[(&'a str, &'a dyn rusqlite::ToSql)],
+    ) -> Vec<(&'a str, &'a dyn rusqlite::ToSql)> {
+        let filtros: [(&str, &dyn rusqlite::ToSql); 5] = [
+            (":provincias", &self.provincias),
+            (":ciudades", &self.ciudades),
+            (":sexos", &self.sexos),
+            (":columnas", &self.columnas),
+            (":exclusiones", &self.exclusiones),
+        ];
+
+        params.iter().copied().chain(filtros).collect()
+    }
+
+    /// Si la búsqueda restringe los registros con algún filtro.
+    fn activos(&self) -> bool {
+        self.provincias.is_some()
+            || self.ciudades.is_some()
+            || self.sexos.is_some()
+            || self.columnas.is_some()
+            || self.exclusiones.is_some()
+    }
+}
+
+/// Elige entre el índice HNSW y la búsqueda exacta, y devuelve el motivo para mostrarlo junto con
+/// los resultados. Con pocos vectores la búsqueda exacta es rápida, y con filtros los vecinos
+/// aproximados que los cumplen pueden ser muy pocos.
+fn elegir_indice(
+    app: &AppState,
+    filtros: &Filtros,
+    cuantizacion: Cuantizacion,
+) -> eyre::Result<(Indice, Option<String>)> {
+    let Some(hnsw) = &app.hnsw else {
+        return Ok((Indice::Exacto(cuantizacion), None));
+    };
+    let hnsw = hnsw.read().map_err(|err| eyre::eyre!("{err}"))?;
+
+    if hnsw.len() < hnsw.min_registros {
+        Ok((
+            Indice::Exacto(cuantizacion),
+            Some(format!(
+                "Búsqueda exacta: el índice HNSW tiene {} vectores, se usa a partir de {}.",
+                hnsw.len(),
+                hnsw.min_registros
+            )),
+        ))
+    } else if filtros.activos() {
+        Ok((
+            Indice::Exacto(cuantizacion),
+            Some("Búsqueda exacta: la búsqueda tiene filtros.".to_string()),
+        ))
+    } else {
+        Ok((
+            Indice::Hnsw,
+            Some(format!(
+                "Vecinos aproximados con el índice HNSW (ef={}).",
+                hnsw.ef
+            )),
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Params {
     query: String,
     strategy: SearchStrategy,
@@ -24,6 +143,131 @@ pub struct Params {
     edad_max: u64,
     peso_fts: f32,
     peso_semantic: f32,
+    /// Pesos de `bm25` que reemplazan a los de la configuración, ver [`crate::fts::Bm25Weights`].
+    bm25: Option<String>,
+    /// Si se expanden los términos con el diccionario de sinónimos. Por defecto se expanden.
+    sinonimos: Option<bool>,
+    /// Normalización de los puntajes en [`SearchStrategy::HybridConvex`].
+    normalizacion: Option<Normalizacion>,
+    /// Si se re-ordenan los primeros resultados con el cross-encoder.
+    rerank: Option<bool>,
+    /// Cantidad de resultados que se re-ordenan, reemplaza a la de la configuración.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    rerank_n: Option<usize>,
+    /// Lambda de MMR entre 0 y 1, ver [`mmr::diversificar`]. Si no se indica, no se diversifica.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    mmr: Option<f32>,
+    /// Pesos entre 0 y 100 de los embeddings por campo en la distancia semántica, ver
+    /// [`PesosCampos`]. Si no se indican, se usa solamente el embedding de `template`.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    peso_estudios: Option<f32>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    peso_experiencia: Option<f32>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    peso_descripcion: Option<f32>,
+    /// Si `query` es una descripción de puesto completa en lugar de una búsqueda, ver
+    /// [`buscar_descripcion`].
+    descripcion: Option<bool>,
+    /// Puntaje mínimo de los resultados, ver [`cortar`]. Se compara con el puntaje que determina
+    /// el orden: bm25 con signo invertido en FTS, o el puntaje combinado.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    puntaje_minimo: Option<f32>,
+    /// Distancia máxima de los resultados de la búsqueda semántica, ver [`cortar`].
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    distancia_maxima: Option<f32>,
+    /// Si se corta la lista donde los puntajes caen bruscamente, ver [`corte::codo`].
+    codo: Option<bool>,
+    /// Orden de los resultados, por defecto por relevancia. Las búsquedas que solamente cambian
+    /// el orden no se vuelven a contar en el historial.
+    sort: Option<Orden>,
+}
+
+impl Params {
+    /// Enlace a `/search` con los mismos parámetros, salvo la búsqueda y la estrategia.
+    fn href(&self, query: &str, strategy: SearchStrategy) -> String {
+        let params = Params {
+            query: query.to_string(),
+            strategy,
+            ..self.clone()
+        };
+
+        format!(
+            "/search?{}",
+            serde_urlencoded::to_string(&params).unwrap_or_default()
+        )
+    }
+
+    /// Enlaces de los encabezados de la tabla, con los mismos parámetros salvo el orden.
+    fn ordenamiento(&self) -> Ordenamiento {
+        let params = Params {
+            sort: None,
+            ..self.clone()
+        };
+
+        Ordenamiento::new(
+            format!(
+                "/search?{}",
+                serde_urlencoded::to_string(&params).unwrap_or_default()
+            ),
+            self.sort.unwrap_or_default(),
+        )
+    }
+}
+
+/// Búsquedas alternativas para cuando no hubo resultados: la búsqueda con las palabras
+/// corregidas según el vocabulario de `fts_tnea`, búsquedas anteriores parecidas que sí tuvieron
+/// resultados y, si la estrategia depende solamente de FTS, la misma búsqueda con la estrategia
+/// semántica.
+fn sugerencias(db: &rusqlite::Connection, params: &Params) -> eyre::Result<Vec<Sugerencia>> {
+    let mut sugerencias = Vec::new();
+
+    if let Some(corregida) = fuzzy::sugerir_correccion(db, &params.query)? {
+        sugerencias.push(Sugerencia {
+            href: params.href(&corregida, params.strategy),
+            texto: corregida,
+            motivo: "corrección ortográfica".to_string(),
+        });
+    }
+
+    for anterior in fuzzy::historial_similar(db, &params.query)? {
+        if sugerencias.iter().any(|s| s.texto == anterior) {
+            continue;
+        }
+        sugerencias.push(Sugerencia {
+            href: params.href(&anterior, params.strategy),
+            texto: anterior,
+            motivo: "búsqueda anterior con resultados".to_string(),
+        });
+    }
+
+    if matches!(
+        params.strategy,
+        SearchStrategy::Fts | SearchStrategy::HybridReRank
+    ) {
+        sugerencias.push(Sugerencia {
+            href: params.href(&params.query, SearchStrategy::Semantic),
+            texto: params.query.clone(),
+            motivo: "búsqueda semántica".to_string(),
+        });
+    }
+
+    Ok(sugerencias)
+}
+
+fn bad_request(
+    db: &rusqlite::Connection,
+    error: String,
+) -> Result<DisplayableContent, ReportError> {
+    Ok(DisplayableContent::BadRequest(Table {
+        msg: "No se realizó la búsqueda.".to_string(),
+        table: Vec::new(),
+        historial: sqlite::get_historial(db)?,
+        error: Some(error),
+        sugerencias: Vec::new(),
+        explicacion: Vec::new(),
+        metrica: None,
+        ordenamiento: None,
+    }))
 }
 
 #[axum::debug_handler]
@@ -39,23 +283,92 @@ pub async fn search(
         }
         Cache::Disabled => tracing::debug!("El caché se encuentra desactivado!"),
     };
-    let db = app.db.lock().await;
+    let mut db = app.db.lock().await;
+
+    let bm25 = match &params.bm25 {
+        Some(overrides) => match app.bm25.with_overrides(overrides) {
+            Ok(bm25) => bm25,
+            Err(err) => {
+                tracing::warn!("Los pesos de bm25 `{overrides}` no son válidos: {err}");
+                return bad_request(&db, err);
+            }
+        },
+        None => app.bm25,
+    };
+
+    if let Some(lambda) = params.mmr {
+        if !(0.0..=1.0).contains(&lambda) {
+            tracing::warn!("El lambda de MMR `{lambda}` no es válido.");
+            return bad_request(&db, "El lambda de MMR debe estar entre 0 y 1.".to_string());
+        }
+    }
+
+    let pesos_campos = match PesosCampos::new(
+        params.peso_estudios.unwrap_or_default(),
+        params.peso_experiencia.unwrap_or_default(),
+        params.peso_descripcion.unwrap_or_default(),
+    ) {
+        Ok(pesos) => pesos,
+        Err(err) => {
+            tracing::warn!("Los pesos de los campos no son válidos: {err}");
+            return bad_request(&db, err);
+        }
+    };
+
+    let cuantizacion = Cuantizacion::from_db(&db)?;
+    let metrica = Metrica::from_db(&db)?;
+
+    if params.descripcion.unwrap_or(false) {
+        let indice = elegir_indice(&app, &Filtros::new(&SearchQuery::default()), cuantizacion)?;
+        return buscar_descripcion(&app, &params, db, &client, &bm25, indice).await;
+    }
+
+    let mut query = match query::parse(&params.query) {
+        Ok(query) => query,
+        Err(err) => {
+            tracing::warn!("La búsqueda `{}` no es válida: {err}", params.query);
+            return bad_request(&db, err.to_string());
+        }
+    };
+
+    let expansiones = if params.sinonimos.unwrap_or(true) {
+        app.sinonimos.read().await.expandir(&mut query)
+    } else {
+        Vec::new()
+    };
+
+    // La búsqueda semántica no usa la expresión de FTS, a la que se agregan las variantes.
+    let correcciones = if matches!(params.strategy, SearchStrategy::Semantic) {
+        Vec::new()
+    } else {
+        fuzzy::corregir(&db, &mut query)?
+    };
+
+    let filtros = Filtros::new(&query);
+    let (indice, motivo_indice) = elegir_indice(&app, &filtros, cuantizacion)?;
+    let rank = sinonimos::rank_sql(&bm25.rank_sql());
+    let desglose = bm25.desglose_sql();
 
     let table = match params.strategy {
         SearchStrategy::Fts => {
-            let mut statement = match db.prepare(
+            let mut statement = match db.prepare(&format!(
                 "select
-                    rank as score, 
-                    email, 
-                    edad, 
-                    sexo, 
+                    {rank} as score, 
+                    tnea.email, 
+                    tnea.edad, 
+                    tnea.sexo, 
                     highlight(fts_tnea, 3, '<b style=\"color: green;\">', '</b>') as template,
-                    'fts' as match_type
+                    'fts' as match_type,
+                    {desglose} as desglose,
+                    tnea.id,
+                    tnea.provincia
                 from fts_tnea
-                where template match :query
-                order by rank 
+                join tnea on tnea.id = fts_tnea.rowid
+                where fts_tnea match :query
+                and {FILTROS}
+                order by score 
                 ",
-            ) {
+            )) {
                 Ok(stmt) => stmt,
                 Err(err) => {
                     tracing::error!("{}", err);
@@ -63,17 +376,28 @@ pub async fn search(
                 }
             };
 
-            let mut rows = match statement.query_map(&[(":query", &params.query)], |row| {
-                let score = row.get::<_, f32>(0).unwrap_or_default() * -1.;
-                let email: String = row.get(1).unwrap_or_default();
-                let edad: u64 = row.get(2).unwrap_or_default();
-                let sexo: Sexo = row.get(3).unwrap_or_default();
-                let template: String = row.get(4).unwrap_or_default();
-                let match_type: String = row.get(5).unwrap_or_default();
+            let mut rows = match statement.query_map(
+                filtros
+                    .with(rusqlite::named_params! { ":query": query.fts, ":sin_sinonimos": query.sin_sinonimos })
+                    .as_slice(),
+                |row| {
+                    let score = -row.get::<_, f32>(0).unwrap_or_default();
+                    let email: String = row.get(1).unwrap_or_default();
+                    let edad: u64 = row.get(2).unwrap_or_default();
+                    let sexo: Sexo = row.get(3).unwrap_or_default();
+                    let template: String = row.get(4).unwrap_or_default();
+                    let match_type: String = row.get(5).unwrap_or_default();
+                    let desglose: String = row.get(6).unwrap_or_default();
+                    let id: u64 = row.get(7).unwrap_or_default();
+                    let provincia: String = row.get(8).unwrap_or_default();
 
-                let data = TneaDisplay::new(email, edad, sexo, template, score, match_type);
-                Ok(data)
-            }) {
+                    let data = TneaDisplay::new(email, edad, sexo, template, score, match_type)
+                        .with_desglose(desglose)
+                        .with_id(id)
+                        .with_provincia(provincia);
+                    Ok(data)
+                },
+            ) {
                 Ok(rows) => rows
                     .collect::<Result<Vec<TneaDisplay>, _>>()
                     .unwrap_or_default(),
@@ -83,84 +407,28 @@ pub async fn search(
                 }
             };
 
-            match params.sexo {
-                Sexo::U => rows.retain(|x| (params.edad_min..params.edad_max).contains(&x.edad)),
-                Sexo::M => rows.retain(|x| {
-                    x.sexo == Sexo::M && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-                Sexo::F => rows.retain(|x| {
-                    x.sexo == Sexo::F && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-            };
+            retener(&mut rows, &params);
 
             TableData::Standard(rows)
         }
         SearchStrategy::Semantic => {
-            let query_emb = openai::embed_single(params.query.clone(), &client)
-                .await
-                .map_err(|err| tracing::error!("{err}"))
-                .expect("Fallo al crear un embedding del query");
+            let vec_matches = pesos_campos.vec_matches_sql("1000", indice, metrica);
 
-            let mut statement = match db.prepare(
-                "
-                select
-                    vec_tnea.distance,
-                    tnea.email,
-                    tnea.edad,
-                    tnea.sexo,
-                    tnea.template,
-                    'vec' as match_type
-                from vec_tnea
-                left join tnea on tnea.id = vec_tnea.row_id
-                where template_embedding match :embedding
-                and k = 1000
-                ",
-            ) {
-                Ok(stmt) => stmt,
-                Err(err) => {
-                    tracing::error!("{}", err);
-                    return Err(ReportError(err.into()));
-                }
-            };
+            let query_emb = embed_query(&app, &query, &client).await?;
 
-            let mut rows =
-                match statement.query_map(&[(":embedding", query_emb.as_bytes())], |row| {
-                    let score = row.get::<_, f32>(0).unwrap_or_default();
-                    let email: String = row.get(1).unwrap_or_default();
-                    let edad: u64 = row.get(2).unwrap_or_default();
-                    let sexo: Sexo = row.get(3).unwrap_or_default();
-                    let template: String = row.get(4).unwrap_or_default();
-                    let match_type: String = row.get(5).unwrap_or_default();
-
-                    let data = TneaDisplay::new(email, edad, sexo, template, score, match_type);
-
-                    Ok(data)
-                }) {
-                    Ok(rows) => rows
-                        .collect::<Result<Vec<TneaDisplay>, _>>()
-                        .unwrap_or_default(),
-                    Err(err) => {
-                        tracing::error!("{}", err);
-                        return Err(ReportError(err.into()));
-                    }
-                };
-
-            match params.sexo {
-                Sexo::U => rows.retain(|x| (params.edad_min..params.edad_max).contains(&x.edad)),
-                Sexo::M => rows.retain(|x| {
-                    x.sexo == Sexo::M && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-                Sexo::F => rows.retain(|x| {
-                    x.sexo == Sexo::F && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-            };
-            TableData::Standard(rows)
+            TableData::Standard(vecinos(
+                &db,
+                &vec_matches,
+                &filtros,
+                &query_emb,
+                None,
+                &params,
+            )?)
         }
         SearchStrategy::HybridRrf => {
-            let query_emb = openai::embed_single(params.query.clone(), &client)
-                .await
-                .map_err(|err| tracing::error!("{err}"))
-                .expect("Fallo al crear un embedding del query");
+            let vec_matches = pesos_campos.vec_matches_sql(":k", indice, metrica);
+
+            let query_emb = embed_query(&app, &query, &client).await?;
 
             let k: i64 = 1_000;
 
@@ -169,29 +437,36 @@ pub async fn search(
             let weight_fts: f32 = params.peso_fts / 100.0;
             let rrf_k: i64 = 60;
 
-            let mut statement = match db.prepare(
+            let mut statement = match db.prepare(&format!(
                 "
                 with vec_matches as (
                 select
                     row_id,
                     row_number() over (order by distance) as rank_number,
                     distance
-                from vec_tnea
-                where
-                    template_embedding match :embedding
-                    and k = :k
+                from ({vec_matches})
                 ),
 
-                fts_matches as (
+                fts_scores as (
                 select
                     rowid as row_id,
-                    row_number() over (order by rank) as rank_number,
-                    rank as score
+                    {rank} as score,
+                    {desglose} as desglose
                 from fts_tnea
-                where template match :query
+                where fts_tnea match :query
+                order by score
                 limit :k
                 ),
 
+                fts_matches as (
+                select
+                    row_id,
+                    row_number() over (order by score) as rank_number,
+                    score,
+                    desglose
+                from fts_scores
+                ),
+
                 final as (
                 select
                     tnea.template,
@@ -205,15 +480,19 @@ pub async fn search(
                         coalesce(1.0 / (:rrf_k + vec_matches.rank_number), 0.0) * :weight_vec
                     ) as combined_rank,
                     vec_matches.distance as vec_distance,
-                    fts_matches.score as fts_score
+                    fts_matches.score as fts_score,
+                    fts_matches.desglose,
+                    tnea.id,
+                    tnea.provincia
                 from fts_matches
                 full outer join vec_matches on vec_matches.row_id = fts_matches.row_id
                 join tnea on tnea.id = coalesce(fts_matches.row_id, vec_matches.row_id)
+                where {FILTROS}
                 order by combined_rank desc
                 )
                 select * from final;                
             ",
-            ) {
+            )) {
                 Ok(stmt) => stmt,
                 Err(err) => {
                     tracing::error!("{}", err);
@@ -222,7 +501,7 @@ pub async fn search(
             };
 
             let mut rows = match statement.query_map(
-                rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": params.query, ":k": k, ":weight_fts":weight_fts, ":weight_vec":weight_vec ,":rrf_k":rrf_k },
+                filtros.with(rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": query.fts, ":sin_sinonimos": query.sin_sinonimos, ":k": k, ":weight_fts":weight_fts, ":weight_vec":weight_vec ,":rrf_k":rrf_k }).as_slice(),
                 |row| {
                     let template: String = row.get(0).unwrap_or_default();
                     let email: String = row.get(1).unwrap_or_default();
@@ -232,10 +511,16 @@ pub async fn search(
                     let fts_rank: i64= row.get(5).unwrap_or_default();
                     let combined_rank: f32 = row.get(6).unwrap_or_default();
                     let vec_score: f32= row.get(7).unwrap_or_default();
-                    let fts_score = row.get::<_, f32>(8).unwrap_or_default() * -1.;
+                    let fts_score = -row.get::<_, f32>(8).unwrap_or_default();
+                    let desglose: String = row.get(9).unwrap_or_default();
+                    let id: u64 = row.get(10).unwrap_or_default();
+                    let provincia: String = row.get(11).unwrap_or_default();
 
 
-                    let data = ReRankDisplay::new(template,email, edad, sexo, fts_rank, vec_rank, combined_rank, vec_score, fts_score);
+                    let data = ReRankDisplay::new(template,email, edad, sexo, fts_rank, vec_rank, combined_rank, vec_score, fts_score)
+                        .with_desglose(desglose)
+                        .with_id(id)
+                        .with_provincia(provincia);
                     Ok(data)
                 },
             ) {
@@ -248,67 +533,61 @@ pub async fn search(
 
                 }
             };
-            match params.sexo {
-                Sexo::U => rows.retain(|x| (params.edad_min..params.edad_max).contains(&x.edad)),
-                Sexo::M => rows.retain(|x| {
-                    x.sexo == Sexo::M && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-                Sexo::F => rows.retain(|x| {
-                    x.sexo == Sexo::F && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-            };
+            retener(&mut rows, &params);
             TableData::Rrf(rows)
         }
         SearchStrategy::HybridKf => {
-            let query_emb = openai::embed_single(params.query.clone(), &client)
-                .await
-                .map_err(|err| tracing::error!("{err}"))
-                .expect("Fallo al crear un embedding del query");
+            let vec_matches = pesos_campos.vec_matches_sql(":k", indice, metrica);
+
+            let query_emb = embed_query(&app, &query, &client).await?;
 
             let k: i64 = 1000;
 
-            let mut statement = match db.prepare(
+            // Primero los resultados de FTS ordenados por bm25 y luego los que solamente encontró
+            // la búsqueda semántica, ordenados por distancia. Cada registro aparece una sola vez.
+            let mut statement = match db.prepare(&format!(
                 "
                 with fts_matches as (
                 select
                     rowid as row_id,
-                    rank as score
+                    {rank} as score,
+                    {desglose} as desglose
                 from fts_tnea
-                where template match :query
+                where fts_tnea match :query
+                order by score
                 limit :k
                 ),
 
                 vec_matches as (
                 select
                     row_id,
-                    distance as score
-                from vec_tnea
-                where
-                    template_embedding match :embedding
-                    and k = :k
-                order by distance
-                ),
-
-                combined as (
-                select 'fts' as match_type, * from fts_matches
-                union all
-                select 'vec' as match_type, * from vec_matches
-                ),
+                    distance
+                from ({vec_matches})
+                )
 
-                final as (
-                select distinct
+                select
                     tnea.template,
                     tnea.email,
                     tnea.edad,
                     tnea.sexo,
-                    combined.score,
-                    combined.match_type
-                from combined
-                left join tnea on tnea.id = combined.row_id
-                )
-                select * from final;
+                    fts_matches.score as fts_score,
+                    vec_matches.distance as vec_distance,
+                    case
+                        when fts_matches.row_id is not null and vec_matches.row_id is not null
+                            then 'fts+vec'
+                        when fts_matches.row_id is not null then 'fts'
+                        else 'vec'
+                    end as match_type,
+                    coalesce(fts_matches.desglose, '') as desglose,
+                    tnea.id,
+                    tnea.provincia
+                from fts_matches
+                full outer join vec_matches on vec_matches.row_id = fts_matches.row_id
+                join tnea on tnea.id = coalesce(fts_matches.row_id, vec_matches.row_id)
+                where {FILTROS}
+                order by fts_matches.score is null, fts_matches.score, vec_matches.distance
                 ",
-            ) {
+            )) {
                 Ok(stmt) => stmt,
                 Err(err) => {
                     tracing::error!("{}", err);
@@ -317,16 +596,28 @@ pub async fn search(
             };
 
             let mut rows = match statement.query_map(
-                rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": params.query, ":k": k},
+                filtros.with(rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": query.fts, ":sin_sinonimos": query.sin_sinonimos, ":k": k}).as_slice(),
                 |row| {
                     let template: String = row.get(0).unwrap_or_default();
                     let email: String = row.get(1).unwrap_or_default();
                     let edad: u64 = row.get(2).unwrap_or_default();
                     let sexo: Sexo= row.get(3).unwrap_or_default();
-                    let score: f32 = row.get(4).unwrap_or_default();
-                    let match_type: String = row.get(5).unwrap_or_default();
+                    let fts_score = row.get::<_, Option<f32>>(4).unwrap_or_default().map(|score| -score);
+                    let vec_distance: Option<f32> = row.get(5).unwrap_or_default();
+                    let match_type: String = row.get(6).unwrap_or_default();
+                    let desglose: String = row.get(7).unwrap_or_default();
+                    let id: u64 = row.get(8).unwrap_or_default();
+                    let provincia: String = row.get(9).unwrap_or_default();
+
+                    // El puntaje está en la escala de bm25, en la que un registro que FTS no
+                    // encontró vale 0. Entre ellos el orden lo da la distancia.
+                    let score = fts_score.unwrap_or_default();
 
-                    let data = TneaDisplay::new(email, edad, sexo, template, score, match_type);
+                    let data = TneaDisplay::new(email, edad, sexo, template, score, match_type)
+                        .with_desglose(desglose)
+                        .with_puntajes(fts_score, vec_distance)
+                        .with_id(id)
+                        .with_provincia(provincia);
                     Ok(data)
                 },
             ) {
@@ -338,33 +629,24 @@ pub async fn search(
                     return Err(ReportError(err.into()));
                 }
             };
-            match params.sexo {
-                Sexo::U => rows.retain(|x| (params.edad_min..params.edad_max).contains(&x.edad)),
-                Sexo::M => rows.retain(|x| {
-                    x.sexo == Sexo::M && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-                Sexo::F => rows.retain(|x| {
-                    x.sexo == Sexo::F && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-            };
+            retener(&mut rows, &params);
             TableData::Standard(rows)
         }
         SearchStrategy::HybridReRank => {
-            let query_emb = openai::embed_single(params.query.clone(), &client)
-                .await
-                .map_err(|err| tracing::error!("{err}"))
-                .expect("Fallo al crear un embedding del query");
+            let query_emb = embed_query(&app, &query, &client).await?;
 
             let k: i64 = 1000;
+            let distancia = metrica.distancia_entre(":embedding", "embeddings.template_embedding");
 
-            let mut statement = match db.prepare(
+            let mut statement = match db.prepare(&format!(
                 "
                 with fts_matches as (
                 select
                     rowid,
-                    rank as score
+                    {rank} as score,
+                    {desglose} as desglose
                 from fts_tnea
-                where template match :query
+                where fts_tnea match :query
                 limit :k
                 ),
 
@@ -383,15 +665,19 @@ pub async fn search(
                     tnea.edad,
                     tnea.sexo,
                     fts_matches.score,
-                    'fts' as match_type
+                    'fts' as match_type,
+                    fts_matches.desglose,
+                    tnea.id,
+                    tnea.provincia
                 from fts_matches
                 left join tnea on tnea.id = fts_matches.rowid
                 left join embeddings on embeddings.rowid = fts_matches.rowid
-                order by vec_distance_cosine(:embedding, embeddings.template_embedding)
+                where {FILTROS}
+                order by {distancia}
                 )
                 select * from final;
                 ",
-            ) {
+            )) {
                 Ok(stmt) => stmt,
                 Err(err) => {
                     tracing::error!("{}", err);
@@ -400,16 +686,22 @@ pub async fn search(
             };
 
             let mut rows = match statement.query_map(
-                rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": params.query, ":k": k},
+                filtros.with(rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": query.fts, ":sin_sinonimos": query.sin_sinonimos, ":k": k}).as_slice(),
                 |row| {
                     let template: String = row.get(0).unwrap_or_default();
                     let email: String = row.get(1).unwrap_or_default();
                     let edad: u64= row.get(2).unwrap_or_default();
                     let sexo:Sexo = row.get(3).unwrap_or_default();
-                let score = row.get::<_, f32>(4).unwrap_or_default() * -1.;
+                let score = -row.get::<_, f32>(4).unwrap_or_default();
                     let match_type: String = row.get(5).unwrap_or_default();
+                    let desglose: String = row.get(6).unwrap_or_default();
+                    let id: u64 = row.get(7).unwrap_or_default();
+                    let provincia: String = row.get(8).unwrap_or_default();
 
-                    let data = TneaDisplay::new(email, edad, sexo, template, score, match_type);
+                    let data = TneaDisplay::new(email, edad, sexo, template, score, match_type)
+                        .with_desglose(desglose)
+                        .with_id(id)
+                        .with_provincia(provincia);
                     Ok(data)
                 },
             ) {
@@ -421,20 +713,204 @@ pub async fn search(
                     return Err(ReportError(err.into()));
                 }
             };
-            match params.sexo {
-                Sexo::U => rows.retain(|x| (params.edad_min..params.edad_max).contains(&x.edad)),
-                Sexo::M => rows.retain(|x| {
-                    x.sexo == Sexo::M && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-                Sexo::F => rows.retain(|x| {
-                    x.sexo == Sexo::F && (params.edad_min..params.edad_max).contains(&x.edad)
-                }),
-            };
+            retener(&mut rows, &params);
 
             TableData::Standard(rows)
         }
+        SearchStrategy::HybridConvex => {
+            let vec_matches = pesos_campos.vec_matches_sql(":k", indice, metrica);
+
+            let query_emb = embed_query(&app, &query, &client).await?;
+
+            let k: i64 = 1000;
+
+            let weight_vec = params.peso_semantic / 100.0;
+            let weight_fts: f32 = params.peso_fts / 100.0;
+            let normalizacion = params.normalizacion.unwrap_or_default();
+
+            let mut statement = match db.prepare(&format!(
+                "
+                with fts_matches as (
+                select
+                    rowid as row_id,
+                    {rank} as score,
+                    {desglose} as desglose
+                from fts_tnea
+                where fts_tnea match :query
+                order by score
+                limit :k
+                ),
+
+                vec_matches as (
+                select
+                    row_id,
+                    distance
+                from ({vec_matches})
+                )
+
+                select
+                    tnea.template,
+                    tnea.email,
+                    tnea.edad,
+                    tnea.sexo,
+                    fts_matches.score,
+                    vec_matches.distance,
+                    fts_matches.desglose,
+                    tnea.id,
+                    tnea.provincia
+                from fts_matches
+                full outer join vec_matches on vec_matches.row_id = fts_matches.row_id
+                join tnea on tnea.id = coalesce(fts_matches.row_id, vec_matches.row_id)
+                where {FILTROS}
+                ",
+            )) {
+                Ok(stmt) => stmt,
+                Err(err) => {
+                    tracing::error!("{}", err);
+                    return Err(ReportError(err.into()));
+                }
+            };
+
+            let mut rows = match statement.query_map(
+                filtros.with(rusqlite::named_params! { ":embedding": query_emb.as_bytes(), ":query": query.fts, ":sin_sinonimos": query.sin_sinonimos, ":k": k }).as_slice(),
+                |row| {
+                    let template: String = row.get(0).unwrap_or_default();
+                    let email: String = row.get(1).unwrap_or_default();
+                    let edad: u64 = row.get(2).unwrap_or_default();
+                    let sexo: Sexo = row.get(3).unwrap_or_default();
+                    let fts_score = row.get::<_, Option<f32>>(4).unwrap_or_default().map(|score| -score);
+                    let vec_distance: Option<f32> = row.get(5).unwrap_or_default();
+                    let desglose: String = row.get::<_, Option<String>>(6).unwrap_or_default().unwrap_or_default();
+                    let id: u64 = row.get(7).unwrap_or_default();
+                    let provincia: String = row.get(8).unwrap_or_default();
+
+                    Ok(ConvexDisplay::new(template, email, edad, sexo, fts_score, vec_distance, desglose)
+                        .with_id(id)
+                        .with_provincia(provincia))
+                },
+            ) {
+                Ok(rows) => rows
+                    .collect::<Result<Vec<ConvexDisplay>, _>>()
+                    .unwrap_or_default(),
+                Err(err) => {
+                    tracing::error!("{}", err);
+                    return Err(ReportError(err.into()));
+                }
+            };
+
+            // Se filtra antes de normalizar, para que los registros descartados no cambien los
+            // extremos de cada lista.
+            retener(&mut rows, &params);
+
+            // Las distancias se invierten para que, igual que en FTS, un puntaje mayor sea mejor.
+            let fts_norm =
+                normalizacion.normalizar(&rows.iter().map(|row| row.fts_score).collect::<Vec<_>>());
+            let vec_norm = normalizacion.normalizar(
+                &rows
+                    .iter()
+                    .map(|row| row.vec_distance.map(|distance| -distance))
+                    .collect::<Vec<_>>(),
+            );
+
+            let mut rows: Vec<ConvexDisplay> = rows
+                .into_iter()
+                .zip(fts_norm.into_iter().zip(vec_norm))
+                .map(|(row, (fts_norm, vec_norm))| {
+                    row.with_normalizados(fts_norm, vec_norm, weight_fts, weight_vec)
+                })
+                .collect();
+
+            rows.sort_by(|a, b| b.combined.total_cmp(&a.combined));
+
+            TableData::Convex(rows)
+        }
+    };
+
+    let mut table = table;
+    let Postproceso {
+        cortes,
+        reranking,
+        diversificacion,
+        orden,
+    } = postprocesar(&app, &mut db, &params, &query.text, &mut table).await?;
+
+    let mut pesos = if matches!(params.strategy, SearchStrategy::Semantic) {
+        String::new()
+    } else {
+        format!(" Pesos de bm25: {bm25}.")
     };
 
+    if !correcciones.is_empty() {
+        let correcciones: Vec<String> = correcciones.iter().map(ToString::to_string).collect();
+        pesos.push_str(&format!(
+            " Se buscaron también variantes de: {}.",
+            correcciones.join("; ")
+        ));
+    }
+
+    for corte in &cortes {
+        pesos.push_str(&format!(" {corte}"));
+    }
+
+    if let Some(reranking) = &reranking {
+        pesos.push_str(&format!(" {reranking}"));
+    }
+
+    let mut explicacion = Vec::new();
+    if !matches!(params.strategy, SearchStrategy::Semantic) {
+        explicacion.push(format!("Expresión FTS: {}", query.fts));
+    }
+    if !matches!(params.strategy, SearchStrategy::Fts) {
+        explicacion.push(format!("Texto del embedding: {}", query.text));
+        explicacion.push(format!("Métrica de distancia: {metrica}"));
+        explicacion.extend(motivo_indice);
+    }
+    if matches!(params.strategy, SearchStrategy::HybridConvex) {
+        explicacion.push(format!(
+            "Normalización: {}, peso FTS {:.2}, peso semántico {:.2}",
+            params.normalizacion.unwrap_or_default(),
+            params.peso_fts / 100.0,
+            params.peso_semantic / 100.0
+        ));
+    }
+    if !pesos_campos.is_empty() {
+        if matches!(
+            params.strategy,
+            SearchStrategy::Fts | SearchStrategy::HybridReRank
+        ) {
+            explicacion.push(format!(
+                "Los pesos por campo no se aplican a la estrategia {:?}.",
+                params.strategy
+            ));
+        } else if campos::habilitados(&db)? {
+            explicacion.push(format!("Pesos de los embeddings: {pesos_campos}"));
+        } else {
+            explicacion.push(
+                "La base de datos no tiene embeddings por campo, ejecutá `sync -S vector --campos` \
+                para generarlos."
+                    .to_string(),
+            );
+        }
+    }
+    if let Some(diversificacion) = diversificacion {
+        explicacion.push(diversificacion);
+    }
+    if !params.sinonimos.unwrap_or(true) {
+        explicacion.push("Sinónimos desactivados.".to_string());
+    }
+    for expansion in &expansiones {
+        explicacion.push(format!(
+            "Sinónimos: {expansion} (peso {})",
+            sinonimos::PESO_SINONIMO
+        ));
+    }
+    for correccion in &correcciones {
+        explicacion.push(format!("Variantes: {correccion}"));
+    }
+    if orden != Orden::Relevancia {
+        explicacion.push(format!("Ordenado por {orden}."));
+    }
+
     match table {
         TableData::Standard(table) => {
             tracing::info!(
@@ -445,14 +921,26 @@ pub async fn search(
                 table.last().map_or_else(Default::default, |d| d.score),
             );
 
-            sqlite::update_historial(&db, &params.query)?;
+            if params.sort.is_none() {
+                sqlite::update_historial(&db, &params.query, table.len())?;
+            }
 
             let historial = sqlite::get_historial(&db)?;
+            let sugerencias = if table.is_empty() {
+                sugerencias(&db, &params)?
+            } else {
+                Vec::new()
+            };
 
             Ok(DisplayableContent::Common(Table {
-                msg: format!("Hay un total de {} resultados.", table.len()),
+                msg: resumen(table.len(), &pesos),
                 table,
                 historial,
+                error: None,
+                sugerencias,
+                explicacion,
+                metrica: Some(metrica),
+                ordenamiento: Some(params.ordenamiento()),
             }))
         }
         TableData::Rrf(table) => {
@@ -464,15 +952,849 @@ pub async fn search(
                 table.last().map_or_else(Default::default, |d| d.combined_rank),
             );
 
-            sqlite::update_historial(&db, &params.query)?;
+            if params.sort.is_none() {
+                sqlite::update_historial(&db, &params.query, table.len())?;
+            }
 
             let historial = sqlite::get_historial(&db)?;
+            let sugerencias = if table.is_empty() {
+                sugerencias(&db, &params)?
+            } else {
+                Vec::new()
+            };
 
             Ok(DisplayableContent::RrfTable(RrfTable {
-                msg: format!("Hay un total de {} resultados.", table.len()),
+                msg: resumen(table.len(), &pesos),
                 table,
                 historial,
+                error: None,
+                sugerencias,
+                explicacion,
+                ordenamiento: Some(params.ordenamiento()),
             }))
         }
+        TableData::Convex(table) => {
+            tracing::info!(
+                "Busqueda para el query: `{}`, exitosa! de {} registros, el mejor puntaje fue: `{}` y el peor fue: `{}`",
+                params.query,
+                table.len(),
+                table.first().map_or_else(Default::default, |d| d.combined),
+                table.last().map_or_else(Default::default, |d| d.combined),
+            );
+
+            if params.sort.is_none() {
+                sqlite::update_historial(&db, &params.query, table.len())?;
+            }
+
+            let historial = sqlite::get_historial(&db)?;
+            let sugerencias = if table.is_empty() {
+                sugerencias(&db, &params)?
+            } else {
+                Vec::new()
+            };
+
+            Ok(DisplayableContent::ConvexTable(ConvexTable {
+                msg: resumen(table.len(), &pesos),
+                table,
+                historial,
+                error: None,
+                sugerencias,
+                explicacion,
+                metrica: Some(metrica),
+                ordenamiento: Some(params.ordenamiento()),
+            }))
+        }
+    }
+}
+
+/// Genera el embedding del texto de la búsqueda, registrando el error si falla.
+async fn embed_query(
+    app: &AppState,
+    query: &SearchQuery,
+    client: &reqwest::Client,
+) -> eyre::Result<Vec<f32>, ReportError> {
+    openai::embed_single(query.text.clone(), app.dimensiones, client)
+        .await
+        .map_err(|err| {
+            tracing::error!("Fallo al crear un embedding del query: {err}");
+            ReportError(err)
+        })
+}
+
+/// Descarta los resultados que no están en el rango de edad de `params` o, si se filtró por
+/// sexo, que no son del sexo indicado.
+fn retener<T: Filtrable>(rows: &mut Vec<T>, params: &Params) {
+    let edades = params.edad_min..params.edad_max;
+    rows.retain(|row| {
+        edades.contains(&row.edad()) && (params.sexo == Sexo::U || *row.sexo() == params.sexo)
+    });
+}
+
+/// Registros de `vec_matches` que cumplen los filtros y la edad y el sexo de `params`, ordenados
+/// por su distancia a `embedding`, sin el registro `excluir`.
+fn vecinos(
+    db: &rusqlite::Connection,
+    vec_matches: &str,
+    filtros: &Filtros,
+    embedding: &[f32],
+    excluir: Option<u64>,
+    params: &Params,
+) -> eyre::Result<Vec<TneaDisplay>, ReportError> {
+    let mut statement = match db.prepare(&format!(
+        "
+        with vec_matches as (
+        select
+            row_id,
+            distance
+        from ({vec_matches})
+        )
+        select
+            vec_matches.distance,
+            tnea.email,
+            tnea.edad,
+            tnea.sexo,
+            tnea.template,
+            'vec' as match_type,
+            tnea.id,
+            tnea.provincia
+        from vec_matches
+        join tnea on tnea.id = vec_matches.row_id
+        where (:excluir is null or tnea.id != :excluir) and {FILTROS}
+        order by vec_matches.distance
+        ",
+    )) {
+        Ok(stmt) => stmt,
+        Err(err) => {
+            tracing::error!("{}", err);
+            return Err(ReportError(err.into()));
+        }
+    };
+
+    let mut rows = match statement.query_map(
+        filtros
+            .with(
+                rusqlite::named_params! { ":embedding": embedding.as_bytes(), ":excluir": excluir },
+            )
+            .as_slice(),
+        |row| {
+            let score = row.get::<_, f32>(0).unwrap_or_default();
+            let email: String = row.get(1).unwrap_or_default();
+            let edad: u64 = row.get(2).unwrap_or_default();
+            let sexo: Sexo = row.get(3).unwrap_or_default();
+            let template: String = row.get(4).unwrap_or_default();
+            let match_type: String = row.get(5).unwrap_or_default();
+            let id: u64 = row.get(6).unwrap_or_default();
+            let provincia: String = row.get(7).unwrap_or_default();
+
+            let data = TneaDisplay::new(email, edad, sexo, template, score, match_type)
+                .with_id(id)
+                .with_provincia(provincia);
+
+            Ok(data)
+        },
+    ) {
+        Ok(rows) => rows
+            .collect::<Result<Vec<TneaDisplay>, _>>()
+            .unwrap_or_default(),
+        Err(err) => {
+            tracing::error!("{}", err);
+            return Err(ReportError(err.into()));
+        }
+    };
+
+    retener(&mut rows, params);
+
+    Ok(rows)
+}
+
+/// Lo que se aplicó a los resultados en [`postprocesar`], para mostrarlo junto con ellos.
+struct Postproceso {
+    cortes: Vec<String>,
+    reranking: Option<String>,
+    diversificacion: Option<String>,
+    orden: Orden,
+}
+
+/// Aplica a los resultados, en este orden, los cortes de [`cortar`], el re-ordenamiento con el
+/// cross-encoder usando `consulta`, la diversificación con MMR y el orden pedido. Se recibe el
+/// lock de la base de datos, y no la conexión, para que el future se pueda enviar entre hilos.
+async fn postprocesar(
+    app: &AppState,
+    db: &mut tokio::sync::MutexGuard<'_, rusqlite::Connection>,
+    params: &Params,
+    consulta: &str,
+    table: &mut TableData,
+) -> eyre::Result<Postproceso> {
+    let cortes = cortar(table, params);
+
+    let reranking = if params.rerank.unwrap_or(false) {
+        Some(rerank(app, db, consulta, table, params.rerank_n).await?)
+    } else {
+        None
+    };
+
+    let diversificacion = match params.mmr {
+        Some(lambda) => {
+            let candidatos = match &mut *table {
+                TableData::Standard(rows) => mmr::diversificar(db, rows, |row| row.id, lambda)?,
+                TableData::Rrf(rows) => mmr::diversificar(db, rows, |row| row.id, lambda)?,
+                TableData::Convex(rows) => mmr::diversificar(db, rows, |row| row.id, lambda)?,
+            };
+            Some(format!(
+                "MMR con lambda {lambda} sobre los primeros {candidatos} resultados"
+            ))
+        }
+        None => None,
+    };
+
+    let orden = params.sort.unwrap_or_default();
+    match table {
+        TableData::Standard(rows) => orden::ordenar(rows, orden),
+        TableData::Rrf(rows) => orden::ordenar(rows, orden),
+        TableData::Convex(rows) => orden::ordenar(rows, orden),
+    }
+
+    Ok(Postproceso {
+        cortes,
+        reranking,
+        diversificacion,
+        orden,
+    })
+}
+
+/// Aplica los umbrales de relevancia y el corte por codo de `params`, antes de re-ordenar o
+/// diversificar los resultados, y devuelve el motivo de cada corte para el mensaje.
+///
+/// - `puntaje_minimo` se compara con el puntaje de cada estrategia. En
+///   [`SearchStrategy::HybridKf`] los registros que FTS no encontró tienen puntaje 0.
+/// - `distancia_maxima` se compara con la distancia semántica. En las estrategias híbridas
+///   solamente descarta a los registros que no encontró FTS, al resto los juzga su puntaje.
+/// - El codo se busca en el puntaje que determina el orden, por lo que no se aplica a las
+///   estrategias que ordenan por un criterio distinto al puntaje que muestran.
+fn cortar(table: &mut TableData, params: &Params) -> Vec<String> {
+    let strategy = params.strategy;
+    let mut motivos = Vec::new();
+
+    if let Some(minimo) = params.puntaje_minimo {
+        let descartados = match (strategy, &mut *table) {
+            (SearchStrategy::Semantic, _) => None,
+            (_, TableData::Standard(rows)) => Some(corte::umbral(rows, |row| row.score >= minimo)),
+            (_, TableData::Rrf(rows)) => {
+                Some(corte::umbral(rows, |row| row.combined_rank >= minimo))
+            }
+            (_, TableData::Convex(rows)) => Some(corte::umbral(rows, |row| row.combined >= minimo)),
+        };
+
+        motivos.push(match descartados {
+            Some(descartados) => {
+                format!("Se descartaron {descartados} resultados con puntaje menor a {minimo}.")
+            }
+            None => format!("El puntaje mínimo no se aplica a la estrategia {strategy:?}."),
+        });
+    }
+
+    if let Some(maxima) = params.distancia_maxima {
+        let descartados = match (strategy, &mut *table) {
+            (SearchStrategy::Fts | SearchStrategy::HybridReRank, _) => None,
+            (SearchStrategy::Semantic, TableData::Standard(rows)) => {
+                Some(corte::umbral(rows, |row| row.score <= maxima))
+            }
+            (_, TableData::Standard(rows)) => Some(corte::umbral(rows, |row| {
+                row.fts_score.is_some() || row.vec_distance.is_none_or(|d| d <= maxima)
+            })),
+            (_, TableData::Rrf(rows)) => Some(corte::umbral(rows, |row| {
+                row.fts_rank != 0 || row.vec_score <= maxima
+            })),
+            (_, TableData::Convex(rows)) => Some(corte::umbral(rows, |row| {
+                row.fts_score.is_some() || row.vec_distance.is_none_or(|d| d <= maxima)
+            })),
+        };
+
+        motivos.push(match descartados {
+            Some(descartados) => {
+                format!("Se descartaron {descartados} resultados con distancia mayor a {maxima}.")
+            }
+            None => format!("La distancia máxima no se aplica a la estrategia {strategy:?}."),
+        });
+    }
+
+    if params.codo.unwrap_or(false) {
+        let codo = match (strategy, &mut *table) {
+            (SearchStrategy::HybridReRank, _) => None,
+            // En la búsqueda semántica el puntaje es la distancia, menor es mejor.
+            (SearchStrategy::Semantic, TableData::Standard(rows)) => {
+                Some(corte::codo(rows, |row| -row.score).map(|codo| Codo {
+                    puntaje: -codo.puntaje,
+                    ..codo
+                }))
+            }
+            (_, TableData::Standard(rows)) => Some(corte::codo(rows, |row| row.score)),
+            (_, TableData::Rrf(rows)) => Some(corte::codo(rows, |row| row.combined_rank)),
+            (_, TableData::Convex(rows)) => Some(corte::codo(rows, |row| row.combined)),
+        };
+
+        motivos.push(match codo {
+            Some(Some(codo)) => format!(
+                "Corte por codo: se conservaron los primeros {} resultados, hasta el puntaje {}, y \
+                se descartaron {} con puntajes mucho menores.",
+                codo.conservados, codo.puntaje, codo.descartados
+            ),
+            Some(None) => {
+                "Corte por codo: los puntajes no caen bruscamente, no se cortó.".to_string()
+            }
+            None => format!("El corte por codo no se aplica a la estrategia {strategy:?}."),
+        });
+    }
+
+    motivos
+}
+
+/// Posición de un registro en cada una de las listas que se combinan en [`buscar_descripcion`].
+#[derive(Debug, Default)]
+struct Coincidencia {
+    fts_rank: Option<usize>,
+    fts_score: Option<f32>,
+    desglose: String,
+    vec_rank: Option<usize>,
+    vec_distance: Option<f32>,
+    combined: f32,
+}
+
+/// Búsqueda a partir de una descripción de puesto, que es demasiado larga para usarla
+/// directamente como expresión de FTS o como un único embedding:
+/// - Para FTS se buscan las palabras clave de la descripción con `OR`, ver
+///   [`descripcion::palabras_clave`].
+/// - Para la búsqueda semántica se genera un embedding de cada fragmento de la descripción, ver
+///   [`descripcion::fragmentos`], y se busca cada uno por separado.
+///
+/// Las listas se combinan con Reciprocal Rank Fusion, repartiendo el peso semántico entre los
+/// fragmentos. Con la estrategia [`SearchStrategy::Fts`] solamente se usan las palabras clave y
+/// con [`SearchStrategy::Semantic`] solamente los fragmentos, con cualquier otra se usan ambos.
+async fn buscar_descripcion(
+    app: &AppState,
+    params: &Params,
+    mut db: tokio::sync::MutexGuard<'_, rusqlite::Connection>,
+    client: &reqwest::Client,
+    bm25: &Bm25Weights,
+    (indice, motivo_indice): (Indice, Option<String>),
+) -> eyre::Result<DisplayableContent, ReportError> {
+    let k: i64 = 1_000;
+    let rrf_k: f32 = 60.0;
+    let metrica = Metrica::from_db(&db)?;
+
+    let usar_fts = !matches!(params.strategy, SearchStrategy::Semantic);
+    let usar_vec = !matches!(params.strategy, SearchStrategy::Fts);
+
+    let palabras_clave = if usar_fts {
+        descripcion::palabras_clave(&db, &params.query)?
+    } else {
+        Vec::new()
+    };
+    let fragmentos = if usar_vec {
+        descripcion::fragmentos(&params.query)
+    } else {
+        Vec::new()
+    };
+
+    if palabras_clave.is_empty() && fragmentos.is_empty() {
+        tracing::warn!("La descripción no tiene palabras para buscar.");
+        return bad_request(
+            &db,
+            "La descripción no tiene palabras que aparezcan en los registros.".to_string(),
+        );
+    }
+
+    let mut coincidencias: HashMap<u64, Coincidencia> = HashMap::new();
+
+    if !palabras_clave.is_empty() {
+        let palabras: Vec<&str> = palabras_clave
+            .iter()
+            .map(|clave| clave.palabra.as_str())
+            .collect();
+        let query = match query::parse(&palabras.join(" OR ")) {
+            Ok(query) => query,
+            Err(err) => {
+                tracing::error!("{err}");
+                return bad_request(&db, err.to_string());
+            }
+        };
+
+        let mut statement = db
+            .prepare(&format!(
+                "select
+                    rowid,
+                    {rank} as score,
+                    {desglose} as desglose
+                from fts_tnea
+                where fts_tnea match :query
+                order by score
+                limit :k",
+                rank = bm25.rank_sql(),
+                desglose = bm25.desglose_sql(),
+            ))
+            .map_err(|err| ReportError(err.into()))?;
+
+        let rows = statement
+            .query_map(
+                rusqlite::named_params! { ":query": query.fts, ":k": k },
+                |row| {
+                    Ok((
+                        row.get::<_, u64>(0)?,
+                        row.get::<_, f32>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(|err| ReportError(err.into()))?;
+
+        let weight_fts = params.peso_fts / 100.0;
+        for (rank, (id, score, desglose)) in rows.into_iter().enumerate() {
+            let coincidencia = coincidencias.entry(id).or_default();
+            coincidencia.fts_rank = Some(rank + 1);
+            coincidencia.fts_score = Some(-score);
+            coincidencia.desglose = desglose;
+            coincidencia.combined += weight_fts / (rrf_k + (rank + 1) as f32);
+        }
+    }
+
+    if !fragmentos.is_empty() {
+        let embeddings = openai::embed_vec(
+            (0..fragmentos.len() as u64).collect(),
+            fragmentos.clone(),
+            app.dimensiones,
+            client,
+        )
+        .await?;
+
+        let mut statement = db
+            .prepare(&format!(
+                "select row_id, distance
+                from ({})
+                order by distance",
+                indice.knn_sql(":k", metrica)
+            ))
+            .map_err(|err| ReportError(err.into()))?;
+
+        // Cada fragmento aporta la misma parte del peso semántico.
+        let weight_vec = params.peso_semantic / 100.0 / embeddings.len() as f32;
+        for (_, embedding) in embeddings {
+            let rows = statement
+                .query_map(
+                    rusqlite::named_params! { ":embedding": embedding.as_bytes(), ":k": k },
+                    |row| Ok((row.get::<_, u64>(0)?, row.get::<_, f32>(1)?)),
+                )
+                .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+                .map_err(|err| ReportError(err.into()))?;
+
+            for (rank, (id, distance)) in rows.into_iter().enumerate() {
+                let coincidencia = coincidencias.entry(id).or_default();
+                // Se muestra la mejor posición y distancia entre todos los fragmentos.
+                coincidencia.vec_rank =
+                    Some(coincidencia.vec_rank.map_or(rank + 1, |r| r.min(rank + 1)));
+                coincidencia.vec_distance = Some(
+                    coincidencia
+                        .vec_distance
+                        .map_or(distance, |d| d.min(distance)),
+                );
+                coincidencia.combined += weight_vec / (rrf_k + (rank + 1) as f32);
+            }
+        }
+    }
+
+    let ids: Vec<u64> = coincidencias.keys().copied().collect();
+    let ids = serde_json::Value::from(ids).to_string();
+    let filtros = Filtros::new(&SearchQuery::default());
+
+    // La sentencia se libera antes de `postprocesar`, que la tendría viva durante el `await`.
+    let mut rows = {
+        let mut statement = match db.prepare(&format!(
+            "select
+                tnea.id,
+                tnea.template,
+                tnea.email,
+                tnea.edad,
+                tnea.sexo,
+                tnea.provincia
+            from tnea
+            where tnea.id in (select value from json_each(:ids))
+            and {FILTROS}"
+        )) {
+            Ok(stmt) => stmt,
+            Err(err) => {
+                tracing::error!("{}", err);
+                return Err(ReportError(err.into()));
+            }
+        };
+
+        let rows = match statement.query_map(
+            filtros
+                .with(rusqlite::named_params! { ":ids": ids })
+                .as_slice(),
+            |row| {
+                let id: u64 = row.get(0).unwrap_or_default();
+                let template: String = row.get(1).unwrap_or_default();
+                let email: String = row.get(2).unwrap_or_default();
+                let edad: u64 = row.get(3).unwrap_or_default();
+                let sexo: Sexo = row.get(4).unwrap_or_default();
+                let provincia: String = row.get(5).unwrap_or_default();
+
+                let coincidencia = &coincidencias[&id];
+                let data = ReRankDisplay::new(
+                    template,
+                    email,
+                    edad,
+                    sexo,
+                    coincidencia.fts_rank.unwrap_or_default() as i64,
+                    coincidencia.vec_rank.unwrap_or_default() as i64,
+                    coincidencia.combined,
+                    coincidencia.vec_distance.unwrap_or_default(),
+                    coincidencia.fts_score.unwrap_or_default(),
+                )
+                .with_desglose(coincidencia.desglose.clone())
+                .with_id(id)
+                .with_provincia(provincia);
+
+                Ok(data)
+            },
+        ) {
+            Ok(rows) => rows
+                .collect::<Result<Vec<ReRankDisplay>, _>>()
+                .unwrap_or_default(),
+            Err(err) => {
+                tracing::error!("{}", err);
+                return Err(ReportError(err.into()));
+            }
+        };
+        rows
+    };
+
+    rows.sort_by(|a, b| b.combined_rank.total_cmp(&a.combined_rank));
+
+    retener(&mut rows, params);
+
+    // Con cualquier estrategia las listas se combinan con RRF, por lo que se corta igual que en
+    // `HybridRrf`, salvo la distancia cuando solamente se usaron las palabras clave.
+    let corte = Params {
+        strategy: if usar_vec {
+            SearchStrategy::HybridRrf
+        } else {
+            SearchStrategy::Fts
+        },
+        ..params.clone()
+    };
+    let mut table = TableData::Rrf(rows);
+    let Postproceso {
+        cortes,
+        reranking,
+        diversificacion,
+        orden,
+    } = postprocesar(app, &mut db, &corte, &params.query, &mut table).await?;
+
+    let TableData::Rrf(rows) = table else {
+        unreachable!("La búsqueda por descripción devuelve una tabla RRF");
+    };
+
+    tracing::info!(
+        "Busqueda por descripción de puesto exitosa! de {} registros, con {} palabras clave y {} fragmentos",
+        rows.len(),
+        palabras_clave.len(),
+        fragmentos.len()
+    );
+
+    let mut explicacion = Vec::new();
+    if usar_fts {
+        let palabras: Vec<String> = palabras_clave.iter().map(ToString::to_string).collect();
+        explicacion.push(format!("Palabras clave (TF-IDF): {}", palabras.join(", ")));
+    }
+    for (i, fragmento) in fragmentos.iter().enumerate() {
+        explicacion.push(format!("Fragmento {}: {fragmento}", i + 1));
+    }
+    if usar_vec {
+        explicacion.push(format!("Métrica de distancia: {metrica}"));
+        explicacion.extend(motivo_indice);
+    }
+    explicacion.extend(diversificacion);
+    if orden != Orden::Relevancia {
+        explicacion.push(format!("Ordenado por {orden}."));
+    }
+
+    let mut msg = " Búsqueda por descripción de puesto.".to_string();
+    for corte in cortes.iter().chain(&reranking) {
+        msg.push_str(&format!(" {corte}"));
+    }
+
+    // Las descripciones no se guardan en el historial porque no sirven como sugerencias.
+    Ok(DisplayableContent::RrfTable(RrfTable {
+        msg: resumen(rows.len(), &msg),
+        table: rows,
+        historial: sqlite::get_historial(&db)?,
+        error: None,
+        sugerencias: Vec::new(),
+        explicacion,
+        ordenamiento: Some(params.ordenamiento()),
+    }))
+}
+
+/// Parámetros de [`similares`]. El enlace agrega los de la búsqueda desde la que se abrió, pero
+/// todos son opcionales para que también funcione sin ellos: de `query` solamente se usan los
+/// filtros, y sin edades ni sexo no se filtra por ellos.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ParamsSimilares {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    query: String,
+    #[serde(default)]
+    sexo: Sexo,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    edad_min: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    edad_max: Option<u64>,
+    rerank: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    rerank_n: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    mmr: Option<f32>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    distancia_maxima: Option<f32>,
+    codo: Option<bool>,
+    sort: Option<Orden>,
+}
+
+impl ParamsSimilares {
+    /// Los parámetros de una búsqueda semántica equivalente, para aplicar los mismos cortes,
+    /// re-ordenamiento, diversificación y orden que en [`search`].
+    fn busqueda(&self) -> Params {
+        Params {
+            query: self.query.clone(),
+            strategy: SearchStrategy::Semantic,
+            sexo: self.sexo.clone(),
+            edad_min: self.edad_min.unwrap_or(0),
+            edad_max: self.edad_max.unwrap_or(u64::MAX),
+            peso_fts: 0.0,
+            peso_semantic: 100.0,
+            bm25: None,
+            sinonimos: None,
+            normalizacion: None,
+            rerank: self.rerank,
+            rerank_n: self.rerank_n,
+            mmr: self.mmr,
+            peso_estudios: None,
+            peso_experiencia: None,
+            peso_descripcion: None,
+            descripcion: None,
+            puntaje_minimo: None,
+            distancia_maxima: self.distancia_maxima,
+            codo: self.codo,
+            sort: self.sort,
+        }
+    }
+
+    /// Enlaces de los encabezados de la tabla, a los similares del mismo perfil salvo el orden.
+    fn ordenamiento(&self, id: u64) -> Ordenamiento {
+        let params = ParamsSimilares {
+            sort: None,
+            ..self.clone()
+        };
+
+        Ordenamiento::new(
+            format!(
+                "/similares/{id}?{}",
+                serde_urlencoded::to_string(&params).unwrap_or_default()
+            ),
+            self.sort.unwrap_or_default(),
+        )
+    }
+}
+
+/// Busca los perfiles más parecidos al perfil `id`, usando el embedding de su template que ya
+/// está guardado en `vec_tnea`, por lo que no se llama a la API de embeddings. Se aplican los
+/// mismos filtros, cortes y orden que en la búsqueda semántica, pero se ignoran sus términos. El
+/// cross-encoder compara los resultados con el template del perfil.
+#[axum::debug_handler]
+#[instrument(name = "Buscando perfiles similares", skip(app))]
+pub async fn similares(
+    Path(id): Path<u64>,
+    Query(similares): Query<ParamsSimilares>,
+    State(app): State<AppState>,
+) -> eyre::Result<DisplayableContent, ReportError> {
+    let mut db = app.db.lock().await;
+    let params = similares.busqueda();
+
+    if let Some(lambda) = params.mmr {
+        if !(0.0..=1.0).contains(&lambda) {
+            tracing::warn!("El lambda de MMR `{lambda}` no es válido.");
+            return bad_request(&db, "El lambda de MMR debe estar entre 0 y 1.".to_string());
+        }
+    }
+
+    let query = match query::parse_filters(&params.query) {
+        Ok(query) => query,
+        Err(err) => {
+            tracing::warn!("La búsqueda `{}` no es válida: {err}", params.query);
+            return bad_request(&db, err.to_string());
+        }
+    };
+
+    let Some(embedding) = sqlite::get_embeddings(&db, &[id])?.remove(&id) else {
+        tracing::warn!("El perfil `{id}` no tiene un embedding en `vec_tnea`.");
+        return bad_request(
+            &db,
+            format!(
+                "El perfil {id} no existe o todavía no tiene embedding, ejecutá `sync -S vector`."
+            ),
+        );
+    };
+
+    let (email, template): (String, String) = db
+        .query_row(
+            "select email, template from tnea where id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|err| ReportError(err.into()))?;
+
+    let filtros = Filtros::new(&query);
+    let metrica = Metrica::from_db(&db)?;
+    let (indice, motivo_indice) = elegir_indice(&app, &filtros, Cuantizacion::from_db(&db)?)?;
+
+    let mut table = TableData::Standard(vecinos(
+        &db,
+        &indice.knn_sql("1000", metrica),
+        &filtros,
+        &embedding,
+        Some(id),
+        &params,
+    )?);
+
+    let Postproceso {
+        cortes,
+        reranking,
+        diversificacion,
+        orden,
+    } = postprocesar(&app, &mut db, &params, &template, &mut table).await?;
+
+    let TableData::Standard(rows) = table else {
+        unreachable!("La búsqueda de similares devuelve una tabla estándar");
+    };
+
+    tracing::info!(
+        "Busqueda de perfiles similares a `{email}` exitosa! de {} registros",
+        rows.len()
+    );
+
+    let mut msg = format!(" Perfiles similares a {email}.");
+    for corte in cortes.iter().chain(&reranking) {
+        msg.push_str(&format!(" {corte}"));
+    }
+
+    let mut explicacion = vec![
+        format!("Se usó el embedding guardado del perfil {id} ({email}), sin llamar a la API."),
+        format!("Métrica de distancia: {metrica}"),
+    ];
+    explicacion.extend(motivo_indice);
+    explicacion.extend(diversificacion);
+    if orden != Orden::Relevancia {
+        explicacion.push(format!("Ordenado por {orden}."));
+    }
+
+    Ok(DisplayableContent::Common(Table {
+        msg: resumen(rows.len(), &msg),
+        table: rows,
+        historial: sqlite::get_historial(&db)?,
+        error: None,
+        sugerencias: Vec::new(),
+        explicacion,
+        metrica: Some(metrica),
+        ordenamiento: Some(similares.ordenamiento(id)),
+    }))
+}
+
+/// Re-ordena los primeros resultados con el cross-encoder y devuelve cuántos re-ordenó y cuánto
+/// tardó, para mostrarlo junto con los resultados.
+#[cfg(feature = "local")]
+async fn rerank(
+    app: &AppState,
+    db: &mut tokio::sync::MutexGuard<'_, rusqlite::Connection>,
+    query: &str,
+    table: &mut TableData,
+    top_n: Option<usize>,
+) -> eyre::Result<String> {
+    let Some(encoder) = app.reranker.clone() else {
+        return Ok(
+            "No se re-ordenaron los resultados porque no se configuró `--reranker`.".to_string(),
+        );
+    };
+
+    let top_n = top_n.unwrap_or(encoder.top_n);
+    let start = std::time::Instant::now();
+
+    let reordenados = match table {
+        TableData::Standard(rows) => {
+            rerank_rows(encoder, db, query, rows, top_n, |row| row.id).await?
+        }
+        TableData::Rrf(rows) => rerank_rows(encoder, db, query, rows, top_n, |row| row.id).await?,
+        TableData::Convex(rows) => {
+            rerank_rows(encoder, db, query, rows, top_n, |row| row.id).await?
+        }
+    };
+
+    let latencia = start.elapsed().as_millis();
+    tracing::info!("El cross-encoder re-ordenó {reordenados} resultados en {latencia} ms.");
+
+    Ok(format!(
+        "El cross-encoder re-ordenó los primeros {reordenados} resultados en {latencia} ms."
+    ))
+}
+
+#[cfg(not(feature = "local"))]
+async fn rerank(
+    _app: &AppState,
+    _db: &mut tokio::sync::MutexGuard<'_, rusqlite::Connection>,
+    _query: &str,
+    _table: &mut TableData,
+    _top_n: Option<usize>,
+) -> eyre::Result<String> {
+    Ok(
+        "No se re-ordenaron los resultados porque el cross-encoder requiere la feature `local`."
+            .to_string(),
+    )
+}
+
+#[cfg(feature = "local")]
+async fn rerank_rows<T>(
+    encoder: std::sync::Arc<crate::reranker::CrossEncoder>,
+    db: &mut tokio::sync::MutexGuard<'_, rusqlite::Connection>,
+    query: &str,
+    rows: &mut Vec<T>,
+    top_n: usize,
+    id: fn(&T) -> u64,
+) -> eyre::Result<usize> {
+    let n = top_n.min(rows.len());
+
+    // Los templates de los resultados pueden tener el resaltado de FTS, por lo que el modelo
+    // recibe los de `tnea`.
+    let ids: Vec<u64> = rows[..n].iter().map(id).collect();
+    let mut originales = sqlite::get_templates(db, &ids)?;
+    let templates: Vec<String> = ids
+        .iter()
+        .map(|id| originales.remove(id).unwrap_or_default())
+        .collect();
+
+    // La inferencia usa la CPU, así que no se ejecuta en los hilos de tokio.
+    let query = query.to_string();
+    let scores = tokio::task::spawn_blocking(move || encoder.score(&query, &templates)).await??;
+
+    let mut top: Vec<(f32, T)> = scores.into_iter().zip(rows.drain(..n)).collect();
+    top.sort_by(|a, b| b.0.total_cmp(&a.0));
+    rows.splice(0..0, top.into_iter().map(|(_, row)| row));
+
+    Ok(n)
+}
+
+fn resumen(resultados: usize, pesos: &str) -> String {
+    if resultados == 0 {
+        format!("No se encontró ningún registro.{pesos}")
+    } else {
+        format!("Hay un total de {resultados} resultados.{pesos}")
     }
 }
@@ -0,0 +1,39 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use http::StatusCode;
+use tracing::instrument;
+
+use crate::{sinonimos::SinonimosError, startup::AppState};
+
+use super::{Admin, ReportError};
+
+/// Devuelve el diccionario de sinónimos en el mismo formato que su archivo.
+#[instrument(name = "Obteniendo los sinónimos", skip(app))]
+pub async fn get_sinonimos(_admin: Admin, State(app): State<AppState>) -> String {
+    app.sinonimos.read().await.to_string()
+}
+
+/// Reemplaza el diccionario de sinónimos por el del cuerpo de la petición, con una regla por
+/// línea, y lo guarda en su archivo.
+#[instrument(name = "Actualizando los sinónimos", skip(app, body))]
+pub async fn put_sinonimos(
+    _admin: Admin,
+    State(app): State<AppState>,
+    body: String,
+) -> eyre::Result<Response, ReportError> {
+    let mut sinonimos = app.sinonimos.write().await;
+
+    match sinonimos.replace(&body) {
+        Ok(()) => {
+            tracing::info!(
+                "Se actualizaron los sinónimos, hay {} reglas.",
+                sinonimos.len()
+            );
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        Err(SinonimosError::Invalid(err)) => Ok((StatusCode::BAD_REQUEST, err).into_response()),
+        Err(SinonimosError::Io(err)) => Err(ReportError(err)),
+    }
+}
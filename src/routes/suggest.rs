@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{fuzzy, sqlite, startup::AppState};
+
+use super::ReportError;
+
+/// Cantidad máxima de sugerencias que se devuelven.
+const MAX_SUGERENCIAS: usize = 8;
+
+#[derive(Deserialize, Debug)]
+pub struct SuggestParams {
+    q: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Completion {
+    pub texto: String,
+    /// `historial` si es una búsqueda anterior, `termino` si completa la última palabra con un
+    /// término del índice.
+    pub origen: &'static str,
+}
+
+/// Devuelve las sugerencias para completar la búsqueda `q`: primero las búsquedas anteriores
+/// que comienzan igual, ordenadas por frecuencia y por lo reciente que son, y luego la búsqueda
+/// con su última palabra completada con los términos más frecuentes de `fts_tnea`.
+#[tracing::instrument(name = "Sugiriendo búsquedas", skip(app))]
+pub async fn suggest(
+    Query(params): Query<SuggestParams>,
+    State(app): State<AppState>,
+) -> eyre::Result<Json<Vec<Completion>>, ReportError> {
+    let q = params.q.trim_start();
+    if q.trim().is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let db = app.db.lock().await;
+
+    let mut completions: Vec<Completion> = sqlite::historial_prefix(&db, q, MAX_SUGERENCIAS)?
+        .into_iter()
+        .map(|(texto, _)| Completion {
+            texto,
+            origen: "historial",
+        })
+        .collect();
+
+    // Solamente se completa la última palabra mientras se está escribiendo.
+    let ultima = q.rsplit(char::is_whitespace).next().unwrap_or_default();
+    if !q.ends_with(char::is_whitespace) && ultima.chars().all(char::is_alphanumeric) {
+        let inicio = &q[..q.len() - ultima.len()];
+
+        for (termino, _) in fuzzy::completar_termino(&db, &app.formas, ultima, MAX_SUGERENCIAS)? {
+            let texto = format!("{inicio}{termino}");
+            if !completions.iter().any(|c| c.texto == texto) {
+                completions.push(Completion {
+                    texto,
+                    origen: "termino",
+                });
+            }
+        }
+    }
+
+    completions.truncate(MAX_SUGERENCIAS);
+
+    Ok(Json(completions))
+}
@@ -0,0 +1,344 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{query::SearchQuery, stemmer};
+
+/// Factor por el que se multiplica el puntaje de `bm25` de los registros que solamente
+/// coinciden gracias a un sinónimo, para que queden debajo de los que coinciden con la búsqueda
+/// original.
+pub const PESO_SINONIMO: f32 = 0.5;
+
+/// Diccionario de sinónimos que se usa para expandir los términos de las búsquedas FTS.
+///
+/// Se carga desde un archivo de texto con una regla por línea:
+/// - `rrhh => recursos humanos, rr.hh.` es una regla en un sentido: buscar `rrhh` también busca
+///   `recursos humanos` y `rr.hh.`, pero no al revés.
+/// - `dev, desarrollador, programador` es una regla bidireccional: buscar cualquiera de los
+///   términos también busca los demás.
+///
+/// Las líneas vacías y las que comienzan con `#` se ignoran. Los términos se comparan sin
+/// distinguir mayúsculas ni diacríticos y pueden tener varias palabras, en cuyo caso se buscan
+/// como frase. Para que un término de varias palabras se expanda, la búsqueda debe escribirlo
+/// entre comillas.
+#[derive(Debug, Clone, Default)]
+pub struct Sinonimos {
+    path: PathBuf,
+    reglas: Vec<Regla>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Regla {
+    origen: Vec<String>,
+    destino: Vec<String>,
+    bidireccional: bool,
+}
+
+/// Los sinónimos agregados a un término de la búsqueda.
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub original: String,
+    pub sinonimos: Vec<String>,
+}
+
+impl Display for Expansion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} → {}", self.original, self.sinonimos.join(", "))
+    }
+}
+
+impl Sinonimos {
+    /// Carga el diccionario desde `path`. Si el archivo no existe se comienza con un diccionario
+    /// vacío, que se crea al guardar la primera regla.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si no se puede leer el archivo o alguna regla está mal formada.
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let reglas = match std::fs::read_to_string(path) {
+            Ok(content) => parse(&content).map_err(|err| {
+                eyre::eyre!(
+                    "El archivo de sinónimos `{}` no es válido. {err}",
+                    path.display()
+                )
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::warn!(
+                    "No se encontró el archivo de sinónimos `{}`, se comienza sin sinónimos.",
+                    path.display()
+                );
+                Vec::new()
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        tracing::info!("Se cargaron {} reglas de sinónimos.", reglas.len());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            reglas,
+        })
+    }
+
+    /// Reemplaza las reglas por las de `content` y las guarda en el archivo.
+    ///
+    /// # Errors
+    ///
+    /// Devuelve un error si alguna regla está mal formada, en cuyo caso se conservan las reglas
+    /// anteriores, o si no se puede escribir el archivo.
+    pub fn replace(&mut self, content: &str) -> Result<(), SinonimosError> {
+        let reglas = parse(content).map_err(SinonimosError::Invalid)?;
+
+        let sinonimos = Self {
+            path: self.path.clone(),
+            reglas,
+        };
+        std::fs::write(&self.path, sinonimos.to_string())
+            .map_err(|err| SinonimosError::Io(err.into()))?;
+
+        *self = sinonimos;
+        Ok(())
+    }
+
+    /// Agrega a los términos de `query` los sinónimos que correspondan según las reglas y
+    /// devuelve las expansiones realizadas.
+    pub fn expandir(&self, query: &mut SearchQuery) -> Vec<Expansion> {
+        let mut expansiones = Vec::new();
+
+        for term in query.terms() {
+            let normalized = normalizar(&term);
+            let mut sinonimos: Vec<String> = Vec::new();
+
+            for regla in &self.reglas {
+                if !regla
+                    .origen
+                    .iter()
+                    .any(|origen| normalizar(origen) == normalized)
+                {
+                    continue;
+                }
+
+                for destino in &regla.destino {
+                    if normalizar(destino) != normalized && !sinonimos.contains(destino) {
+                        sinonimos.push(destino.clone());
+                    }
+                }
+            }
+
+            if !sinonimos.is_empty() {
+                query.add_synonyms(&term, &sinonimos);
+                expansiones.push(Expansion {
+                    original: term,
+                    sinonimos,
+                });
+            }
+        }
+
+        expansiones
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.reglas.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.reglas.is_empty()
+    }
+}
+
+impl Display for Sinonimos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for regla in &self.reglas {
+            if regla.bidireccional {
+                writeln!(f, "{}", regla.origen.join(", "))?;
+            } else {
+                writeln!(
+                    f,
+                    "{} => {}",
+                    regla.origen.join(", "),
+                    regla.destino.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SinonimosError {
+    /// Alguna regla está mal formada.
+    Invalid(String),
+    Io(eyre::Report),
+}
+
+impl Display for SinonimosError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinonimosError::Invalid(err) => write!(f, "{err}"),
+            SinonimosError::Io(err) => write!(f, "No se pudo guardar el archivo: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SinonimosError {}
+
+/// Expresión SQL para el puntaje de FTS que multiplica `rank` por [`PESO_SINONIMO`] en los
+/// registros que no coinciden con el parámetro `:sin_sinonimos`, la búsqueda sin los sinónimos
+/// ([`SearchQuery::sin_sinonimos`]). Como `bm25` es negativo, esos registros quedan con un
+/// puntaje peor. Si el parámetro es `null` el puntaje queda igual.
+#[must_use]
+pub fn rank_sql(rank: &str) -> String {
+    format!(
+        "({rank} * case when :sin_sinonimos is null or fts_tnea.rowid in (
+            select rowid from fts_tnea where fts_tnea match :sin_sinonimos
+        ) then 1.0 else {PESO_SINONIMO} end)"
+    )
+}
+
+fn parse(content: &str) -> Result<Vec<Regla>, String> {
+    let mut reglas = Vec::new();
+
+    for (numero, linea) in content.lines().enumerate() {
+        let linea = linea.trim();
+        if linea.is_empty() || linea.starts_with('#') {
+            continue;
+        }
+
+        let terminos = |lado: &str| -> Result<Vec<String>, String> {
+            let terminos: Vec<String> = lado
+                .split(',')
+                // Las comillas son opcionales, los términos de varias palabras siempre se buscan
+                // como frase.
+                .map(|termino| termino.trim().trim_matches('"'))
+                .map(|termino| termino.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect();
+
+            if terminos
+                .iter()
+                .any(|termino| !termino.chars().any(char::is_alphanumeric))
+            {
+                return Err(format!(
+                    "Línea {}: hay un término vacío en `{linea}`.",
+                    numero + 1
+                ));
+            }
+            Ok(terminos)
+        };
+
+        let regla = match linea.split_once("=>") {
+            Some((origen, destino)) => Regla {
+                origen: terminos(origen)?,
+                destino: terminos(destino)?,
+                bidireccional: false,
+            },
+            None => {
+                let terminos = terminos(linea)?;
+                if terminos.len() < 2 {
+                    return Err(format!(
+                        "Línea {}: `{linea}` necesita al menos dos términos separados por `,` \
+                        o una regla `a => b`.",
+                        numero + 1
+                    ));
+                }
+                Regla {
+                    origen: terminos.clone(),
+                    destino: terminos,
+                    bidireccional: true,
+                }
+            }
+        };
+
+        reglas.push(regla);
+    }
+
+    Ok(reglas)
+}
+
+fn normalizar(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(stemmer::fold)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminos(terminos: &[&str]) -> Vec<String> {
+        terminos.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn reglas_en_un_sentido_y_bidireccionales() {
+        let reglas = parse(
+            "# Comentario
+
+            rrhh => \"recursos  humanos\", rr.hh.
+            dev, desarrollador , programador",
+        )
+        .unwrap();
+
+        assert_eq!(
+            reglas,
+            [
+                Regla {
+                    origen: terminos(&["rrhh"]),
+                    destino: terminos(&["recursos humanos", "rr.hh."]),
+                    bidireccional: false,
+                },
+                Regla {
+                    origen: terminos(&["dev", "desarrollador", "programador"]),
+                    destino: terminos(&["dev", "desarrollador", "programador"]),
+                    bidireccional: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reglas_invalidas() {
+        assert!(parse("rrhh").unwrap_err().starts_with("Línea 1:"));
+        assert!(parse("dev, ").unwrap_err().contains("término vacío"));
+        assert!(parse("a, b\n => recursos humanos")
+            .unwrap_err()
+            .starts_with("Línea 2:"));
+        assert!(parse("rrhh => \"\"").is_err());
+    }
+
+    #[test]
+    fn se_guardan_como_se_cargaron() {
+        let content = "rrhh, personal => recursos humanos\ndev, programador\n";
+        let sinonimos = Sinonimos {
+            path: PathBuf::new(),
+            reglas: parse(content).unwrap(),
+        };
+
+        assert_eq!(sinonimos.to_string(), content);
+    }
+
+    #[test]
+    fn expande_en_el_sentido_de_la_regla() {
+        let sinonimos = Sinonimos {
+            path: PathBuf::new(),
+            reglas: parse("RRHH => recursos humanos\ndev, programador").unwrap(),
+        };
+
+        let mut query = crate::query::parse("rrhh Programador").unwrap();
+        let expansiones = sinonimos.expandir(&mut query);
+        assert_eq!(
+            expansiones
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["rrhh → recursos humanos", "Programador → dev"]
+        );
+
+        let mut query = crate::query::parse("\"recursos humanos\"").unwrap();
+        assert!(sinonimos.expandir(&mut query).is_empty());
+        assert_eq!(query.sin_sinonimos, None);
+    }
+}
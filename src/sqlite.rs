@@ -1,16 +1,23 @@
 use std::{
+    collections::HashMap,
     sync::{Arc, Mutex},
     time::Duration,
 };
 
 use futures::StreamExt;
-use rusqlite::{ffi::sqlite3_auto_extension, Connection};
+use rusqlite::{
+    ffi::{sqlite3, sqlite3_api_routines, sqlite3_auto_extension},
+    Connection,
+};
 use sqlite_vec::sqlite3_vec_init;
 use zerocopy::IntoBytes;
 
 use crate::{
+    campos,
     cli::{self, Model},
-    configuration, openai,
+    configuration, cuantizacion, dimensiones, fts, fuzzy, hnsw,
+    metrica::Metrica,
+    openai,
     routes::ReportError,
     templates::Historial,
     utils::{self, TneaData},
@@ -19,8 +26,42 @@ use crate::{
 #[cfg(feature = "local")]
 use crate::embeddings;
 
+/// Triggers que encolan en `embedding_queue` los registros de `tnea` cuyo embedding debe
+/// generarse nuevamente, y eliminan de `vec_tnea` los registros borrados.
+const EMBEDDING_QUEUE_TRIGGERS: &str = "
+    create trigger if not exists tnea_queue_insert after insert on tnea begin
+        insert into embedding_queue(row_id) values (new.id)
+        on conflict(row_id) do update set version = version + 1;
+    end;
+
+    create trigger if not exists tnea_queue_update after update of template on tnea
+    when old.template is not new.template begin
+        insert into embedding_queue(row_id) values (new.id)
+        on conflict(row_id) do update set version = version + 1;
+    end;
+
+    create trigger if not exists tnea_queue_delete after delete on tnea begin
+        delete from embedding_queue where row_id = old.id;
+        delete from vec_tnea where row_id = old.id;
+    end;
+";
+
+/// Genera los embeddings de los registros que no tienen una entrada en `vec_tnea` o que
+/// están encolados en `embedding_queue`.
 pub async fn sync_vec_tnea(db: &Connection, model: cli::Model) -> eyre::Result<()> {
-    let mut statement = db.prepare("select id, template from tnea")?;
+    let dimensiones = dimensiones::from_db(db)?.unwrap_or(model.dimensions());
+
+    let mut statement = db.prepare(
+        "
+        select id, template
+        from tnea
+        where template is not null
+        and (
+            id in (select row_id from embedding_queue)
+            or id not in (select row_id from vec_tnea)
+        )
+        ",
+    )?;
 
     let templates: Vec<(u64, String)> = match statement.query_map([], |row| {
         let id: u64 = row.get(0)?;
@@ -34,15 +75,14 @@ pub async fn sync_vec_tnea(db: &Connection, model: cli::Model) -> eyre::Result<(
     };
 
     let inserted = Arc::new(Mutex::new(0));
-    let chunk_size = 2048;
 
     tracing::info!("Generando embeddings...");
 
     let client = reqwest::ClientBuilder::new()
-        .timeout(Duration::from_secs(5))
+        .timeout(TIMEOUT_COLA)
         .build()?;
 
-    let jh = templates.chunks(chunk_size).map(|chunk| match model {
+    let jh = templates.chunks(CHUNK_COLA).map(|chunk| match model {
         #[cfg(feature = "local")]
         cli::Model::Local => async { Err(eyre!("Local model is unimplemented")) },
         cli::Model::OpenAI => {
@@ -50,7 +90,7 @@ pub async fn sync_vec_tnea(db: &Connection, model: cli::Model) -> eyre::Result<(
             let templates: Vec<String> =
                 chunk.iter().map(|(_, template)| template.clone()).collect();
 
-            openai::embed_vec(indices, templates, &client)
+            openai::embed_vec(indices, templates, dimensiones, &client)
         }
     });
 
@@ -64,16 +104,22 @@ pub async fn sync_vec_tnea(db: &Connection, model: cli::Model) -> eyre::Result<(
         async move {
             match future.await {
                 Ok(data) => {
+                    let mut delete_statement =
+                        db.prepare("delete from vec_tnea where row_id = ?").unwrap();
                     let mut statement =
                         db.prepare("insert into vec_tnea(row_id, template_embedding) values (?,?)").unwrap();
+                    let mut dequeue_statement =
+                        db.prepare("delete from embedding_queue where row_id = ?").unwrap();
                     db.execute("BEGIN TRANSACTION", []).expect(
                         "Deberia poder ser convertido a un string compatible con C o hubo un error en SQLite",
                     );
                     for (id, embedding) in data {
                         tracing::debug!("{id} - {embedding:?}");
+                        delete_statement.execute([id]).expect("Error deleting from vec_tnea");
                         statement.execute(
                             rusqlite::params![id, embedding.as_bytes()],
                         ).expect("Error inserting into vec_tnea");
+                        dequeue_statement.execute([id]).expect("Error deleting from embedding_queue");
                         *inserted.lock().unwrap() += 1;
                     }
                     db.execute("COMMIT", []).expect(
@@ -93,17 +139,201 @@ pub async fn sync_vec_tnea(db: &Connection, model: cli::Model) -> eyre::Result<(
 
     tracing::info!("Generando embeddings... listo!");
 
+    if campos::habilitados(db)? {
+        match model {
+            #[cfg(feature = "local")]
+            cli::Model::Local => {
+                tracing::warn!(
+                    "Los embeddings por campo no están implementados con el modelo local."
+                );
+            }
+            cli::Model::OpenAI => campos::sync_vec_campos(db, dimensiones, &client).await?,
+        }
+    }
+
+    cuantizacion::sync_vec_cuantizado(db)?;
+    hnsw::sincronizar(db)?;
+
     Ok(())
 }
 
+/// Cantidad de templates que se envían en cada request de embeddings, tanto al sincronizar como
+/// en el worker de `embedding_queue`, para que cada request termine dentro de [`TIMEOUT_COLA`].
+pub const CHUNK_COLA: usize = 256;
+
+/// Tiempo máximo de cada request de embeddings de hasta [`CHUNK_COLA`] templates. Es mayor que
+/// el de las búsquedas, que envían un solo texto.
+pub const TIMEOUT_COLA: Duration = Duration::from_secs(120);
+
+/// Modelo con el que se generaron los embeddings de `vec_tnea`, registrado al sincronizar. Los
+/// embeddings nuevos deben generarse con el mismo modelo para no mezclar espacios vectoriales.
+/// Las bases de datos sincronizadas antes de que se registrara solamente pudieron usar OpenAI.
+pub fn modelo_embedding(db: &Connection) -> eyre::Result<Model> {
+    match get_metadata(db, "modelo_embedding")? {
+        Some(modelo) => Model::from_name(&modelo)
+            .ok_or_else(|| eyre::eyre!("El modelo de embeddings `{modelo}` no es conocido.")),
+        None => Ok(Model::OpenAI),
+    }
+}
+
+/// Genera los embeddings de `textos` con el modelo indicado, en el mismo orden.
+///
+/// # Errors
+///
+/// Devuelve un error si falla el request o si el modelo no permite generar embeddings fuera de
+/// la sincronización.
+pub async fn embed_textos(
+    model: Model,
+    textos: Vec<String>,
+    dimensiones: usize,
+    client: &reqwest::Client,
+) -> eyre::Result<Vec<Vec<f32>>> {
+    match model {
+        #[cfg(feature = "local")]
+        Model::Local => Err(eyre::eyre!(
+            "Los embeddings con el modelo local no están implementados, los registros quedan \
+            encolados."
+        )),
+        Model::OpenAI => {
+            let indices = (0..textos.len() as u64).collect();
+            let embeddings = openai::embed_vec(indices, textos, dimensiones, client).await?;
+            Ok(embeddings
+                .into_iter()
+                .map(|(_, embedding)| embedding)
+                .collect())
+        }
+    }
+}
+
+/// Genera los embeddings de los registros encolados en `embedding_queue` con el modelo de la
+/// sincronización, y los de cada campo si la base de datos tiene embeddings por campo, ya que los
+/// triggers de `vec_{campo}` los eliminan cuando cambia el template. El lock de la base de datos
+/// se libera mientras se espera la respuesta para no bloquear las búsquedas, por lo que los
+/// vectores de un registro solo se escriben si sigue en la cola con la misma versión, es decir, si
+/// no se eliminó ni volvió a ser encolado mientras tanto.
+///
+/// Devuelve los ids cuyos embeddings se reescribieron, para actualizar el índice HNSW.
+pub async fn drain_embedding_queue(
+    db: &tokio::sync::Mutex<Connection>,
+    model: Model,
+    dimensiones: usize,
+    client: &reqwest::Client,
+) -> eyre::Result<Vec<u64>> {
+    let mut embedded = Vec::new();
+
+    loop {
+        let (pending, con_campos) = {
+            let db = db.lock().await;
+            let mut statement = db.prepare(
+                "
+                select
+                    embedding_queue.row_id,
+                    tnea.template,
+                    embedding_queue.version,
+                    coalesce(tnea.estudios, ''),
+                    coalesce(tnea.experiencia, ''),
+                    coalesce(tnea.descripcion, '')
+                from embedding_queue
+                join tnea on tnea.id = embedding_queue.row_id
+                where tnea.template is not null
+                limit ?
+                ",
+            )?;
+            let rows: Vec<(u64, String, i64, [String; 3])> = statement
+                .query_map([CHUNK_COLA], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        [row.get(3)?, row.get(4)?, row.get(5)?],
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            (rows, campos::habilitados(&db)?)
+        };
+
+        if pending.is_empty() {
+            break;
+        }
+
+        // Cada registro aporta su template y, si corresponde, los campos que tienen texto.
+        let pending: Vec<_> = pending
+            .into_iter()
+            .map(|(id, template, version, textos)| {
+                let campos = if con_campos {
+                    campos::con_texto(textos)
+                } else {
+                    Vec::new()
+                };
+                (id, template, version, campos)
+            })
+            .collect();
+
+        let textos = pending
+            .iter()
+            .flat_map(|(_, template, _, campos)| {
+                std::iter::once(template.clone())
+                    .chain(campos.iter().map(|(_, texto)| texto.clone()))
+            })
+            .collect();
+        let mut embeddings = embed_textos(model, textos, dimensiones, client)
+            .await?
+            .into_iter();
+
+        let db = db.lock().await;
+        let tx = db.unchecked_transaction()?;
+        let mut ids = Vec::with_capacity(pending.len());
+        for (id, _, version, campos) in &pending {
+            let embedding = embeddings
+                .next()
+                .ok_or_else(|| eyre::eyre!("Faltan embeddings en la respuesta"))?;
+            let embeddings_campos = campos
+                .iter()
+                .map(|(campo, _)| {
+                    embeddings
+                        .next()
+                        .map(|embedding| (*campo, embedding))
+                        .ok_or_else(|| eyre::eyre!("Faltan embeddings en la respuesta"))
+                })
+                .collect::<eyre::Result<Vec<_>>>()?;
+
+            // Si el registro se eliminó o se volvió a encolar mientras se esperaba la respuesta,
+            // el embedding ya no corresponde a su template.
+            let dequeued = tx.execute(
+                "delete from embedding_queue where row_id = ? and version = ?",
+                rusqlite::params![id, version],
+            )?;
+            if dequeued == 0 {
+                continue;
+            }
+
+            tx.execute("delete from vec_tnea where row_id = ?", [id])?;
+            tx.execute(
+                "insert into vec_tnea(row_id, template_embedding) values (?, ?)",
+                rusqlite::params![id, embedding.as_bytes()],
+            )?;
+            if con_campos {
+                campos::reemplazar(&tx, *id, &embeddings_campos)?;
+            }
+            ids.push(*id);
+        }
+        cuantizacion::cuantizar_registros(&tx, &ids)?;
+        tx.commit()?;
+
+        embedded.extend(ids);
+    }
+
+    Ok(embedded)
+}
+
+/// Reconstruye el índice de `fts_tnea`. Los triggers sobre `tnea` lo mantienen actualizado,
+/// pero reconstruirlo durante la sincronización garantiza que nunca diverja de la tabla.
 pub fn sync_fts_tnea(db: &Connection) {
     let start = std::time::Instant::now();
-    tracing::info!("Insertando nuevos registros en fts_tnea...");
+    tracing::info!("Reconstruyendo el índice de fts_tnea...");
     db.execute_batch(
         "
-        insert into fts_tnea(rowid, email, edad, sexo, template)
-        select rowid, email, edad, sexo, template
-        from tnea;
+        insert into fts_tnea(fts_tnea) values('rebuild');
 
         insert into fts_tnea(fts_tnea) values('optimize');
         ",
@@ -112,14 +342,21 @@ pub fn sync_fts_tnea(db: &Connection) {
     .expect("Deberia poder ser convertido a un string compatible con C o hubo un error en SQLite");
 
     tracing::info!(
-        "Insertando nuevos registros en fts_tnea... listo!. tomó {} ms",
+        "Reconstruyendo el índice de fts_tnea... listo!. tomó {} ms",
         start.elapsed().as_millis()
     );
 }
 
 pub fn init_sqlite() -> eyre::Result<rusqlite::Connection> {
     unsafe {
-        sqlite3_auto_extension(Some(std::mem::transmute(sqlite3_vec_init as *const ())));
+        sqlite3_auto_extension(Some(std::mem::transmute::<
+            *const (),
+            unsafe extern "C" fn(
+                *mut sqlite3,
+                *mut *mut std::ffi::c_char,
+                *const sqlite3_api_routines,
+            ) -> std::ffi::c_int,
+        >(sqlite3_vec_init as *const ())));
     }
     let path = std::env::var("DATABASE_URL").map_err(|err| {
         eyre::eyre!(
@@ -127,9 +364,16 @@ pub fn init_sqlite() -> eyre::Result<rusqlite::Connection> {
             err
         )
     })?;
-    Ok(rusqlite::Connection::open(path)?)
+    let db = rusqlite::Connection::open(path)?;
+    fts::register_spanish_tokenizer(&db)?;
+
+    Ok(db)
 }
-pub fn setup_sqlite(db: &rusqlite::Connection, model: &Model) -> eyre::Result<()> {
+pub fn setup_sqlite(
+    db: &rusqlite::Connection,
+    model: &Model,
+    dimensiones: usize,
+) -> eyre::Result<()> {
     let (sqlite_version, vec_version): (String, String) =
         db.query_row("select sqlite_version(), vec_version()", [], |row| {
             Ok((row.get(0)?, row.get(1)?))
@@ -137,6 +381,19 @@ pub fn setup_sqlite(db: &rusqlite::Connection, model: &Model) -> eyre::Result<()
 
     tracing::debug!("sqlite_version={sqlite_version}, vec_version={vec_version}");
 
+    let tokenizer = fts::TokenizerConfig::from_db(db)?;
+    let migrated = migrate_tnea_columns(db)?;
+    migrate_historial_columns(db)?;
+
+    let metrica = Metrica::from_db(db)?;
+    let vec_tnea = format!(
+        "create virtual table if not exists vec_tnea using vec0(
+            row_id integer primary key,
+            template_embedding float[{dimensiones}] {}
+        );",
+        metrica.opcion()
+    );
+
     let statement = format!(
         "
         create table if not exists tnea_raw(
@@ -157,7 +414,14 @@ pub fn setup_sqlite(db: &rusqlite::Connection, model: &Model) -> eyre::Result<()
         create table if not exists historial(
             id integer primary key,
             query text not null unique,
-            timestamp datetime default current_timestamp
+            timestamp datetime default current_timestamp,
+            frecuencia integer not null default 1,
+            resultados integer not null default 0
+        );
+
+        create table if not exists metadata(
+            clave text primary key,
+            valor text not null
         );
 
         create table if not exists tnea(
@@ -165,33 +429,38 @@ pub fn setup_sqlite(db: &rusqlite::Connection, model: &Model) -> eyre::Result<()
             email text,
             edad integer not null,
             sexo text,
+            provincia text,
+            ciudad text,
+            descripcion text,
+            estudios text,
+            experiencia text,
             template text
         );
 
 
-        create virtual table if not exists fts_tnea using fts5(
-            email, edad, sexo, template,
-            content='tnea', content_rowid='id'
+        create table if not exists embedding_queue(
+            row_id integer primary key,
+            version integer not null default 1
         );
 
         {}
-        ",
-        match model {
-            Model::OpenAI => {
-                "create virtual table if not exists vec_tnea using vec0(
-                    row_id integer primary key,
-                    template_embedding float[1536]
-                );"
-            }
 
-            #[cfg(feature = "local")]
-            Model::Local => {
-                "create virtual table if not exists vec_tnea using vec0(
-                    row_id integer primary key,
-                    template_embedding float[512]
-                );"
-            }
-        }
+        {}
+
+        {}
+
+        {}
+
+        {}
+
+        {}
+        ",
+        fts::create_fts_tnea(&tokenizer),
+        fuzzy::CREATE_FTS_VOCAB,
+        fts::FTS_TNEA_TRIGGERS,
+        EMBEDDING_QUEUE_TRIGGERS,
+        vec_tnea,
+        campos::create_vec_campos(dimensiones, metrica)
     );
 
     db.execute_batch(&statement)
@@ -200,9 +469,172 @@ pub fn setup_sqlite(db: &rusqlite::Connection, model: &Model) -> eyre::Result<()
             "Deberia poder ser convertido a un string compatible con C o hubo un error en SQLite",
         );
 
+    registrar_modelo(db, model)?;
+    set_metadata(
+        db,
+        dimensiones::METADATA_DIMENSIONES,
+        &dimensiones.to_string(),
+    )?;
+    set_metadata(db, "fts_tokenizer", &tokenizer.to_string())?;
+
+    if migrated {
+        fts::rebuild(db)?;
+    }
+
+    Ok(())
+}
+
+/// Registra el modelo con el que se generan los embeddings. Si `vec_tnea` ya tiene vectores de
+/// otro modelo devuelve un error, ya que agregarle embeddings de este mezclaría dos espacios
+/// vectoriales.
+fn registrar_modelo(db: &Connection, model: &Model) -> eyre::Result<()> {
+    if let Some(actual) = get_metadata(db, "modelo_embedding")? {
+        let con_vectores = table_exists(db, "vec_tnea")?
+            && db.query_row("select exists(select 1 from vec_tnea)", [], |row| {
+                row.get(0)
+            })?;
+
+        if actual != model.name() && con_vectores {
+            return Err(eyre::eyre!(
+                "`vec_tnea` tiene embeddings del modelo `{actual}`, usa `sync --force` para \
+                recrearla con `{}`.",
+                model.name()
+            ));
+        }
+    }
+
+    set_metadata(db, "modelo_embedding", model.name())
+}
+
+/// Agrega a una tabla `historial` creada con una versión anterior las columnas `frecuencia` y
+/// `resultados`. Como no requiere regenerar datos, también se ejecuta al iniciar el servidor.
+pub fn migrate_historial_columns(db: &Connection) -> eyre::Result<()> {
+    if !table_exists(db, "historial")? {
+        return Ok(());
+    }
+
+    let columns = db
+        .prepare("select name from pragma_table_info('historial')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (column, definition) in [
+        ("frecuencia", "integer not null default 1"),
+        ("resultados", "integer not null default 0"),
+    ] {
+        if !columns.iter().any(|name| name == column) {
+            tracing::info!("Agregando la columna `{column}` a la tabla `historial`...");
+            db.execute(
+                &format!("alter table historial add column {column} {definition}"),
+                [],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Columnas de `tnea_raw` que se copian a `tnea` para filtrarlas o indexarlas en `fts_tnea`.
+const TNEA_COLUMNS: [&str; 5] = [
+    "provincia",
+    "ciudad",
+    "descripcion",
+    "estudios",
+    "experiencia",
+];
+
+/// Agrega a una tabla `tnea` creada con una versión anterior las columnas de [`TNEA_COLUMNS`].
+/// Como `fts_tnea` también debe indexarlas, se elimina junto con sus triggers para que
+/// `setup_sqlite` los vuelva a crear. Devuelve `true` si hubo que migrar la tabla.
+fn migrate_tnea_columns(db: &Connection) -> eyre::Result<bool> {
+    if !table_exists(db, "tnea")? {
+        return Ok(false);
+    }
+
+    let columns = db
+        .prepare("select name from pragma_table_info('tnea')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let missing: Vec<&str> = TNEA_COLUMNS
+        .into_iter()
+        .filter(|column| !columns.iter().any(|name| name == column))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(false);
+    }
+
+    tracing::info!("Agregando las columnas {missing:?} a la tabla `tnea`...");
+
+    let tx = db.unchecked_transaction()?;
+
+    tx.execute_batch(
+        "
+        drop trigger if exists tnea_fts_insert;
+        drop trigger if exists tnea_fts_delete;
+        drop trigger if exists tnea_fts_update;
+        drop table if exists fts_tnea;
+        ",
+    )?;
+
+    for column in &missing {
+        tx.execute(&format!("alter table tnea add column {column} text"), [])?;
+    }
+
+    tx.execute(
+        "
+        update tnea set
+            provincia = tnea_raw.provincia,
+            ciudad = tnea_raw.ciudad,
+            descripcion = tnea_raw.descripcion,
+            estudios = tnea_raw.estudios,
+            experiencia = tnea_raw.experiencia
+        from tnea_raw
+        where tnea_raw.id = tnea.id
+        ",
+        [],
+    )?;
+
+    tx.commit()?;
+
+    Ok(true)
+}
+
+pub fn set_metadata(db: &Connection, clave: &str, valor: &str) -> eyre::Result<()> {
+    db.execute(
+        "insert into metadata(clave, valor) values (?1, ?2)
+        on conflict(clave) do update set valor = excluded.valor",
+        [clave, valor],
+    )?;
     Ok(())
 }
 
+pub fn get_metadata(db: &Connection, clave: &str) -> eyre::Result<Option<String>> {
+    if !table_exists(db, "metadata")? {
+        return Ok(None);
+    }
+
+    match db.query_row(
+        "select valor from metadata where clave = ?",
+        [clave],
+        |row| row.get(0),
+    ) {
+        Ok(valor) => Ok(Some(valor)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(err) => Err(eyre::eyre!(err)),
+    }
+}
+
+pub fn table_exists(db: &Connection, table: &str) -> eyre::Result<bool> {
+    let exists: bool = db.query_row(
+        "select exists(select 1 from sqlite_master where type in ('table', 'view') and name = ?)",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
 pub fn insert_base_data(
     db: &rusqlite::Connection,
     template: &configuration::Template,
@@ -258,18 +690,10 @@ pub fn insert_base_data(
                 experiencia,
             } = data;
 
-            let clean_html = |str: &str| -> String {
-                if ammonia::is_html(str) {
-                    ammonia::clean(str)
-                } else {
-                    str.to_string()
-                }
-            };
-
-            let descripcion = clean_html(descripcion);
-            let estudios = clean_html(estudios);
-            let estudios_mas_recientes = clean_html(estudios_mas_recientes);
-            let experiencia = clean_html(experiencia);
+            let descripcion = utils::clean_html(descripcion);
+            let estudios = utils::clean_html(estudios);
+            let estudios_mas_recientes = utils::clean_html(estudios_mas_recientes);
+            let experiencia = utils::clean_html(experiencia);
 
             statement.execute((
                 email,
@@ -298,8 +722,29 @@ pub fn insert_base_data(
         let sql_statement = &template.template;
         let mut statement = db.prepare(&format!(
             "
-                    insert into tnea (email, edad, sexo, template)
-                    select email, edad, sexo, {sql_statement} as template
+                    insert into tnea (
+                        id,
+                        email,
+                        edad,
+                        sexo,
+                        provincia,
+                        ciudad,
+                        descripcion,
+                        estudios,
+                        experiencia,
+                        template
+                    )
+                    select
+                        id,
+                        email,
+                        edad,
+                        sexo,
+                        provincia,
+                        ciudad,
+                        descripcion,
+                        estudios,
+                        experiencia,
+                        {sql_statement} as template
                     from tnea_raw;
                     "
         ))?;
@@ -321,10 +766,241 @@ pub fn insert_base_data(
     Ok(())
 }
 
-pub fn update_historial(db: &Connection, query: &str) -> eyre::Result<(), ReportError> {
+/// Genera el template de un perfil sin escribirlo en la base de datos, evaluando la expresión
+/// del template sobre los valores dados en lugar de una fila de `tnea_raw`.
+pub fn render_template(
+    db: &Connection,
+    data: &TneaData,
+    template: &configuration::Template,
+) -> eyre::Result<String> {
+    let sql_statement = &template.template;
+    let data = clean_perfil(data);
+
+    let rendered = db.query_row(
+        &format!(
+            "
+            select {sql_statement}
+            from (
+                select
+                    :email as email,
+                    :nombre as nombre,
+                    :sexo as sexo,
+                    :fecha_nacimiento as fecha_nacimiento,
+                    :edad as edad,
+                    :provincia as provincia,
+                    :ciudad as ciudad,
+                    :descripcion as descripcion,
+                    :estudios as estudios,
+                    :experiencia as experiencia,
+                    :estudios_mas_recientes as estudios_mas_recientes
+            )
+            "
+        ),
+        perfil_params(&data).as_slice(),
+        |row| row.get(0),
+    )?;
+
+    Ok(rendered)
+}
+
+/// Textos de [`campos::CAMPOS`] que se guardan en `tnea` para el perfil, en el mismo orden.
+#[must_use]
+pub fn textos_campos(data: &TneaData) -> [String; 3] {
+    let data = clean_perfil(data);
+    [data.estudios, data.experiencia, data.descripcion]
+}
+
+fn clean_perfil(data: &TneaData) -> TneaData {
+    TneaData {
+        descripcion: utils::clean_html(&data.descripcion),
+        estudios: utils::clean_html(&data.estudios),
+        experiencia: utils::clean_html(&data.experiencia),
+        estudios_mas_recientes: utils::clean_html(&data.estudios_mas_recientes),
+        ..data.clone()
+    }
+}
+
+fn perfil_params(data: &TneaData) -> Vec<(&str, &dyn rusqlite::ToSql)> {
+    vec![
+        (":email", &data.email),
+        (":nombre", &data.nombre),
+        (":sexo", &data.sexo),
+        (":fecha_nacimiento", &data.fecha_nacimiento),
+        (":edad", &data.edad),
+        (":provincia", &data.provincia),
+        (":ciudad", &data.ciudad),
+        (":descripcion", &data.descripcion),
+        (":estudios", &data.estudios),
+        (":experiencia", &data.experiencia),
+        (":estudios_mas_recientes", &data.estudios_mas_recientes),
+    ]
+}
+
+/// Crea o actualiza un perfil en `tnea_raw` y regenera su entrada en `tnea`, `vec_tnea` y, si la
+/// base de datos tiene embeddings por campo, `vec_{campo}` en una sola transacción. Los triggers
+/// de `tnea` actualizan `fts_tnea`. Devuelve el id del perfil, o `None` si se pidió actualizar un
+/// perfil que no existe.
+pub fn upsert_perfil(
+    db: &Connection,
+    id: Option<u64>,
+    data: &TneaData,
+    template: &configuration::Template,
+    embedding: &[f32],
+    embeddings_campos: &[(&str, Vec<f32>)],
+) -> eyre::Result<Option<u64>> {
+    let data = clean_perfil(data);
+    let tx = db.unchecked_transaction()?;
+
+    let id = match id {
+        Some(id) => {
+            let mut params = perfil_params(&data);
+            params.push((":id", &id));
+            let updated = tx.execute(
+                "
+                update tnea_raw set
+                    email = :email,
+                    nombre = :nombre,
+                    sexo = :sexo,
+                    fecha_nacimiento = :fecha_nacimiento,
+                    edad = :edad,
+                    provincia = :provincia,
+                    ciudad = :ciudad,
+                    descripcion = :descripcion,
+                    estudios = :estudios,
+                    experiencia = :experiencia,
+                    estudios_mas_recientes = :estudios_mas_recientes
+                where id = :id
+                ",
+                params.as_slice(),
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            id
+        }
+        None => {
+            tx.execute(
+                "
+                insert into tnea_raw (
+                    email,
+                    nombre,
+                    sexo,
+                    fecha_nacimiento,
+                    edad,
+                    provincia,
+                    ciudad,
+                    descripcion,
+                    estudios,
+                    experiencia,
+                    estudios_mas_recientes
+                ) values (
+                    :email,
+                    :nombre,
+                    :sexo,
+                    :fecha_nacimiento,
+                    :edad,
+                    :provincia,
+                    :ciudad,
+                    :descripcion,
+                    :estudios,
+                    :experiencia,
+                    :estudios_mas_recientes
+                )
+                ",
+                perfil_params(&data).as_slice(),
+            )?;
+            tx.last_insert_rowid() as u64
+        }
+    };
+
+    let sql_statement = &template.template;
+    tx.execute(
+        &format!(
+            "
+            insert into tnea (
+                id,
+                email,
+                edad,
+                sexo,
+                provincia,
+                ciudad,
+                descripcion,
+                estudios,
+                experiencia,
+                template
+            )
+            select
+                id,
+                email,
+                edad,
+                sexo,
+                provincia,
+                ciudad,
+                descripcion,
+                estudios,
+                experiencia,
+                {sql_statement} as template
+            from tnea_raw
+            where id = ?1
+            on conflict(id) do update set
+                email = excluded.email,
+                edad = excluded.edad,
+                sexo = excluded.sexo,
+                provincia = excluded.provincia,
+                ciudad = excluded.ciudad,
+                descripcion = excluded.descripcion,
+                estudios = excluded.estudios,
+                experiencia = excluded.experiencia,
+                template = excluded.template
+            "
+        ),
+        [id],
+    )?;
+
+    // El embedding ya fue generado, por lo que no hace falta que quede encolado.
+    tx.execute("delete from vec_tnea where row_id = ?", [id])?;
+    tx.execute(
+        "insert into vec_tnea(row_id, template_embedding) values (?, ?)",
+        rusqlite::params![id, embedding.as_bytes()],
+    )?;
+    tx.execute("delete from embedding_queue where row_id = ?", [id])?;
+    if campos::habilitados(&tx)? {
+        campos::reemplazar(&tx, id, embeddings_campos)?;
+    }
+    cuantizacion::cuantizar_registros(&tx, &[id])?;
+
+    tx.commit()?;
+
+    Ok(Some(id))
+}
+
+/// Elimina un perfil de `tnea_raw` y `tnea`. Los triggers de `tnea` lo quitan de `fts_tnea` y
+/// `vec_tnea`. Devuelve `false` si el perfil no existía.
+pub fn delete_perfil(db: &Connection, id: u64) -> eyre::Result<bool> {
+    let tx = db.unchecked_transaction()?;
+
+    let deleted = tx.execute("delete from tnea_raw where id = ?", [id])?;
+    tx.execute("delete from tnea where id = ?", [id])?;
+
+    tx.commit()?;
+
+    Ok(deleted > 0)
+}
+
+/// Registra la búsqueda en el historial junto con la cantidad de resultados que obtuvo. Si ya
+/// existía, se incrementa su frecuencia y se actualiza su fecha.
+pub fn update_historial(
+    db: &Connection,
+    query: &str,
+    resultados: usize,
+) -> eyre::Result<(), ReportError> {
     match db.execute(
-        "insert or replace into historial(query) values (?)",
-        [query],
+        "insert into historial(query, resultados) values (?1, ?2)
+        on conflict(query) do update set
+            frecuencia = frecuencia + 1,
+            resultados = excluded.resultados,
+            timestamp = current_timestamp",
+        rusqlite::params![query, resultados],
     ) {
         Ok(updated) => {
             tracing::info!("{} registros fueron añadidos al historial!", updated);
@@ -367,3 +1043,378 @@ pub fn get_historial(db: &Connection) -> eyre::Result<Vec<Historial>, ReportErro
 
     Ok(rows)
 }
+
+/// Lee de `vec_tnea` los embeddings de los registros con los ids dados. Los registros que todavía
+/// no tienen embedding no aparecen en el resultado.
+pub fn get_embeddings(db: &Connection, ids: &[u64]) -> eyre::Result<HashMap<u64, Vec<f32>>> {
+    let ids = serde_json::Value::from(ids.to_vec()).to_string();
+
+    let mut statement = db.prepare(
+        "select row_id, template_embedding from vec_tnea
+        where row_id in (select value from json_each(?))",
+    )?;
+
+    let embeddings = statement
+        .query_map([ids], |row| {
+            let id: u64 = row.get(0)?;
+            let bytes: Vec<u8> = row.get(1)?;
+            let embedding = bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                .collect();
+            Ok((id, embedding))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    Ok(embeddings)
+}
+
+/// Lee de `tnea` los templates de los registros con los ids dados, sin el resaltado que agrega
+/// FTS a los resultados.
+pub fn get_templates(db: &Connection, ids: &[u64]) -> eyre::Result<HashMap<u64, String>> {
+    let ids = serde_json::Value::from(ids.to_vec()).to_string();
+
+    let mut statement = db.prepare(
+        "select id, template from tnea
+        where id in (select value from json_each(?)) and template is not null",
+    )?;
+
+    let templates = statement
+        .query_map([ids], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    Ok(templates)
+}
+
+/// Búsquedas anteriores con resultados que comienzan con `prefix`. Se ordenan por la cantidad de
+/// veces que se realizaron, dividida por los días que pasaron desde la última vez.
+pub fn historial_prefix(
+    db: &Connection,
+    prefix: &str,
+    limit: usize,
+) -> eyre::Result<Vec<(String, f64)>> {
+    let mut statement = db.prepare(
+        "select
+            query,
+            frecuencia / (1.0 + julianday('now') - julianday(timestamp)) as peso
+        from historial
+        where resultados > 0
+        and query like ?1 || '%' escape '\\'
+        order by peso desc
+        limit ?2",
+    )?;
+
+    let escaped = prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    let rows = statement
+        .query_map(rusqlite::params![escaped, limit], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+#[derive(Debug, Default)]
+pub struct DbStats {
+    pub sqlite_version: String,
+    pub vec_version: String,
+    pub modelo_embedding: Option<String>,
+    pub dimensiones: Option<usize>,
+    pub metrica: Metrica,
+    /// Cantidad de registros y tamaño en bytes de cada tabla, incluyendo sus tablas internas.
+    pub tablas: Vec<(String, Option<usize>, usize)>,
+    pub page_size: usize,
+    pub page_count: usize,
+    pub freelist_count: usize,
+    /// Recall y latencia de la búsqueda cuantizada, si se configuró una cuantización.
+    pub cuantizacion: Option<cuantizacion::Comparacion>,
+    /// Recall y latencia del índice HNSW, si se construyó uno.
+    pub hnsw: Option<hnsw::Comparacion>,
+}
+
+impl std::fmt::Display for DbStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "SQLite: {}", self.sqlite_version)?;
+        writeln!(f, "sqlite-vec: {}", self.vec_version)?;
+        writeln!(
+            f,
+            "Modelo de embeddings: {}",
+            self.modelo_embedding.as_deref().unwrap_or("desconocido")
+        )?;
+        match self.dimensiones {
+            Some(dimensiones) => writeln!(f, "Dimensiones: {dimensiones}")?,
+            None => writeln!(f, "Dimensiones: desconocidas")?,
+        }
+        writeln!(f, "Métrica de distancia: {}", self.metrica)?;
+        writeln!(f)?;
+
+        for (tabla, registros, bytes) in &self.tablas {
+            match registros {
+                Some(registros) => writeln!(
+                    f,
+                    "{tabla:<16} {registros:>10} registros {:>12.2} KiB",
+                    *bytes as f64 / 1024.0
+                )?,
+                None => writeln!(f, "{tabla:<16} {:>10}", "no existe")?,
+            }
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "Páginas: {} de {} bytes ({:.2} MiB), {} libres",
+            self.page_count,
+            self.page_size,
+            (self.page_count * self.page_size) as f64 / (1024.0 * 1024.0),
+            self.freelist_count
+        )?;
+
+        if let Some(cuantizacion) = &self.cuantizacion {
+            writeln!(f)?;
+            writeln!(f, "{cuantizacion}")?;
+        }
+
+        if let Some(hnsw) = &self.hnsw {
+            writeln!(f)?;
+            writeln!(f, "{hnsw}")?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn db_stats(db: &Connection) -> eyre::Result<DbStats> {
+    let (sqlite_version, vec_version): (String, String) =
+        db.query_row("select sqlite_version(), vec_version()", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+
+    let pragma = |name: &str| -> eyre::Result<usize> {
+        Ok(db.query_row(&format!("pragma {name}"), [], |row| row.get(0))?)
+    };
+
+    // `fts_tnea` es una tabla con contenido externo, contar sus filas devolvería las de `tnea`.
+    // La cantidad de documentos indexados se obtiene de su tabla interna `fts_tnea_docsize`.
+    let tablas = [
+        ("tnea_raw", "tnea_raw", "tnea_raw"),
+        ("tnea", "tnea", "tnea"),
+        ("fts_tnea", "fts_tnea_docsize", "fts_tnea%"),
+        ("vec_tnea", "vec_tnea", "vec_tnea%"),
+        ("vec_estudios", "vec_estudios", "vec_estudios%"),
+        ("vec_experiencia", "vec_experiencia", "vec_experiencia%"),
+        ("vec_descripcion", "vec_descripcion", "vec_descripcion%"),
+        ("vec_cuantizado", "vec_cuantizado", "vec_cuantizado%"),
+        ("embedding_queue", "embedding_queue", "embedding_queue"),
+    ]
+    .into_iter()
+    .map(|(tabla, conteo, patron)| {
+        if !table_exists(db, tabla)? {
+            return Ok((tabla.to_string(), None, 0));
+        }
+
+        let registros: usize =
+            db.query_row(&format!("select count(*) from {conteo}"), [], |row| {
+                row.get(0)
+            })?;
+        let bytes: usize = db.query_row(
+            "select coalesce(sum(pgsize), 0) from dbstat where name like ?",
+            [patron],
+            |row| row.get(0),
+        )?;
+
+        Ok((tabla.to_string(), Some(registros), bytes))
+    })
+    .collect::<eyre::Result<Vec<_>>>()?;
+
+    Ok(DbStats {
+        sqlite_version,
+        vec_version,
+        modelo_embedding: get_metadata(db, "modelo_embedding")?,
+        dimensiones: dimensiones::from_db(db)?,
+        metrica: Metrica::from_db(db)?,
+        tablas,
+        page_size: pragma("page_size")?,
+        page_count: pragma("page_count")?,
+        freelist_count: pragma("freelist_count")?,
+        cuantizacion: cuantizacion::comparar(db)?,
+        hnsw: hnsw::comparar(db, hnsw::HnswSettings::default().ef)?,
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct DbCheck {
+    pub sin_fts: Vec<u64>,
+    pub fts_huerfanos: Vec<u64>,
+    pub sin_vector: Vec<u64>,
+    /// Los vectores sin registro en `tnea` de cada tabla de vectores que existe: `vec_tnea`, las
+    /// de cada campo y `vec_cuantizado`.
+    pub vectores_huerfanos: Vec<(String, Vec<u64>)>,
+    pub templates_nulos: Vec<u64>,
+    pub emails_duplicados: Vec<(String, usize)>,
+}
+
+impl DbCheck {
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.sin_fts.is_empty()
+            && self.fts_huerfanos.is_empty()
+            && self.sin_vector.is_empty()
+            && self
+                .vectores_huerfanos
+                .iter()
+                .all(|(_, huerfanos)| huerfanos.is_empty())
+            && self.templates_nulos.is_empty()
+            && self.emails_duplicados.is_empty()
+    }
+}
+
+impl std::fmt::Display for DbCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids = |f: &mut std::fmt::Formatter<'_>, titulo: &str, ids: &[u64]| {
+            write!(f, "{titulo}: {}", ids.len())?;
+            if !ids.is_empty() {
+                let muestra: Vec<String> = ids.iter().take(10).map(ToString::to_string).collect();
+                write!(f, " (ids: {}", muestra.join(", "))?;
+                if ids.len() > 10 {
+                    write!(f, ", ...")?;
+                }
+                write!(f, ")")?;
+            }
+            writeln!(f)
+        };
+
+        ids(f, "Registros sin entrada en fts_tnea", &self.sin_fts)?;
+        ids(f, "Entradas huérfanas en fts_tnea", &self.fts_huerfanos)?;
+        ids(f, "Registros sin entrada en vec_tnea", &self.sin_vector)?;
+        for (tabla, huerfanos) in &self.vectores_huerfanos {
+            ids(f, &format!("Vectores huérfanos en {tabla}"), huerfanos)?;
+        }
+        ids(f, "Registros con template nulo", &self.templates_nulos)?;
+
+        writeln!(f, "Emails duplicados: {}", self.emails_duplicados.len())?;
+        for (email, cantidad) in self.emails_duplicados.iter().take(10) {
+            writeln!(f, "    {email} ({cantidad} veces)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Las tablas de vectores que existen, cuyas filas deben corresponder a un registro de `tnea`.
+fn tablas_vectores(db: &Connection) -> eyre::Result<Vec<String>> {
+    let tablas = std::iter::once("vec_tnea".to_string())
+        .chain(campos::CAMPOS.map(|campo| format!("vec_{campo}")))
+        .chain(std::iter::once("vec_cuantizado".to_string()));
+
+    let mut existentes = Vec::new();
+    for tabla in tablas {
+        if table_exists(db, &tabla)? {
+            existentes.push(tabla);
+        }
+    }
+
+    Ok(existentes)
+}
+
+pub fn db_check(db: &Connection) -> eyre::Result<DbCheck> {
+    let ids = |sql: &str| -> eyre::Result<Vec<u64>> {
+        let mut statement = db.prepare(sql)?;
+        let rows = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<u64>, _>>()?;
+        Ok(rows)
+    };
+
+    let mut statement = db.prepare(
+        "select email, count(*) from tnea_raw
+        group by email
+        having count(*) > 1
+        order by count(*) desc",
+    )?;
+    let emails_duplicados = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, usize)>, _>>()?;
+
+    let mut vectores_huerfanos = Vec::new();
+    for tabla in tablas_vectores(db)? {
+        let huerfanos = ids(&format!(
+            "select row_id from {tabla} where row_id not in (select id from tnea)"
+        ))?;
+        vectores_huerfanos.push((tabla, huerfanos));
+    }
+
+    Ok(DbCheck {
+        sin_fts: ids("select id from tnea where id not in (select id from fts_tnea_docsize)")?,
+        fts_huerfanos: ids(
+            "select id from fts_tnea_docsize where id not in (select id from tnea)",
+        )?,
+        sin_vector: ids("select id from tnea where id not in (select row_id from vec_tnea)")?,
+        vectores_huerfanos,
+        templates_nulos: ids("select id from tnea where template is null")?,
+        emails_duplicados,
+    })
+}
+
+/// Repara las inconsistencias encontradas por `db_check`. Los emails duplicados solo se reportan,
+/// ya que no es posible decidir automáticamente cuál de los registros conservar.
+pub async fn db_repair(
+    db: &Connection,
+    check: &DbCheck,
+    template: &configuration::Template,
+    model: cli::Model,
+) -> eyre::Result<()> {
+    if !check.templates_nulos.is_empty() {
+        let sql_statement = &template.template;
+        let updated = db.execute(
+            &format!(
+                "
+                update tnea
+                set template = (
+                    select {sql_statement} from tnea_raw where tnea_raw.id = tnea.id
+                )
+                where template is null
+                "
+            ),
+            [],
+        )?;
+        tracing::info!("Se regeneraron {updated} templates nulos.");
+    }
+
+    if !check.sin_fts.is_empty()
+        || !check.fts_huerfanos.is_empty()
+        || !check.templates_nulos.is_empty()
+    {
+        db.execute("insert into fts_tnea(fts_tnea) values('rebuild')", [])?;
+        tracing::info!("Se reconstruyó el índice de fts_tnea.");
+    }
+
+    for (tabla, huerfanos) in &check.vectores_huerfanos {
+        if huerfanos.is_empty() {
+            continue;
+        }
+
+        let deleted = db.execute(
+            &format!("delete from {tabla} where row_id not in (select id from tnea)"),
+            [],
+        )?;
+        tracing::info!("Se eliminaron {deleted} vectores huérfanos de {tabla}.");
+    }
+
+    if !check.sin_vector.is_empty() {
+        registrar_modelo(db, &model)?;
+        sync_vec_tnea(db, model).await?;
+    }
+
+    if !check.emails_duplicados.is_empty() {
+        tracing::warn!(
+            "Hay {} emails duplicados en tnea_raw, deben ser corregidos manualmente.",
+            check.emails_duplicados.len()
+        );
+    }
+
+    Ok(())
+}
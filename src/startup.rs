@@ -4,23 +4,50 @@ use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use axum::{body::Body, http::Request, routing::get, serve::Serve, Router};
+use axum::{
+    body::Body,
+    http::Request,
+    routing::{get, post, put},
+    serve::Serve,
+    Router,
+};
 use tokio::signal;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 use tower::ServiceBuilder;
 use tower_http::trace::{DefaultOnResponse, TraceLayer};
 use tower_request_id::{RequestId, RequestIdLayer};
 use tracing::{error_span, instrument, Level};
 
-use crate::cli::Cache;
+use crate::cli::{Cache, Model};
 use crate::configuration::{self, ApplicationSettings};
+use crate::dimensiones;
+use crate::fts::Bm25Weights;
+use crate::fuzzy;
+use crate::hnsw::{self, Hnsw};
+use crate::metrica;
 use crate::routes;
-use crate::sqlite::init_sqlite;
+use crate::sinonimos::Sinonimos;
+use crate::sqlite::{self, init_sqlite};
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub db: Arc<Mutex<rusqlite::Connection>>,
     pub cache: Cache,
+    pub template: Arc<configuration::Template>,
+    pub bm25: Bm25Weights,
+    pub sinonimos: Arc<RwLock<Sinonimos>>,
+    /// Formas originales de los términos que sugiere el autocompletado.
+    pub formas: Arc<fuzzy::Formas>,
+    /// Modelo con el que se sincronizó `vec_tnea`, con el que se generan los embeddings nuevos.
+    pub modelo: Model,
+    /// Dimensión de los embeddings de `vec_tnea`, con la que se generan los de las búsquedas.
+    pub dimensiones: usize,
+    /// Índice HNSW de `vec_tnea`, si se sincronizó con `--hnsw`. Se consulta desde SQL con la
+    /// función `hnsw_knn`.
+    pub hnsw: Option<Arc<std::sync::RwLock<Hnsw>>>,
+    /// Cross-encoder para re-ordenar los primeros resultados, si se configuró uno.
+    #[cfg(feature = "local")]
+    pub reranker: Option<Arc<crate::reranker::CrossEncoder>>,
 }
 
 #[derive(Debug)]
@@ -63,10 +90,58 @@ impl Application {
 
         let host = configuration.host;
 
-        let db = Arc::new(Mutex::new(init_sqlite()?));
+        let db = init_sqlite()?;
+        sqlite::migrate_historial_columns(&db)?;
+        db.execute_batch(fuzzy::CREATE_FTS_VOCAB)?;
+        let dimensiones = dimensiones::verificar(&db)?;
+        let metrica = metrica::verificar(&db)?;
+        let modelo = sqlite::modelo_embedding(&db)?;
+        tracing::info!(
+            "Los embeddings tienen {dimensiones} dimensiones y se comparan con la métrica `{metrica}`."
+        );
+
+        let hnsw = match hnsw::cargar(&db, &configuration.hnsw)? {
+            Some(indice) => {
+                let indice = Arc::new(std::sync::RwLock::new(indice));
+                hnsw::registrar(&db, Arc::clone(&indice))?;
+                Some(indice)
+            }
+            None => None,
+        };
+
+        let db = Arc::new(Mutex::new(db));
         let cache = configuration.cache;
 
-        let state = AppState { db, cache };
+        spawn_embedding_worker(
+            Arc::clone(&db),
+            hnsw.clone(),
+            modelo,
+            dimensiones,
+            configuration.queue_interval,
+        )?;
+
+        let template = Arc::new(configuration.template);
+        let sinonimos = Arc::new(RwLock::new(Sinonimos::load(&configuration.sinonimos)?));
+
+        #[cfg(feature = "local")]
+        let reranker = match &configuration.reranker {
+            Some(settings) => Some(Arc::new(crate::reranker::CrossEncoder::load(settings)?)),
+            None => None,
+        };
+
+        let state = AppState {
+            db,
+            cache,
+            template,
+            bm25: configuration.bm25,
+            sinonimos,
+            formas: Arc::default(),
+            modelo,
+            dimensiones,
+            hnsw,
+            #[cfg(feature = "local")]
+            reranker,
+        };
 
         let server = build_server(listener, state)?;
 
@@ -119,6 +194,65 @@ impl Application {
     }
 }
 
+/// Genera periódicamente los embeddings de los registros encolados en `embedding_queue`, para
+/// que `vec_tnea` no quede desactualizada respecto a `tnea`, actualiza en el índice HNSW los
+/// vectores reescritos y guarda el índice si cambió.
+pub fn spawn_embedding_worker(
+    db: Arc<Mutex<rusqlite::Connection>>,
+    hnsw: Option<Arc<std::sync::RwLock<Hnsw>>>,
+    modelo: Model,
+    dimensiones: usize,
+    interval: Duration,
+) -> eyre::Result<()> {
+    let client = reqwest::ClientBuilder::new()
+        .timeout(sqlite::TIMEOUT_COLA)
+        .build()?;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let ids = match sqlite::drain_embedding_queue(&db, modelo, dimensiones, &client).await {
+                Ok(ids) => ids,
+                Err(err) => {
+                    tracing::error!("Fallo al generar los embeddings encolados: {err}");
+                    Vec::new()
+                }
+            };
+            if !ids.is_empty() {
+                tracing::info!(
+                    "Se generaron {} embeddings encolados en vec_tnea.",
+                    ids.len()
+                );
+            }
+
+            let Some(hnsw) = &hnsw else {
+                continue;
+            };
+
+            if !ids.is_empty() {
+                match hnsw::actualizar(&*db.lock().await, hnsw, &ids) {
+                    Ok(actualizados) => {
+                        tracing::info!("Se actualizaron {actualizados} vectores del índice HNSW.");
+                    }
+                    Err(err) => tracing::error!("Fallo al actualizar el índice HNSW: {err}"),
+                }
+            }
+
+            // También guarda los cambios de los perfiles creados, modificados o eliminados.
+            let indice = Arc::clone(hnsw);
+            match tokio::task::spawn_blocking(move || hnsw::persistir(&indice)).await {
+                Ok(Ok(true)) => tracing::info!("Se guardó el índice HNSW."),
+                Ok(Ok(false)) => {}
+                Ok(Err(err)) => tracing::error!("Fallo al guardar el índice HNSW: {err}"),
+                Err(err) => tracing::error!("Fallo al guardar el índice HNSW: {err}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
 pub fn build_server(
     listener: tokio::net::TcpListener,
     state: AppState,
@@ -127,7 +261,18 @@ pub fn build_server(
         .route("/", get(routes::index))
         .route("/health", get(routes::health_check))
         .route("/search", get(routes::search))
+        .route("/suggest", get(routes::suggest))
+        .route("/similares/:id", get(routes::similares))
         .route("/historial", get(routes::get_from_db))
+        .route("/perfiles", post(routes::create_perfil))
+        .route(
+            "/perfiles/:id",
+            put(routes::update_perfil).delete(routes::delete_perfil),
+        )
+        .route(
+            "/sinonimos",
+            get(routes::get_sinonimos).put(routes::put_sinonimos),
+        )
         .route("/_assets/*path", get(routes::handle_assets))
         .fallback_service(routes::fallback.into_service())
         .with_state(state)
@@ -0,0 +1,436 @@
+// Implementación del stemmer de Snowball para el español:
+// https://snowballstem.org/algorithms/spanish/stemmer.html
+//
+// A diferencia del algoritmo original, las palabras se normalizan quitando los acentos antes de
+// aplicar los sufijos. Así "ingenieria" e "ingeniería" producen la misma raíz, que es lo que
+// necesitamos para que las búsquedas sin acentos encuentren los mismos registros.
+//
+// Como consecuencia, los sufijos que el original solamente elimina con acento también se eliminan
+// sin él, y las raíces de algunas palabras son más cortas que las de referencia:
+// - Los de los verbos: "ía", "ío", "ás", "ís", "erá", "aré", etc. Por ejemplo "bestia" → "best"
+//   (original "besti"), "medio" → "med" ("medi") y "manera" → "man" ("maner").
+// - El residual "í": "taxi" → "tax" ("taxi").
+// - Los sufijos estándar seguidos de una vocal con acento: "santificó" → "santif" ("santific").
+// Los tests comparan con las raíces del stemmer de referencia y enumeran estas diferencias.
+
+const PRONOUNS: &[&str] = &[
+    "me", "se", "sela", "selo", "selas", "selos", "la", "le", "lo", "las", "les", "los", "nos",
+];
+
+const PRONOUN_VERB_ENDINGS: &[&str] = &["iendo", "ando", "ar", "er", "ir", "yendo"];
+
+enum Standard {
+    /// Se elimina si está en R2.
+    Delete,
+    /// Se elimina si está en R2, y luego `ic` si también lo está.
+    DeleteIc,
+    /// Se reemplaza por el valor dado si está en R2.
+    Replace(&'static str),
+    Amente,
+    Mente,
+    Idad,
+    Iva,
+}
+
+const STANDARD_SUFFIXES: &[(&str, Standard)] = &[
+    ("anza", Standard::Delete),
+    ("anzas", Standard::Delete),
+    ("ico", Standard::Delete),
+    ("ica", Standard::Delete),
+    ("icos", Standard::Delete),
+    ("icas", Standard::Delete),
+    ("ismo", Standard::Delete),
+    ("ismos", Standard::Delete),
+    ("able", Standard::Delete),
+    ("ables", Standard::Delete),
+    ("ible", Standard::Delete),
+    ("ibles", Standard::Delete),
+    ("ista", Standard::Delete),
+    ("istas", Standard::Delete),
+    ("oso", Standard::Delete),
+    ("osa", Standard::Delete),
+    ("osos", Standard::Delete),
+    ("osas", Standard::Delete),
+    ("amiento", Standard::Delete),
+    ("amientos", Standard::Delete),
+    ("imiento", Standard::Delete),
+    ("imientos", Standard::Delete),
+    ("adora", Standard::DeleteIc),
+    ("ador", Standard::DeleteIc),
+    ("acion", Standard::DeleteIc),
+    ("adoras", Standard::DeleteIc),
+    ("adores", Standard::DeleteIc),
+    ("aciones", Standard::DeleteIc),
+    ("ante", Standard::DeleteIc),
+    ("antes", Standard::DeleteIc),
+    ("ancia", Standard::DeleteIc),
+    ("ancias", Standard::DeleteIc),
+    ("logia", Standard::Replace("log")),
+    ("logias", Standard::Replace("log")),
+    ("ucion", Standard::Replace("u")),
+    ("uciones", Standard::Replace("u")),
+    ("encia", Standard::Replace("ente")),
+    ("encias", Standard::Replace("ente")),
+    ("amente", Standard::Amente),
+    ("mente", Standard::Mente),
+    ("idad", Standard::Idad),
+    ("idades", Standard::Idad),
+    ("iva", Standard::Iva),
+    ("ivo", Standard::Iva),
+    ("ivas", Standard::Iva),
+    ("ivos", Standard::Iva),
+];
+
+const Y_VERB_SUFFIXES: &[&str] = &[
+    "ya", "ye", "yan", "yen", "yeron", "yendo", "yo", "yas", "yes", "yais", "yamos",
+];
+
+/// Sufijos que se eliminan junto con la `u` de un `gu` anterior.
+const GU_VERB_SUFFIXES: &[&str] = &["en", "es", "eis", "emos"];
+
+const VERB_SUFFIXES: &[&str] = &[
+    "arian", "arias", "aran", "aras", "ariais", "aria", "areis", "ariamos", "aremos", "ara", "are",
+    "erian", "erias", "eran", "eras", "eriais", "eria", "ereis", "eriamos", "eremos", "era", "ere",
+    "irian", "irias", "iran", "iras", "iriais", "iria", "ireis", "iriamos", "iremos", "ira", "ire",
+    "aba", "ada", "ida", "ia", "iera", "ad", "ed", "id", "ase", "iese", "aste", "iste", "an",
+    "aban", "ian", "ieran", "asen", "iesen", "aron", "ieron", "ado", "ido", "ando", "iendo", "io",
+    "ar", "er", "ir", "as", "abas", "adas", "idas", "ias", "ieras", "ases", "ieses", "is", "ais",
+    "abais", "iais", "arais", "ierais", "aseis", "ieseis", "asteis", "isteis", "ados", "idos",
+    "amos", "abamos", "iamos", "imos", "aramos", "ieramos", "iesemos", "asemos",
+];
+
+const RESIDUAL_SUFFIXES: &[&str] = &["os", "a", "o", "i", "e"];
+
+/// Devuelve la raíz de una palabra en español, sin acentos y en minúsculas.
+#[must_use]
+pub fn stem(word: &str) -> String {
+    let mut word: Vec<char> = word
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(fold)
+        .collect();
+
+    let (rv, r1, r2) = regions(&word);
+
+    attached_pronoun(&mut word, rv);
+
+    if !standard_suffix(&mut word, r1, r2) && !y_verb_suffix(&mut word, rv) {
+        verb_suffix(&mut word, rv);
+    }
+
+    residual_suffix(&mut word, rv);
+
+    word.into_iter().collect()
+}
+
+/// Quita los acentos y diacríticos de un caracter, de la misma forma que lo hace el tokenizer
+/// `unicode61` con `remove_diacritics`.
+#[must_use]
+pub fn fold(c: char) -> char {
+    match c {
+        'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'í' | 'ì' | 'î' | 'ï' => 'i',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'ñ' => 'n',
+        'ç' => 'c',
+        c => c,
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+/// Calcula el inicio de las regiones RV, R1 y R2 de la palabra.
+fn regions(word: &[char]) -> (usize, usize, usize) {
+    let len = word.len();
+
+    let rv = if len < 2 {
+        len
+    } else if !is_vowel(word[1]) {
+        word[2..]
+            .iter()
+            .position(|c| is_vowel(*c))
+            .map_or(len, |i| i + 3)
+    } else if is_vowel(word[0]) {
+        word[2..]
+            .iter()
+            .position(|c| !is_vowel(*c))
+            .map_or(len, |i| i + 3)
+    } else {
+        3.min(len)
+    };
+
+    let region_after = |start: usize| -> usize {
+        let Some(vowel) = word[start..].iter().position(|c| is_vowel(*c)) else {
+            return len;
+        };
+        let start = start + vowel + 1;
+        word[start..]
+            .iter()
+            .position(|c| !is_vowel(*c))
+            .map_or(len, |i| start + i + 1)
+    };
+
+    let r1 = region_after(0);
+    let r2 = region_after(r1);
+
+    (rv, r1, r2)
+}
+
+fn ends_with(word: &[char], suffix: &str) -> bool {
+    let len = suffix.chars().count();
+    word.len() >= len && word[word.len() - len..].iter().copied().eq(suffix.chars())
+}
+
+/// Busca el sufijo más largo de la lista que comience a partir de `limit`.
+fn longest<'a>(word: &[char], suffixes: &[&'a str], limit: usize) -> Option<&'a str> {
+    suffixes
+        .iter()
+        .filter(|suffix| ends_with(word, suffix) && word.len() - suffix.len() >= limit)
+        .max_by_key(|suffix| suffix.len())
+        .copied()
+}
+
+fn attached_pronoun(word: &mut Vec<char>, rv: usize) {
+    let Some(pronoun) = longest(word, PRONOUNS, 0) else {
+        return;
+    };
+    let stem = word.len() - pronoun.len();
+
+    let Some(ending) = longest(&word[..stem], PRONOUN_VERB_ENDINGS, 0) else {
+        return;
+    };
+    let ending_start = stem - ending.len();
+
+    if ending_start < rv {
+        return;
+    }
+
+    if ending == "yendo" && (ending_start == 0 || word[ending_start - 1] != 'u') {
+        return;
+    }
+
+    word.truncate(stem);
+}
+
+/// Elimina el sufijo `suffix` si la palabra termina con él y comienza a partir de `limit`.
+fn delete_if(word: &mut Vec<char>, suffix: &str, limit: usize) -> bool {
+    if ends_with(word, suffix) && word.len() - suffix.len() >= limit {
+        word.truncate(word.len() - suffix.len());
+        true
+    } else {
+        false
+    }
+}
+
+fn standard_suffix(word: &mut Vec<char>, r1: usize, r2: usize) -> bool {
+    let Some((suffix, action)) = STANDARD_SUFFIXES
+        .iter()
+        .filter(|(suffix, _)| ends_with(word, suffix))
+        .max_by_key(|(suffix, _)| suffix.len())
+    else {
+        return false;
+    };
+
+    let start = word.len() - suffix.len();
+
+    match action {
+        Standard::Amente => {
+            if start < r1 {
+                return false;
+            }
+            word.truncate(start);
+
+            if delete_if(word, "iv", r2) {
+                delete_if(word, "at", r2);
+            } else if let Some(suffix) = longest(word, &["os", "ic", "ad"], 0) {
+                delete_if(word, suffix, r2);
+            }
+            return true;
+        }
+        _ if start < r2 => return false,
+        Standard::Delete => word.truncate(start),
+        Standard::DeleteIc => {
+            word.truncate(start);
+            delete_if(word, "ic", r2);
+        }
+        Standard::Replace(replacement) => {
+            word.truncate(start);
+            word.extend(replacement.chars());
+        }
+        Standard::Mente => {
+            word.truncate(start);
+            if let Some(suffix) = longest(word, &["ante", "able", "ible"], 0) {
+                delete_if(word, suffix, r2);
+            }
+        }
+        Standard::Idad => {
+            word.truncate(start);
+            if let Some(suffix) = longest(word, &["abil", "ic", "iv"], 0) {
+                delete_if(word, suffix, r2);
+            }
+        }
+        Standard::Iva => {
+            word.truncate(start);
+            delete_if(word, "at", r2);
+        }
+    }
+
+    true
+}
+
+fn y_verb_suffix(word: &mut Vec<char>, rv: usize) -> bool {
+    let Some(suffix) = longest(word, Y_VERB_SUFFIXES, rv) else {
+        return false;
+    };
+    let start = word.len() - suffix.len();
+
+    if start == 0 || word[start - 1] != 'u' {
+        return false;
+    }
+
+    word.truncate(start);
+    true
+}
+
+fn verb_suffix(word: &mut Vec<char>, rv: usize) {
+    let Some(suffix) = longest(word, GU_VERB_SUFFIXES, rv)
+        .into_iter()
+        .chain(longest(word, VERB_SUFFIXES, rv))
+        .max_by_key(|suffix| suffix.len())
+    else {
+        return;
+    };
+
+    word.truncate(word.len() - suffix.len());
+
+    if GU_VERB_SUFFIXES.contains(&suffix) && ends_with(word, "gu") {
+        word.pop();
+    }
+}
+
+fn residual_suffix(word: &mut Vec<char>, rv: usize) {
+    let Some(suffix) = longest(word, RESIDUAL_SUFFIXES, 0) else {
+        return;
+    };
+
+    if !delete_if(word, suffix, rv) {
+        return;
+    }
+
+    if suffix == "e" && ends_with(word, "gu") && word.len() > rv {
+        word.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Palabras del vocabulario de ejemplo de Snowball y otras frecuentes en los perfiles, con la
+    /// raíz que devuelve el stemmer de referencia, sin acentos.
+    const REFERENCIA: &[(&str, &str)] = &[
+        ("chica", "chic"),
+        ("chicharrón", "chicharron"),
+        ("chicharrones", "chicharron"),
+        ("chicle", "chicl"),
+        ("chicos", "chic"),
+        ("chichimecas", "chichimec"),
+        ("chiflados", "chifl"),
+        ("chihuahua", "chihuahu"),
+        ("chilangos", "chilang"),
+        ("chilena", "chilen"),
+        ("chiles", "chil"),
+        ("chilpancingo", "chilpancing"),
+        ("chimenea", "chimene"),
+        ("chimeneas", "chimen"),
+        ("chipre", "chipr"),
+        ("chiquita", "chiquit"),
+        ("torá", "tor"),
+        ("tórax", "torax"),
+        ("torcer", "torc"),
+        ("toreados", "tor"),
+        ("toreándolo", "tor"),
+        ("toreara", "tor"),
+        ("torearlo", "tor"),
+        ("toreo", "tore"),
+        ("toreros", "torer"),
+        ("tormentas", "torment"),
+        ("tornar", "torn"),
+        ("torpe", "torp"),
+        ("torrencial", "torrencial"),
+        ("torres", "torr"),
+        ("torrente", "torrent"),
+        ("contadora", "contador"),
+        ("contadores", "contador"),
+        ("contabilidad", "contabil"),
+        ("administración", "administr"),
+        ("administrativo", "administr"),
+        ("programación", "program"),
+        ("desarrolladores", "desarroll"),
+        ("gestión", "gestion"),
+        ("auditoría", "auditor"),
+        ("liquidación", "liquid"),
+        ("enfermería", "enferm"),
+        ("informática", "informat"),
+        ("analista", "anal"),
+        ("haciéndola", "hac"),
+        ("diciéndole", "dic"),
+        ("arguyendo", "argu"),
+        ("cantándoselo", "cant"),
+        ("averiguemos", "averig"),
+        ("huyendo", "huyend"),
+        ("rápidamente", "rapid"),
+        ("felizmente", "feliz"),
+        ("posibilidades", "posibil"),
+        ("creativa", "creativ"),
+        ("organizaciones", "organiz"),
+        ("responsabilidad", "respons"),
+    ];
+
+    /// Palabras cuya raíz difiere de la de referencia por quitar los acentos antes de los
+    /// sufijos: la palabra, la raíz de referencia y la de este stemmer.
+    const DIFERENCIAS: &[(&str, &str, &str)] = &[
+        ("bestia", "besti", "best"),
+        ("medio", "medi", "med"),
+        ("manera", "maner", "man"),
+        ("podrás", "podras", "podr"),
+        ("análisis", "analisis", "analis"),
+        ("separe", "separ", "sep"),
+        ("ingeniería", "ingeni", "ingen"),
+        ("taxi", "taxi", "tax"),
+        ("santificó", "santific", "santif"),
+    ];
+
+    #[test]
+    fn coincide_con_snowball() {
+        for (palabra, raiz) in REFERENCIA {
+            assert_eq!(stem(palabra), *raiz, "raíz de `{palabra}`");
+        }
+    }
+
+    #[test]
+    fn diferencias_con_snowball() {
+        for (palabra, referencia, raiz) in DIFERENCIAS {
+            assert_eq!(stem(palabra), *raiz, "raíz de `{palabra}`");
+            assert_ne!(referencia, raiz);
+        }
+    }
+
+    #[test]
+    fn ignora_acentos_y_mayusculas() {
+        assert_eq!(stem("ingeniería"), stem("ingenieria"));
+        assert_eq!(stem("Gestión"), stem("gestion"));
+        assert_eq!(stem("CONTADORA"), "contador");
+    }
+
+    #[test]
+    fn palabras_cortas() {
+        assert_eq!(stem(""), "");
+        assert_eq!(stem("a"), "a");
+        assert_eq!(stem("de"), "de");
+    }
+}
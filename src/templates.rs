@@ -1,12 +1,21 @@
 use std::fmt::Display;
 
 use askama_axum::{IntoResponse, Template};
+use http::StatusCode;
 use rusqlite::types::{FromSql, FromSqlError, ValueRef};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    metrica::Metrica,
+    orden::{Ordenable, Ordenamiento},
+};
 
 pub enum DisplayableContent {
     Common(Table),
     RrfTable(RrfTable),
+    ConvexTable(ConvexTable),
+    /// La búsqueda no es válida, se muestra el formulario con el error.
+    BadRequest(Table),
 }
 
 impl IntoResponse for DisplayableContent {
@@ -14,6 +23,10 @@ impl IntoResponse for DisplayableContent {
         match self {
             DisplayableContent::Common(table) => table.into_response(),
             DisplayableContent::RrfTable(rrf_table) => rrf_table.into_response(),
+            DisplayableContent::ConvexTable(convex_table) => convex_table.into_response(),
+            DisplayableContent::BadRequest(table) => {
+                (StatusCode::BAD_REQUEST, table).into_response()
+            }
         }
     }
 }
@@ -30,14 +43,36 @@ pub struct Table {
     pub msg: String,
     pub table: Vec<TneaDisplay>,
     pub historial: Vec<Historial>,
+    pub error: Option<String>,
+    /// Búsquedas alternativas que se ofrecen cuando no hubo resultados.
+    pub sugerencias: Vec<Sugerencia>,
+    /// Cómo se interpretó la búsqueda: la expresión FTS, los sinónimos y las variantes.
+    pub explicacion: Vec<String>,
+    /// Métrica de `vec_distance`, si la búsqueda usó embeddings.
+    pub metrica: Option<Metrica>,
+    /// Enlaces para ordenar por cada columna, si la tabla es el resultado de `/search`.
+    pub ordenamiento: Option<Ordenamiento>,
+}
+
+impl Table {
+    /// Si se muestran por separado el puntaje de FTS y la distancia semántica de cada resultado.
+    #[must_use]
+    pub fn puntajes_separados(&self) -> bool {
+        self.table.iter().any(TneaDisplay::tiene_puntajes)
+    }
 }
 
 impl Default for Table {
     fn default() -> Self {
         Self {
-            msg: "No se encontraron ningun registro.".to_string(),
+            msg: "No se encontró ningún registro.".to_string(),
             table: vec![TneaDisplay::default()],
             historial: vec![Historial::default()],
+            error: None,
+            sugerencias: Vec::new(),
+            explicacion: Vec::new(),
+            metrica: None,
+            ordenamiento: None,
         }
     }
 }
@@ -48,31 +83,65 @@ pub struct RrfTable {
     pub msg: String,
     pub table: Vec<ReRankDisplay>,
     pub historial: Vec<Historial>,
+    pub error: Option<String>,
+    /// Búsquedas alternativas que se ofrecen cuando no hubo resultados.
+    pub sugerencias: Vec<Sugerencia>,
+    /// Cómo se interpretó la búsqueda: la expresión FTS, los sinónimos y las variantes.
+    pub explicacion: Vec<String>,
+    /// Enlaces para ordenar por cada columna, si la tabla es el resultado de `/search`.
+    pub ordenamiento: Option<Ordenamiento>,
 }
 
 impl Default for RrfTable {
     fn default() -> Self {
         Self {
-            msg: "No se encontraron ningun registro.".to_string(),
+            msg: "No se encontró ningún registro.".to_string(),
             table: vec![ReRankDisplay::default()],
             historial: vec![Historial::default()],
+            error: None,
+            sugerencias: Vec::new(),
+            explicacion: Vec::new(),
+            ordenamiento: None,
         }
     }
 }
 
+#[derive(Template)]
+#[template(path = "table_convex.html")]
+pub struct ConvexTable {
+    pub msg: String,
+    pub table: Vec<ConvexDisplay>,
+    pub historial: Vec<Historial>,
+    pub error: Option<String>,
+    pub sugerencias: Vec<Sugerencia>,
+    pub explicacion: Vec<String>,
+    pub metrica: Option<Metrica>,
+    pub ordenamiento: Option<Ordenamiento>,
+}
+
 pub enum TableData {
     Standard(Vec<TneaDisplay>),
     Rrf(Vec<ReRankDisplay>),
+    Convex(Vec<ConvexDisplay>),
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct TneaDisplay {
+    /// Id del registro en `tnea`.
+    pub id: u64,
     email: String,
     pub edad: u64,
     pub sexo: Sexo,
-    template: String,
+    pub provincia: String,
+    pub template: String,
     pub score: f32,
     match_type: String,
+    /// Aporte de cada columna de `fts_tnea` al puntaje.
+    desglose: String,
+    /// Puntaje de FTS y distancia de la búsqueda semántica, en las estrategias que combinan
+    /// ambas. Son `None` si esa búsqueda no encontró al registro.
+    pub fts_score: Option<f32>,
+    pub vec_distance: Option<f32>,
 }
 
 impl TneaDisplay {
@@ -86,31 +155,72 @@ impl TneaDisplay {
         match_type: String,
     ) -> Self {
         Self {
+            id: 0,
             email,
             edad,
             sexo,
+            provincia: String::new(),
             template,
             score,
             match_type,
+            desglose: String::new(),
+            fts_score: None,
+            vec_distance: None,
         }
     }
+
+    #[must_use]
+    pub fn with_desglose(mut self, desglose: String) -> Self {
+        self.desglose = desglose;
+        self
+    }
+
+    #[must_use]
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    #[must_use]
+    pub fn with_provincia(mut self, provincia: String) -> Self {
+        self.provincia = provincia;
+        self
+    }
+
+    #[must_use]
+    pub fn with_puntajes(mut self, fts_score: Option<f32>, vec_distance: Option<f32>) -> Self {
+        self.fts_score = fts_score;
+        self.vec_distance = vec_distance;
+        self
+    }
+
+    fn tiene_puntajes(&self) -> bool {
+        self.fts_score.is_some() || self.vec_distance.is_some()
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct ReRankDisplay {
-    template: String,
+    /// Id del registro en `tnea`.
+    pub id: u64,
+    pub template: String,
     email: String,
     pub edad: u64,
     pub sexo: Sexo,
-    fts_rank: i64,
+    pub provincia: String,
+    /// Posición en cada búsqueda, 0 si esa búsqueda no encontró al registro.
+    pub fts_rank: i64,
     vec_rank: i64,
     pub combined_rank: f32,
-    vec_score: f32,
+    pub vec_score: f32,
     fts_score: f32,
+    /// Aporte de cada columna de `fts_tnea` al puntaje.
+    desglose: String,
 }
 
 impl ReRankDisplay {
     #[must_use]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         template: String,
         email: String,
@@ -123,21 +233,178 @@ impl ReRankDisplay {
         fts_score: f32,
     ) -> Self {
         Self {
+            id: 0,
             template,
             email,
             edad,
             sexo,
+            provincia: String::new(),
             fts_rank,
             vec_rank,
             combined_rank,
             vec_score,
             fts_score,
+            desglose: String::new(),
         }
     }
+
+    #[must_use]
+    pub fn with_desglose(mut self, desglose: String) -> Self {
+        self.desglose = desglose;
+        self
+    }
+
+    #[must_use]
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    #[must_use]
+    pub fn with_provincia(mut self, provincia: String) -> Self {
+        self.provincia = provincia;
+        self
+    }
+}
+
+/// Un resultado de la combinación convexa, con los puntajes originales de cada búsqueda y los
+/// normalizados. Los puntajes son `None` si la búsqueda no encontró al registro.
+#[derive(Debug, Clone, Default)]
+pub struct ConvexDisplay {
+    /// Id del registro en `tnea`.
+    pub id: u64,
+    pub template: String,
+    email: String,
+    pub edad: u64,
+    pub sexo: Sexo,
+    pub provincia: String,
+    pub fts_score: Option<f32>,
+    pub vec_distance: Option<f32>,
+    fts_norm: f32,
+    vec_norm: f32,
+    pub combined: f32,
+    /// Aporte de cada columna de `fts_tnea` al puntaje.
+    desglose: String,
+}
+
+impl ConvexDisplay {
+    #[must_use]
+    pub fn new(
+        template: String,
+        email: String,
+        edad: u64,
+        sexo: Sexo,
+        fts_score: Option<f32>,
+        vec_distance: Option<f32>,
+        desglose: String,
+    ) -> Self {
+        Self {
+            template,
+            email,
+            edad,
+            sexo,
+            fts_score,
+            vec_distance,
+            desglose,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    #[must_use]
+    pub fn with_provincia(mut self, provincia: String) -> Self {
+        self.provincia = provincia;
+        self
+    }
+
+    /// Guarda los puntajes normalizados y calcula su combinación con los pesos dados.
+    #[must_use]
+    pub fn with_normalizados(
+        mut self,
+        fts_norm: f32,
+        vec_norm: f32,
+        weight_fts: f32,
+        weight_vec: f32,
+    ) -> Self {
+        self.fts_norm = fts_norm;
+        self.vec_norm = vec_norm;
+        self.combined = weight_fts * fts_norm + weight_vec * vec_norm;
+        self
+    }
+}
+
+impl Ordenable for TneaDisplay {
+    fn edad(&self) -> u64 {
+        self.edad
+    }
+
+    fn email(&self) -> &str {
+        &self.email
+    }
+
+    fn provincia(&self) -> &str {
+        &self.provincia
+    }
+}
+
+impl Ordenable for ReRankDisplay {
+    fn edad(&self) -> u64 {
+        self.edad
+    }
+
+    fn email(&self) -> &str {
+        &self.email
+    }
+
+    fn provincia(&self) -> &str {
+        &self.provincia
+    }
+}
+
+impl Ordenable for ConvexDisplay {
+    fn edad(&self) -> u64 {
+        self.edad
+    }
+
+    fn email(&self) -> &str {
+        &self.email
+    }
+
+    fn provincia(&self) -> &str {
+        &self.provincia
+    }
+}
+
+/// Un resultado que se puede descartar con los filtros de sexo y edad de la búsqueda.
+pub trait Filtrable: Ordenable {
+    fn sexo(&self) -> &Sexo;
+}
+
+impl Filtrable for TneaDisplay {
+    fn sexo(&self) -> &Sexo {
+        &self.sexo
+    }
+}
+
+impl Filtrable for ReRankDisplay {
+    fn sexo(&self) -> &Sexo {
+        &self.sexo
+    }
+}
+
+impl Filtrable for ConvexDisplay {
+    fn sexo(&self) -> &Sexo {
+        &self.sexo
+    }
 }
 
 // El dataset solamente distingue entre estos dos.
-#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
 pub enum Sexo {
     #[default]
     U,
@@ -169,6 +436,15 @@ impl Display for Sexo {
     }
 }
 
+/// Un enlace a `/search` con otra búsqueda o estrategia y los mismos parámetros que la búsqueda
+/// original.
+#[derive(Debug, Clone)]
+pub struct Sugerencia {
+    pub texto: String,
+    pub href: String,
+    pub motivo: String,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Historial {
     pub id: u64,
@@ -39,6 +39,14 @@ where
     })
 }
 
+pub fn clean_html(str: &str) -> String {
+    if ammonia::is_html(str) {
+        ammonia::clean(str)
+    } else {
+        str.to_string()
+    }
+}
+
 pub trait RegistroSQLITE {}
 
 impl RegistroSQLITE for TneaData {}